@@ -0,0 +1,172 @@
+//! Text rendering of a [`World`] for debugging layouts from the terminal.
+//!
+//! Every tile is drawn as a fixed-width cell made of the belt direction arrow,
+//! a letter for the belt tier and the number of items on the left and right lane:
+//!
+//! ```text
+//! >Y20>Y00.Y00
+//! ```
+
+use std::fmt::Write;
+
+use super::{BeltType, Coordinate, Direction, SingleBelt, SingleBeltLane, World};
+
+/// Width in characters of a single rendered tile
+const CELL_WIDTH: usize = 4;
+
+/// Options controlling how a world is rendered
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsciiOptions {
+    /// Colour each belt with the ANSI colour of its tier
+    pub color: bool,
+    /// Tile to highlight with inverse video. The grid is extended to include it.
+    pub cursor: Option<Coordinate>,
+}
+
+const fn arrow(direction: Option<Direction>) -> char {
+    match direction {
+        Some(Direction::North) => '^',
+        Some(Direction::South) => 'v',
+        Some(Direction::East) => '>',
+        Some(Direction::West) => '<',
+        // End of a line, the belt does not feed into anything
+        None => '.',
+    }
+}
+
+/// Letter named after the colour the tier has in game
+const fn tier_letter(belt_type: BeltType) -> char {
+    match belt_type {
+        BeltType::Regular => 'Y',
+        BeltType::Fast => 'R',
+        BeltType::Express => 'B',
+        BeltType::Turbo => 'G',
+        // Modded tiers have no colour of their own
+        BeltType::Modded { .. } => 'M',
+    }
+}
+
+const fn tier_ansi_color(belt_type: BeltType) -> &'static str {
+    match belt_type {
+        BeltType::Regular => "\x1b[33m",
+        BeltType::Fast => "\x1b[31m",
+        BeltType::Express => "\x1b[34m",
+        BeltType::Turbo => "\x1b[32m",
+        BeltType::Modded { .. } => "\x1b[35m",
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_INVERSE: &str = "\x1b[7m";
+
+fn lane_fill(lane: &SingleBeltLane) -> char {
+    let count = lane.item_count();
+    u32::try_from(count)
+        .ok()
+        .and_then(|count| char::from_digit(count, 10))
+        .unwrap_or('+')
+}
+
+fn render_belt(out: &mut String, belt: &SingleBelt, options: AsciiOptions) {
+    // Both lanes of a belt always share the same tier
+    let belt_type = belt.left_lane.belt_type;
+    if options.color {
+        out.push_str(tier_ansi_color(belt_type));
+    }
+    out.push(arrow(belt.direction()));
+    out.push(tier_letter(belt_type));
+    out.push(lane_fill(&belt.left_lane));
+    out.push(lane_fill(&belt.right_lane));
+    if options.color {
+        out.push_str(ANSI_RESET);
+    }
+}
+
+/// Renders all belts of the world as a grid, one line per row of tiles.
+/// Rows go from north to south and columns from west to east, covering
+/// the bounding box of all placed belts. Empty tiles are left blank.
+/// The cursor is always drawn with ANSI escapes, even without `color`.
+pub fn render_world(world: &World, options: AsciiOptions) -> String {
+    let mut out = String::new();
+    let bounds = match (world.bounds(), options.cursor) {
+        (Some((min, max)), Some(cursor)) => Some((
+            Coordinate::new(min.x.min(cursor.x), min.y.min(cursor.y)),
+            Coordinate::new(max.x.max(cursor.x), max.y.max(cursor.y)),
+        )),
+        (bounds, cursor) => bounds.or_else(|| cursor.map(|cursor| (cursor, cursor))),
+    };
+    let Some((min, max)) = bounds else {
+        return out;
+    };
+
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let coord = Coordinate::new(x, y);
+            let highlighted = options.cursor == Some(coord);
+            if highlighted {
+                out.push_str(ANSI_INVERSE);
+            }
+            match world.belts.get(&coord) {
+                Some(belt) => render_belt(&mut out, belt, options),
+                None => out.push_str(&" ".repeat(CELL_WIDTH)),
+            }
+            if highlighted {
+                out.push_str(ANSI_RESET);
+            }
+        }
+        // Trailing blanks only add noise when copying output into bug reports
+        let trimmed = out.trim_end_matches(' ').len();
+        out.truncate(trimmed);
+        out.push('\n');
+    }
+    out
+}
+
+/// Renders a single lane at full resolution, one character per position.
+/// Items are drawn at their position as the base 36 digit of their id
+/// (or `#` for ids that do not fit in one digit), free positions as `.`.
+/// A ruler line marking every 64 positions is appended to make gaps easy to read.
+#[allow(dead_code)]
+pub fn render_lane(lane: &SingleBeltLane) -> String {
+    let mut cells: Vec<char> = vec!['.'; lane.length as usize];
+    for (item, pos) in lane.items.iter().flatten() {
+        let symbol = u32::try_from(item.get())
+            .ok()
+            .and_then(|id| char::from_digit(id, 36))
+            .unwrap_or('#');
+        if let Some(cell) = cells.get_mut(*pos as usize) {
+            *cell = symbol;
+        }
+    }
+
+    let mut out: String = cells.into_iter().collect();
+    out.push('\n');
+    for pos in 0..lane.length {
+        out.push(if pos % 64 == 0 { '|' } else { ' ' });
+    }
+    let trimmed = out.trim_end_matches(' ').len();
+    out.truncate(trimmed);
+    out.push('\n');
+    out
+}
+
+/// Renders one lane of the belt at `coord`, see [`render_lane`].
+/// Returns `None` if there is no belt at that coordinate.
+#[allow(dead_code)]
+pub fn render_lane_at(world: &World, coord: Coordinate, is_left: bool) -> Option<String> {
+    let belt = world.belts.get(&coord)?;
+    let lane = if is_left {
+        &belt.left_lane
+    } else {
+        &belt.right_lane
+    };
+    let mut out = String::new();
+    let side = if is_left { "left" } else { "right" };
+    // Writing into a String cannot fail
+    let _ = writeln!(out, "({}, {}) {side} lane:", coord.x, coord.y);
+    out.push_str(&render_lane(lane));
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,154 @@
+use super::*;
+use std::num::NonZeroUsize;
+
+fn item(id: usize) -> crate::Item {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+#[test]
+fn test_render_empty_world() {
+    let world = World::new();
+    assert_eq!(render_world(&world, AsciiOptions::default()), "");
+}
+
+#[test]
+fn test_render_line_of_belts() {
+    let mut world = World::new();
+    let coord1 = Coordinate::new(0, 0);
+    let coord2 = Coordinate::new(1, 0);
+    let coord3 = Coordinate::new(2, 0);
+
+    world.add_belt(SingleBelt::new(coord3, BeltType::Fast, None, None));
+    world.add_belt(SingleBelt::new(
+        coord2,
+        BeltType::Regular,
+        Some(coord3),
+        Some(coord3),
+    ));
+    let mut belt1 = SingleBelt::new(coord1, BeltType::Regular, Some(coord2), Some(coord2));
+    belt1.left_lane.items[0] = Some((item(1), 20));
+    belt1.left_lane.items[1] = Some((item(2), 160));
+    belt1.right_lane.items[0] = Some((item(3), 0));
+    world.add_belt(belt1);
+
+    assert_eq!(
+        render_world(&world, AsciiOptions::default()),
+        ">Y21>Y00.R00\n"
+    );
+}
+
+#[test]
+fn test_render_grid_layout_and_gaps() {
+    let mut world = World::new();
+    // A belt going north with an empty tile to its right, and one further south-east
+    let bottom = Coordinate::new(0, 1);
+    let top = Coordinate::new(0, 0);
+    world.add_belt(SingleBelt::new(top, BeltType::Express, None, None));
+    world.add_belt(SingleBelt::new(
+        bottom,
+        BeltType::Express,
+        Some(top),
+        Some(top),
+    ));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(2, 2),
+        BeltType::Turbo,
+        Some(Coordinate::new(1, 2)),
+        None,
+    ));
+
+    assert_eq!(
+        render_world(&world, AsciiOptions::default()),
+        ".B00\n^B00\n        <G00\n"
+    );
+}
+
+#[test]
+fn test_render_with_color() {
+    let mut world = World::new();
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(0, 0),
+        BeltType::Fast,
+        None,
+        None,
+    ));
+    let rendered = render_world(
+        &world,
+        AsciiOptions {
+            color: true,
+            ..AsciiOptions::default()
+        },
+    );
+    assert_eq!(rendered, "\x1b[31m.R00\x1b[0m\n");
+}
+
+#[test]
+fn test_render_lane_full_resolution() {
+    let mut lane = SingleBeltLane::new(BeltType::Regular, None);
+    lane.items[0] = Some((item(1), 0));
+    lane.items[1] = Some((item(11), 64));
+    lane.items[2] = Some((item(40), 255));
+
+    let rendered = render_lane(&lane);
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].chars().count(), 256);
+    assert_eq!(lines[0].chars().next(), Some('1'));
+    assert_eq!(lines[0].chars().nth(64), Some('b'));
+    assert_eq!(lines[0].chars().nth(255), Some('#'));
+    assert_eq!(lines[0].chars().filter(|c| *c != '.').count(), 3);
+    assert_eq!(
+        lines[1]
+            .match_indices('|')
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>(),
+        vec![0, 64, 128, 192]
+    );
+}
+
+#[test]
+fn test_render_lane_at_missing_belt() {
+    let world = World::new();
+    assert!(render_lane_at(&world, Coordinate::new(0, 0), true).is_none());
+}
+
+#[test]
+fn test_belt_direction_from_next_lane() {
+    let coord = Coordinate::new(3, 3);
+    let belt = SingleBelt::new(
+        coord,
+        BeltType::Regular,
+        Some(coord.neighbor(Direction::West)),
+        None,
+    );
+    assert_eq!(belt.direction(), Some(Direction::West));
+
+    let end = SingleBelt::new(coord, BeltType::Regular, None, None);
+    assert_eq!(end.direction(), None);
+}
+
+#[test]
+fn test_render_cursor_extends_grid() {
+    let mut world = World::new();
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(0, 0),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    let options = AsciiOptions {
+        cursor: Some(Coordinate::new(1, 1)),
+        ..AsciiOptions::default()
+    };
+    assert_eq!(
+        render_world(&world, options),
+        ".Y00\n    \x1b[7m    \x1b[0m\n"
+    );
+
+    // The cursor alone is enough to draw a grid
+    let options = AsciiOptions {
+        cursor: Some(Coordinate::new(0, 0)),
+        ..AsciiOptions::default()
+    };
+    assert_eq!(render_world(&World::new(), options), "\x1b[7m    \x1b[0m\n");
+}
@@ -0,0 +1,64 @@
+//! Belt tiers added by mods, loaded from a plain text list.
+//!
+//! Every line names a tier and how many positions its lanes move items per tick, e.g.
+//! `hyper-belt 45`. Blank lines and lines starting with `#` are skipped. Unlike the
+//! tiers of the base game, speeds need not be multiples of 8 and may be more than an
+//! item spacing per tick. Items moving past the end of a belt carry on over as many
+//! belts as their speed takes them, going round a loop of belts at most once a tick.
+
+use super::BeltType;
+
+/// Names and speeds of modded belt tiers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BeltTiers {
+    /// Name and positions per tick of every tier, in the order they were listed
+    tiers: Vec<(String, u32)>,
+}
+
+impl BeltTiers {
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let mut tiers = Self::default();
+        for (index, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let number = index + 1;
+            let mut words = line.split_whitespace();
+            let (Some(name), Some(speed), None) = (words.next(), words.next(), words.next()) else {
+                return Err(format!(
+                    "Line {number}: expected `<name> <positions per tick>`"
+                ));
+            };
+            let positions_per_tick = speed
+                .parse()
+                .ok()
+                .ok_or_else(|| format!("Line {number}: invalid speed `{speed}`"))?;
+            if positions_per_tick == 0 {
+                return Err(format!("Line {number}: speed `{speed}` is not positive"));
+            }
+            if tiers.get(name).is_some() {
+                return Err(format!("Line {number}: tier `{name}` is listed twice"));
+            }
+            tiers.tiers.push((name.to_string(), positions_per_tick));
+        }
+        Ok(tiers)
+    }
+
+    /// The belt type of the tier called `name`
+    pub fn get(&self, name: &str) -> Option<BeltType> {
+        self.tiers
+            .iter()
+            .find(|(tier, _)| tier == name)
+            .map(|&(_, positions_per_tick)| BeltType::Modded { positions_per_tick })
+    }
+
+    /// Names of all tiers, in the order they were listed
+    #[allow(dead_code)]
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tiers.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,48 @@
+use super::*;
+
+#[test]
+fn test_parse_tiers() {
+    let tiers = BeltTiers::parse(
+        "# Belt speed mod\n\
+         hyper-belt 45\n\
+         \n\
+         warp-belt   100\n\
+         light-belt 5000\n",
+    )
+    .expect("valid tiers");
+    assert_eq!(
+        tiers.names().collect::<Vec<_>>(),
+        vec!["hyper-belt", "warp-belt", "light-belt"]
+    );
+    assert_eq!(
+        tiers.get("hyper-belt"),
+        Some(BeltType::Modded {
+            positions_per_tick: 45
+        })
+    );
+    assert_eq!(tiers.get("transport-belt"), None);
+}
+
+#[test]
+fn test_parse_rejects_bad_lines() {
+    assert_eq!(
+        BeltTiers::parse("hyper-belt"),
+        Err("Line 1: expected `<name> <positions per tick>`".to_string())
+    );
+    assert_eq!(
+        BeltTiers::parse("a 8\nb fast"),
+        Err("Line 2: invalid speed `fast`".to_string())
+    );
+    assert_eq!(
+        BeltTiers::parse("a 0"),
+        Err("Line 1: speed `0` is not positive".to_string())
+    );
+    assert_eq!(
+        BeltTiers::parse("a 99999999999"),
+        Err("Line 1: invalid speed `99999999999`".to_string())
+    );
+    assert_eq!(
+        BeltTiers::parse("a 8\na 16"),
+        Err("Line 2: tier `a` is listed twice".to_string())
+    );
+}
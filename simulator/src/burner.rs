@@ -0,0 +1,158 @@
+//! Burner energy sources and the boilers that turn their fuel into steam.
+//!
+//! A burner holds a stack of one kind of fuel and burns one item at a time. Every
+//! entity with a burner asks it for the power it needs each tick and runs at
+//! whatever fraction of that the burner could supply, so an entity out of fuel
+//! stops just like one on an electric network without power.
+
+use super::{Coordinate, Direction, Item, TICKS_PER_SECOND, World, fluid::Fluid};
+
+/// Most fuel items a burner holds
+pub const FUEL_SLOT_SIZE: u32 = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Burner {
+    /// Fuel waiting to be burnt
+    pub fuel: Option<(Item, u32)>,
+    /// Energy of one item of the fuel in the slot, in joules
+    pub fuel_value: f64,
+    /// Energy left in the item currently burning, in joules
+    pub remaining: f64,
+}
+
+#[allow(dead_code)]
+impl Burner {
+    pub const fn new() -> Self {
+        Self {
+            fuel: None,
+            fuel_value: 0.0,
+            remaining: 0.0,
+        }
+    }
+
+    /// Adds up to `count` items of fuel worth `fuel_value` joules each. A burner only
+    /// holds one kind of fuel at a time. Returns how many items were added.
+    pub fn insert_fuel(&mut self, item: Item, count: u32, fuel_value: f64) -> u32 {
+        let stored = match self.fuel {
+            None => 0,
+            Some((fuel, stored)) if fuel == item => stored,
+            Some(_) => return 0,
+        };
+        let added = count.min(FUEL_SLOT_SIZE - stored);
+        if added > 0 {
+            self.fuel = Some((item, stored + added));
+            self.fuel_value = fuel_value;
+        }
+        added
+    }
+
+    /// Whether at least one `item` fits into the fuel slot
+    pub fn accepts_fuel(&self, item: Item) -> bool {
+        match self.fuel {
+            None => true,
+            Some((fuel, stored)) => fuel == item && stored < FUEL_SLOT_SIZE,
+        }
+    }
+
+    /// Number of fuel items waiting in the slot
+    pub fn fuel_count(&self) -> u32 {
+        self.fuel.map_or(0, |(_, count)| count)
+    }
+
+    /// Draws `power` watts for one tick and returns the fraction of it that could be
+    /// supplied, starting on the next fuel item when the current one burns out
+    pub fn burn(&mut self, power: f64) -> f64 {
+        if power <= 0.0 {
+            return 1.0;
+        }
+        let needed = power / TICKS_PER_SECOND;
+        if self.remaining < needed
+            && let Some((item, count)) = self.fuel
+        {
+            self.fuel = (count > 1).then_some((item, count - 1));
+            self.remaining += self.fuel_value;
+        }
+        let supplied = needed.min(self.remaining);
+        self.remaining -= supplied;
+        supplied / needed
+    }
+}
+
+/// Turns water from the pipe on one side into steam on the other side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Boiler {
+    pub water: Fluid,
+    pub steam: Fluid,
+    /// Side the water comes in from
+    pub input_side: Direction,
+    /// Side the steam goes out to
+    pub output_side: Direction,
+    pub burner: Burner,
+}
+
+#[allow(dead_code)]
+impl Boiler {
+    /// Fuel consumption at full load, in watts
+    pub const ENERGY_USAGE: f64 = 1_800_000.0;
+    /// Steam produced at full load, in units per second
+    pub const STEAM_PER_SECOND: f64 = 60.0;
+
+    pub const fn new(
+        water: Fluid,
+        steam: Fluid,
+        input_side: Direction,
+        output_side: Direction,
+    ) -> Self {
+        Self {
+            water,
+            steam,
+            input_side,
+            output_side,
+            burner: Burner::new(),
+        }
+    }
+}
+
+impl World {
+    #[allow(dead_code)]
+    pub fn add_boiler(&mut self, coordinate: Coordinate, boiler: Boiler) {
+        self.boilers.insert(coordinate, boiler);
+    }
+
+    /// Heats as much water into steam as the fuel, the water and the room for steam allow
+    pub fn tick_boilers(&mut self) {
+        let full_load = Boiler::STEAM_PER_SECOND / TICKS_PER_SECOND;
+        let energy_per_unit = Boiler::ENERGY_USAGE / Boiler::STEAM_PER_SECOND;
+        let mut coordinates: Vec<Coordinate> = self.boilers.keys().copied().collect();
+        coordinates.sort_by_key(|c| (c.y, c.x));
+        for coordinate in coordinates {
+            let Some(boiler) = self.boilers.get_mut(&coordinate) else {
+                continue;
+            };
+            let input = coordinate.neighbor(boiler.input_side);
+            let output = coordinate.neighbor(boiler.output_side);
+            let water = match self.fluids.contents(input) {
+                Some((Some(fluid), amount)) if fluid == boiler.water => amount,
+                _ => 0.0,
+            };
+            let wanted = full_load.min(water);
+            if wanted <= 0.0 {
+                continue;
+            }
+            let heated = wanted
+                * boiler
+                    .burner
+                    .burn(Boiler::ENERGY_USAGE * wanted / full_load);
+            let taken = self.fluids.take(input, boiler.water, heated);
+            let added = self.fluids.put(output, boiler.steam, taken);
+            // Water that found no room as steam stays water, and its energy stays unburnt
+            if added < taken {
+                self.fluids.put(input, boiler.water, taken - added);
+                boiler.burner.remaining += (taken - added) * energy_per_unit;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
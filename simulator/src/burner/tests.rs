@@ -0,0 +1,118 @@
+use super::*;
+use crate::{
+    fluid::{FluidEntity, FluidEntityKind},
+    power::{GeneratorKind, PoleKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+const COAL_FUEL_VALUE: f64 = 4_000_000.0;
+
+#[test]
+fn test_burner_holds_one_kind_of_fuel() {
+    let mut burner = Burner::new();
+    assert_eq!(burner.insert_fuel(id(1), 30, COAL_FUEL_VALUE), 30);
+    assert_eq!(burner.insert_fuel(id(2), 5, 2_000_000.0), 0);
+    assert_eq!(burner.insert_fuel(id(1), 30, COAL_FUEL_VALUE), 20);
+    assert_eq!(burner.fuel_count(), FUEL_SLOT_SIZE);
+}
+
+#[test]
+fn test_burner_burns_one_item_at_a_time() {
+    let mut burner = Burner::new();
+    assert!(burner.burn(90_000.0).abs() < 1e-9);
+    burner.insert_fuel(id(1), 2, COAL_FUEL_VALUE);
+
+    // 90 kW is 1500 J per tick, so one coal lasts 2666 full ticks
+    assert!((burner.burn(90_000.0) - 1.0).abs() < 1e-9);
+    assert_eq!(burner.fuel_count(), 1);
+    for _ in 1..2666 {
+        assert!((burner.burn(90_000.0) - 1.0).abs() < 1e-9);
+    }
+    assert_eq!(burner.fuel_count(), 1);
+    // The rest of the first item and the start of the second one, 2667 ticks in total
+    assert!((burner.burn(90_000.0) - 1.0).abs() < 1e-9);
+    assert_eq!(burner.fuel, None);
+    assert!((burner.remaining - 3_999_500.0).abs() < 1e-6);
+    // Idle entities burn nothing
+    assert!((burner.burn(0.0) - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_boiler_powers_steam_engine() {
+    let (water, steam) = (id(1), id(2));
+    let mut world = World::new();
+    world.add_fluid_entity(
+        Coordinate::new(-2, 0),
+        FluidEntity::new(FluidEntityKind::OffshorePump {
+            direction: Direction::East,
+            fluid: water,
+        }),
+    );
+    world.add_fluid_entity(
+        Coordinate::new(-1, 0),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+    let mut boiler = Boiler::new(water, steam, Direction::West, Direction::East);
+    boiler.burner.insert_fuel(id(3), 10, COAL_FUEL_VALUE);
+    world.add_boiler(Coordinate::new(0, 0), boiler);
+    world.add_fluid_entity(
+        Coordinate::new(1, 0),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+    world.power.generators.insert(
+        Coordinate::new(2, 0),
+        GeneratorKind::SteamEngine {
+            steam,
+            input_side: Direction::West,
+        },
+    );
+    world.power.add_pole(Coordinate::new(2, 1), PoleKind::Small);
+
+    // Without consumers the steam piles up in the pipe
+    for _ in 0..61 {
+        world.tick();
+    }
+    let (fluid, amount) = world
+        .fluids
+        .contents(Coordinate::new(1, 0))
+        .expect("pipe exists");
+    assert_eq!(fluid, Some(steam));
+    assert!((amount - 60.0).abs() < 1e-6);
+    // 1.8 MW for one second
+    let burner = world.boilers[&Coordinate::new(0, 0)].burner;
+    assert_eq!(burner.fuel_count(), 9);
+    assert!((burner.remaining - (COAL_FUEL_VALUE - 1_800_000.0)).abs() < 1e-3);
+    assert!((world.power.stats[0].production_capacity - 900_000.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_boiler_without_fuel_makes_no_steam() {
+    let (water, steam) = (id(1), id(2));
+    let mut world = World::new();
+    let mut tank = FluidEntity::new(FluidEntityKind::StorageTank);
+    tank.fluidbox.insert(water, 1000.0);
+    world.add_fluid_entity(Coordinate::new(-1, 0), tank);
+    world.add_boiler(
+        Coordinate::new(0, 0),
+        Boiler::new(water, steam, Direction::West, Direction::East),
+    );
+    world.add_fluid_entity(
+        Coordinate::new(1, 0),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+    for _ in 0..60 {
+        world.tick();
+    }
+    assert_eq!(
+        world.fluids.contents(Coordinate::new(1, 0)),
+        Some((None, 0.0))
+    );
+    assert_eq!(
+        world.fluids.contents(Coordinate::new(-1, 0)),
+        Some((Some(water), 1000.0))
+    );
+}
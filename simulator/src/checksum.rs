@@ -0,0 +1,354 @@
+//! Stable checksums of the whole world.
+//!
+//! The checksum covers everything that changes while ticking: items and their
+//! positions on every lane, the inventories and progress of machines, inserters and
+//! drills, fuel, fluids and their flows, trains, robots and logistic chests,
+//! accumulator charge, daylight and the outputs and belt memory of circuits. Entities
+//! are visited in coordinate order and hashed with FNV-1a over little-endian bytes,
+//! so the value does not depend on hash map order or the platform. Enums, strings and
+//! collections go through their standard `Hash` implementations, which may feed the
+//! hasher differently in another Rust release: compare checksums only between builds
+//! made with the same compiler. Two such builds that simulate the same world the same
+//! way produce the same checksum every tick, which makes it usable for determinism
+//! checks between builds, for comparing two ways of ticking the same world, and as a
+//! cycle detection key.
+
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+};
+
+use super::{
+    Coordinate, SingleBeltLane, World,
+    burner::Burner,
+    circuit::CircuitSystem,
+    fluid::FluidBox,
+    logistic::LogisticSystem,
+    rail::{Train, TrainState, Vehicle},
+};
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// 64 bit FNV-1a. Unlike `DefaultHasher` its output is fixed across releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checksum {
+    state: u64,
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self {
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl Hasher for Checksum {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= u64::from(*byte);
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Always 8 bytes, so 32 and 64 bit platforms agree
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_i16(&mut self, value: i16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_i64(&mut self, value: i64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_i128(&mut self, value: i128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, value: isize) {
+        self.write_i64(value as i64);
+    }
+}
+
+/// Entries of `map` in coordinate order
+fn sorted<T>(map: &HashMap<Coordinate, T>) -> Vec<(&Coordinate, &T)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(c, _)| (c.y, c.x));
+    entries
+}
+
+pub fn hash_f64(value: f64, hasher: &mut Checksum) {
+    value.to_bits().hash(hasher);
+}
+
+fn hash_lane(lane: &SingleBeltLane, hasher: &mut Checksum) {
+    lane.belt_type.hash(hasher);
+    lane.length.hash(hasher);
+    lane.next_lane_coord.map(|c| (c.x, c.y)).hash(hasher);
+    lane.enabled.hash(hasher);
+    lane.accepted.hash(hasher);
+    lane.accepted_items.hash(hasher);
+    // Slots are reused in any order, so only what sits where counts
+    let mut items: Vec<_> = lane
+        .stacks()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, slot)| {
+            slot.map(|(item, pos, height)| (pos, item, lane.quality(index), height))
+        })
+        .collect();
+    items.sort_unstable();
+    Hash::hash_slice(&items, hasher);
+}
+
+pub fn hash_burner(burner: Option<&Burner>, hasher: &mut Checksum) {
+    burner.is_some().hash(hasher);
+    if let Some(burner) = burner {
+        burner.fuel.hash(hasher);
+        hash_f64(burner.fuel_value, hasher);
+        hash_f64(burner.remaining, hasher);
+    }
+}
+
+pub fn hash_fluidbox(fluidbox: &FluidBox, hasher: &mut Checksum) {
+    fluidbox.fluid.hash(hasher);
+    hash_f64(fluidbox.amount, hasher);
+    hash_f64(fluidbox.capacity, hasher);
+}
+
+fn hash_train(train: &Train, hasher: &mut Checksum) {
+    for vehicle in &train.vehicles {
+        match vehicle {
+            Vehicle::Locomotive { burner } => {
+                0u8.hash(hasher);
+                hash_burner(Some(burner), hasher);
+            }
+            Vehicle::CargoWagon { items } => {
+                1u8.hash(hasher);
+                items.hash(hasher);
+            }
+            Vehicle::FluidWagon { fluidbox } => {
+                2u8.hash(hasher);
+                hash_fluidbox(fluidbox, hasher);
+            }
+        }
+    }
+    train.current.hash(hasher);
+    for record in &train.temporary {
+        record.station.hash(hasher);
+    }
+    train.last_activity.hash(hasher);
+    train.cargo_hash.hash(hasher);
+    match train.state {
+        TrainState::Idle => 0u8.hash(hasher),
+        TrainState::Driving => 1u8.hash(hasher),
+        TrainState::NoPath => 2u8.hash(hasher),
+        TrainState::WaitingAtStation { since } => (3u8, since).hash(hasher),
+    }
+    hash_f64(train.speed, hasher);
+    hash_f64(train.front_progress, hasher);
+    train.occupied.hash(hasher);
+    train.route.hash(hasher);
+    train.destination.hash(hasher);
+    train.trips.hash(hasher);
+    train.reserved.hash(hasher);
+    train.waiting_for.hash(hasher);
+}
+
+fn hash_logistics(logistics: &LogisticSystem, hasher: &mut Checksum) {
+    for (coordinate, chest) in sorted(&logistics.chests) {
+        (coordinate.x, coordinate.y).hash(hasher);
+        chest.kind.hash(hasher);
+        chest.items.hash(hasher);
+        chest.requests.hash(hasher);
+        chest.delivered.hash(hasher);
+    }
+    for (coordinate, roboport) in sorted(&logistics.roboports) {
+        (coordinate.x, coordinate.y).hash(hasher);
+        hash_f64(roboport.energy, hasher);
+        roboport.queue.hash(hasher);
+        roboport.charging.hash(hasher);
+    }
+    // Robots keep the order they were added in
+    for robot in &logistics.robots {
+        robot.kind.hash(hasher);
+        hash_f64(robot.position.0, hasher);
+        hash_f64(robot.position.1, hasher);
+        hash_f64(robot.energy, hasher);
+        robot.cargo.hash(hasher);
+        robot.job.hash(hasher);
+        robot.state.hash(hasher);
+        hash_f64(robot.distance, hasher);
+    }
+    for (coordinate, item) in sorted(&logistics.ghosts) {
+        (coordinate.x, coordinate.y, item).hash(hasher);
+    }
+    for (coordinate, item, quality) in &logistics.built {
+        (coordinate.x, coordinate.y, item, quality).hash(hasher);
+    }
+}
+
+fn hash_circuits(circuits: &CircuitSystem, hasher: &mut Checksum) {
+    for (connector, signals) in circuits.last_outputs() {
+        (connector.coordinate.x, connector.coordinate.y).hash(hasher);
+        connector.terminal.hash(hasher);
+        signals.hash(hasher);
+    }
+    for (coordinate, accepted, passed) in circuits.belt_memory() {
+        (coordinate.x, coordinate.y).hash(hasher);
+        accepted.hash(hasher);
+        passed.hash(hasher);
+    }
+}
+
+impl World {
+    /// Stable content hash of the world, see the module documentation
+    #[allow(dead_code)]
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = Checksum::default();
+        self.ticks.hash(&mut hasher);
+
+        for (coordinate, belt) in sorted(&self.belts) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            hash_lane(&belt.left_lane, &mut hasher);
+            hash_lane(&belt.right_lane, &mut hasher);
+        }
+
+        for (coordinate, machine) in sorted(&self.machines) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            machine
+                .recipe
+                .as_ref()
+                .map(|recipe| &recipe.name)
+                .hash(&mut hasher);
+            machine.progress.map(f64::to_bits).hash(&mut hasher);
+            hash_f64(machine.bonus_progress, &mut hasher);
+            machine.crafts.hash(&mut hasher);
+            machine.quality.hash(&mut hasher);
+            machine.recipe_quality.hash(&mut hasher);
+            machine.rng.hash(&mut hasher);
+            machine.modules.hash(&mut hasher);
+            let mut inputs: Vec<_> = machine.item_inputs.iter().collect();
+            inputs.sort_unstable();
+            Hash::hash_slice(&inputs, &mut hasher);
+            let mut outputs: Vec<_> = machine.item_outputs.iter().collect();
+            outputs.sort_unstable();
+            Hash::hash_slice(&outputs, &mut hasher);
+            for port in machine.fluid_inputs.iter().chain(&machine.fluid_outputs) {
+                port.side.hash(&mut hasher);
+                hash_fluidbox(&port.fluidbox, &mut hasher);
+            }
+            hash_burner(machine.burner.as_ref(), &mut hasher);
+        }
+
+        for (coordinate, inserter) in sorted(&self.inserters) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            inserter.kind.hash(&mut hasher);
+            inserter.direction.hash(&mut hasher);
+            inserter.held.hash(&mut hasher);
+            inserter.held_count.hash(&mut hasher);
+            hash_f64(inserter.angle, &mut hasher);
+            inserter.moved.hash(&mut hasher);
+            inserter.quality.hash(&mut hasher);
+            hash_burner(inserter.burner.as_ref(), &mut hasher);
+        }
+
+        for (coordinate, drill) in sorted(&self.drills) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            drill.kind.hash(&mut hasher);
+            drill.direction.hash(&mut hasher);
+            hash_f64(drill.progress, &mut hasher);
+            hash_f64(drill.bonus_progress, &mut hasher);
+            hash_f64(drill.drained, &mut hasher);
+            drill.output.hash(&mut hasher);
+            drill.depleted.hash(&mut hasher);
+            drill.mined.hash(&mut hasher);
+            drill.modules.hash(&mut hasher);
+            drill.quality.hash(&mut hasher);
+            hash_burner(drill.burner.as_ref(), &mut hasher);
+        }
+
+        for (coordinate, resource) in sorted(&self.resources) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            resource.item.hash(&mut hasher);
+            resource.amount.hash(&mut hasher);
+        }
+
+        for (coordinate, boiler) in sorted(&self.boilers) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            hash_burner(Some(&boiler.burner), &mut hasher);
+        }
+
+        for (coordinate, beacon) in sorted(&self.beacons) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            beacon.modules.hash(&mut hasher);
+        }
+
+        for (coordinate, entity) in sorted(&self.fluids.entities) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            hash_fluidbox(&entity.fluidbox, &mut hasher);
+        }
+        for ((from, to), flow) in self.fluids.flows() {
+            (from.x, from.y, to.x, to.y).hash(&mut hasher);
+            hash_f64(flow, &mut hasher);
+        }
+
+        hash_f64(self.power.daylight, &mut hasher);
+
+        for (coordinate, accumulator) in sorted(&self.power.accumulators) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            hash_f64(accumulator.charge, &mut hasher);
+        }
+
+        // Trains keep the order they were added in
+        for train in &self.rails.trains {
+            hash_train(train, &mut hasher);
+        }
+        let mut signals: Vec<_> = self.rails.signals.iter().collect();
+        signals.sort_by_key(|((c, side), _)| (c.y, c.x, *side as u8));
+        for ((coordinate, side), kind) in signals {
+            (coordinate.x, coordinate.y, side, kind).hash(&mut hasher);
+        }
+        for (coordinate, stop) in sorted(&self.rails.stops) {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            stop.arrivals.hash(&mut hasher);
+        }
+
+        hash_logistics(&self.logistics, &mut hasher);
+
+        hash_circuits(&self.circuits, &mut hasher);
+
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests;
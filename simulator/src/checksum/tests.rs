@@ -0,0 +1,158 @@
+use super::*;
+use crate::{
+    BeltType, Direction, SingleBelt,
+    crafting::{CraftingMachine, Recipe},
+    inserter::{Inserter, InserterKind},
+};
+use std::{num::NonZeroUsize, thread};
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+/// A machine feeding a line of belts, with the belts added in the given order
+fn belt_line(order: &[i32]) -> World {
+    let mut world = World::new();
+    for x in order {
+        let next = (*x < 4).then(|| Coordinate::new(x + 1, 0));
+        world.add_belt(SingleBelt::new(
+            Coordinate::new(*x, 0),
+            BeltType::Fast,
+            next,
+            next,
+        ));
+    }
+    world.add_machine(
+        Coordinate::new(0, 2),
+        CraftingMachine::new(1.0).with_recipe(Recipe::new("ore", 0.5).item_product(id(1), 1)),
+    );
+    world.add_inserter(
+        Coordinate::new(0, 1),
+        Inserter::new(InserterKind::Burner, Direction::North),
+    );
+    world
+}
+
+/// Three full belts merging into the belt at (2, 1), each carrying its own item, with
+/// the belts added in the given order
+fn merge(order: &[usize]) -> World {
+    let target = Some(Coordinate::new(2, 1));
+    let sources = [(1, 1), (2, 0), (2, 2)];
+    let mut world = World::new();
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(2, 1),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    for index in order {
+        let (x, y) = sources[*index];
+        let mut belt = SingleBelt::new(Coordinate::new(x, y), BeltType::Regular, target, target);
+        for (slot, position) in [255, 191, 127, 63].into_iter().enumerate() {
+            belt.left_lane.items[slot] = Some((id(index + 1), position));
+            belt.right_lane.items[slot] = Some((id(index + 1), position));
+        }
+        world.add_belt(belt);
+    }
+    world
+}
+
+#[test]
+fn test_fnv_1a_reference_values() {
+    let hash = |bytes: &[u8]| {
+        let mut hasher = Checksum::default();
+        hasher.write(bytes);
+        hasher.finish()
+    };
+    assert_eq!(hash(b""), 0xCBF2_9CE4_8422_2325);
+    assert_eq!(hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+    assert_eq!(hash(b"foobar"), 0x8594_4171_F739_67E8);
+}
+
+#[test]
+fn test_same_world_same_checksum_every_tick() {
+    let mut world = belt_line(&[0, 1, 2, 3, 4]);
+    let mut other = belt_line(&[4, 2, 0, 3, 1]);
+    for _ in 0..300 {
+        assert_eq!(world.checksum(), other.checksum());
+        world.tick();
+        other.tick();
+    }
+}
+
+#[test]
+fn test_worlds_ticked_in_parallel_match_sequential_ticking() {
+    let run = |order: &[i32]| {
+        let mut world = belt_line(order);
+        (0..300)
+            .map(|_| {
+                world.tick();
+                world.checksum()
+            })
+            .collect::<Vec<u64>>()
+    };
+    let sequential = run(&[0, 1, 2, 3, 4]);
+    let orders = [
+        [0, 1, 2, 3, 4],
+        [4, 3, 2, 1, 0],
+        [2, 0, 4, 1, 3],
+        [1, 3, 0, 4, 2],
+    ];
+    thread::scope(|scope| {
+        let handles = orders.map(|order| scope.spawn(move || run(&order)));
+        for handle in handles {
+            assert_eq!(handle.join().expect("Ticking thread panicked"), sequential);
+        }
+    });
+}
+
+#[test]
+fn test_merging_belts_do_not_depend_on_their_order() {
+    let mut world = merge(&[0, 1, 2]);
+    let mut other = merge(&[2, 1, 0]);
+    for _ in 0..100 {
+        assert_eq!(world.checksum(), other.checksum());
+        world.tick();
+        other.tick();
+    }
+}
+
+#[test]
+fn test_checksum_changes_with_items_and_time() {
+    let mut world = belt_line(&[0, 1, 2, 3, 4]);
+    let mut other = belt_line(&[0, 1, 2, 3, 4]);
+    let empty = world.checksum();
+    for (world, item) in [(&mut world, 1), (&mut other, 2)] {
+        if let Some(belt) = world.belts.get_mut(&Coordinate::new(2, 0)) {
+            belt.left_lane.items[0] = Some((id(item), 10));
+        }
+    }
+    assert_ne!(world.checksum(), empty);
+    assert_ne!(world.checksum(), other.checksum());
+    let before = world.checksum();
+    world.tick();
+    assert_ne!(world.checksum(), before);
+}
+
+#[test]
+fn test_slot_order_does_not_matter() {
+    let mut world = belt_line(&[0, 1, 2, 3, 4]);
+    let mut other = belt_line(&[0, 1, 2, 3, 4]);
+    if let Some(belt) = world.belts.get_mut(&Coordinate::new(1, 0)) {
+        belt.right_lane.items[0] = Some((id(1), 0));
+        belt.right_lane.items[1] = Some((id(2), 100));
+    }
+    if let Some(belt) = other.belts.get_mut(&Coordinate::new(1, 0)) {
+        belt.right_lane.items[3] = Some((id(2), 100));
+        belt.right_lane.items[4] = Some((id(1), 0));
+    }
+    assert_eq!(world.checksum(), other.checksum());
+}
+
+#[test]
+fn test_checksum_covers_daylight() {
+    let world = belt_line(&[0, 1, 2, 3, 4]);
+    let mut other = belt_line(&[0, 1, 2, 3, 4]);
+    other.power.daylight = 0.5;
+    assert_ne!(world.checksum(), other.checksum());
+}
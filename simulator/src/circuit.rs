@@ -0,0 +1,714 @@
+//! Circuit networks: red and green wires, combinators and belts connected to them.
+//!
+//! Every connector joined by wires of one color forms a network. Each tick the value
+//! of a network is the sum of what every connector on it output during the previous
+//! tick, so combinators take one tick to react, just like in the game. Entities with
+//! two sides (arithmetic, decider and selector combinators) read from their input
+//! connector and write to their output connector; everything else uses its main one.
+
+use std::collections::{BTreeMap, HashMap, hash_map::Entry};
+
+use super::{Coordinate, Item, SingleBelt, World, slots::Slots};
+
+/// A circuit signal: an item or one of the virtual signals `A` to `Z` and `0` to `9`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(dead_code)]
+pub enum Signal {
+    Item(Item),
+    Virtual(char),
+}
+
+/// Values of all non-zero signals on a network or connector
+pub type Signals = BTreeMap<Signal, i32>;
+
+/// Adds `signals` into `into`, dropping signals that end up at zero
+fn add_signals(into: &mut Signals, signals: &Signals) {
+    for (signal, value) in signals {
+        let sum = into.get(signal).copied().unwrap_or(0).wrapping_add(*value);
+        if sum == 0 {
+            into.remove(signal);
+        } else {
+            into.insert(*signal, sum);
+        }
+    }
+}
+
+fn signal_value(signals: &Signals, signal: Signal) -> i32 {
+    signals.get(&signal).copied().unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum WireColor {
+    Red,
+    Green,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum Terminal {
+    /// The only connector of entities with a single side
+    Main,
+    Input,
+    Output,
+}
+
+/// A place a wire can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Connector {
+    pub coordinate: Coordinate,
+    pub terminal: Terminal,
+}
+
+#[allow(dead_code)]
+impl Connector {
+    pub const fn main(coordinate: Coordinate) -> Self {
+        Self {
+            coordinate,
+            terminal: Terminal::Main,
+        }
+    }
+
+    pub const fn input(coordinate: Coordinate) -> Self {
+        Self {
+            coordinate,
+            terminal: Terminal::Input,
+        }
+    }
+
+    pub const fn output(coordinate: Coordinate) -> Self {
+        Self {
+            coordinate,
+            terminal: Terminal::Output,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wire {
+    pub color: WireColor,
+    pub from: Connector,
+    pub to: Connector,
+}
+
+/// Which signals a condition or combinator looks at or outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum SignalSpec {
+    Signal(Signal),
+    /// True if the condition holds for any signal
+    Anything,
+    /// True if the condition holds for all signals, including when there are none
+    Everything,
+    /// Evaluated for every signal separately
+    Each,
+}
+
+/// Right-hand side of a condition or arithmetic operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Operand {
+    Signal(Signal),
+    Constant(i32),
+}
+
+impl Operand {
+    fn value(self, signals: &Signals) -> i32 {
+        match self {
+            Self::Signal(signal) => signal_value(signals, signal),
+            Self::Constant(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Comparator {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparator {
+    pub const fn compare(self, left: i32, right: i32) -> bool {
+        match self {
+            Self::Less => left < right,
+            Self::LessOrEqual => left <= right,
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+            Self::GreaterOrEqual => left >= right,
+            Self::Greater => left > right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub first: SignalSpec,
+    pub comparator: Comparator,
+    pub second: Operand,
+}
+
+#[allow(dead_code)]
+impl Condition {
+    pub const fn new(first: SignalSpec, comparator: Comparator, second: Operand) -> Self {
+        Self {
+            first,
+            comparator,
+            second,
+        }
+    }
+
+    /// Whether the condition holds for `signals`. `Each` behaves like `Anything`.
+    pub fn holds(&self, signals: &Signals) -> bool {
+        let second = self.second.value(signals);
+        match self.first {
+            SignalSpec::Signal(signal) => self
+                .comparator
+                .compare(signal_value(signals, signal), second),
+            SignalSpec::Anything | SignalSpec::Each => signals
+                .values()
+                .any(|value| self.comparator.compare(*value, second)),
+            SignalSpec::Everything => signals
+                .values()
+                .all(|value| self.comparator.compare(*value, second)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ArithmeticOperation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    ShiftLeft,
+    ShiftRight,
+    And,
+    Or,
+    Xor,
+}
+
+impl ArithmeticOperation {
+    /// Applies the operation with 32-bit wrapping like the game. Division and modulo
+    /// by zero give zero.
+    pub fn apply(self, left: i32, right: i32) -> i32 {
+        match self {
+            Self::Add => left.wrapping_add(right),
+            Self::Subtract => left.wrapping_sub(right),
+            Self::Multiply => left.wrapping_mul(right),
+            Self::Divide => left.checked_div(right).unwrap_or(0),
+            Self::Modulo => left.checked_rem(right).unwrap_or(0),
+            Self::Power => u32::try_from(right).map_or(0, |right| left.wrapping_pow(right)),
+            Self::ShiftLeft => left.wrapping_shl(right.cast_unsigned()),
+            Self::ShiftRight => left.wrapping_shr(right.cast_unsigned()),
+            Self::And => left & right,
+            Self::Or => left | right,
+            Self::Xor => left ^ right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum SelectorMode {
+    /// Outputs the input signal at `index` when sorted by value
+    Select { highest_first: bool, index: u32 },
+    /// Outputs the number of distinct input signals on `output`
+    Count { output: Signal },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Combinator {
+    /// Always outputs the same signals
+    Constant(Signals),
+    /// `left` may be a signal or `Each`, `output` a signal or, with `Each` on the
+    /// left, `Each` to keep every signal separate
+    Arithmetic {
+        left: SignalSpec,
+        operation: ArithmeticOperation,
+        right: Operand,
+        output: SignalSpec,
+    },
+    /// Outputs a signal, `Everything` or (with an `Each` condition) `Each`, either
+    /// as 1 or with the input count when `copy_count` is set
+    Decider {
+        condition: Condition,
+        output: SignalSpec,
+        copy_count: bool,
+    },
+    Selector(SelectorMode),
+}
+
+impl Combinator {
+    /// Output of the combinator for one tick given the signals on its input
+    pub fn evaluate(&self, inputs: &Signals) -> Signals {
+        let mut out = Signals::new();
+        match self {
+            Self::Constant(signals) => add_signals(&mut out, signals),
+            Self::Arithmetic {
+                left,
+                operation,
+                right,
+                output,
+            } => {
+                let right = right.value(inputs);
+                match (left, output) {
+                    (SignalSpec::Signal(left), SignalSpec::Signal(output)) => {
+                        let value = operation.apply(signal_value(inputs, *left), right);
+                        add_signals(&mut out, &Signals::from([(*output, value)]));
+                    }
+                    (SignalSpec::Each, _) => {
+                        for (signal, value) in inputs {
+                            let target = match output {
+                                SignalSpec::Signal(output) => *output,
+                                SignalSpec::Each => *signal,
+                                _ => continue,
+                            };
+                            let value = operation.apply(*value, right);
+                            add_signals(&mut out, &Signals::from([(target, value)]));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Self::Decider {
+                condition,
+                output,
+                copy_count,
+            } => {
+                let count = |value: i32| if *copy_count { value } else { 1 };
+                if condition.first == SignalSpec::Each {
+                    let second = condition.second.value(inputs);
+                    for (signal, value) in inputs {
+                        if !condition.comparator.compare(*value, second) {
+                            continue;
+                        }
+                        let target = match output {
+                            SignalSpec::Signal(output) => *output,
+                            SignalSpec::Each => *signal,
+                            _ => continue,
+                        };
+                        add_signals(&mut out, &Signals::from([(target, count(*value))]));
+                    }
+                } else if condition.holds(inputs) {
+                    match output {
+                        SignalSpec::Signal(output) => {
+                            let value = count(signal_value(inputs, *output));
+                            add_signals(&mut out, &Signals::from([(*output, value)]));
+                        }
+                        SignalSpec::Everything => {
+                            for (signal, value) in inputs {
+                                add_signals(&mut out, &Signals::from([(*signal, count(*value))]));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Self::Selector(SelectorMode::Select {
+                highest_first,
+                index,
+            }) => {
+                let mut sorted: Vec<(Signal, i32)> = inputs
+                    .iter()
+                    .map(|(signal, value)| (*signal, *value))
+                    .collect();
+                sorted.sort_by_key(|(_, value)| *value);
+                if *highest_first {
+                    sorted.reverse();
+                }
+                if let Some((signal, value)) = sorted.get(*index as usize) {
+                    out.insert(*signal, *value);
+                }
+            }
+            Self::Selector(SelectorMode::Count { output }) => {
+                let count = i32::try_from(inputs.len()).unwrap_or(i32::MAX);
+                add_signals(&mut out, &Signals::from([(*output, count)]));
+            }
+        }
+        out
+    }
+
+    /// Connector the combinator reads from
+    const fn input(&self, coordinate: Coordinate) -> Connector {
+        match self {
+            Self::Constant(_) => Connector::main(coordinate),
+            _ => Connector::input(coordinate),
+        }
+    }
+
+    /// Connector the combinator writes to
+    const fn output(&self, coordinate: Coordinate) -> Connector {
+        match self {
+            Self::Constant(_) => Connector::main(coordinate),
+            _ => Connector::output(coordinate),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum BeltReadMode {
+    /// Outputs items for one tick when they enter the belt
+    Pulse,
+    /// Outputs all items currently on the belt
+    Hold,
+    /// Outputs all items on the whole transport line the belt is part of
+    HoldAllBelts,
+}
+
+/// Circuit settings of a belt connected to a network
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BeltCircuit {
+    pub read: Option<BeltReadMode>,
+    /// The belt only moves while this condition holds
+    pub enable: Option<Condition>,
+}
+
+/// Items of both lanes of a belt with their stack heights
+type BeltItems = [Slots<Option<(Item, u32, u8)>>; 2];
+
+/// Number of every item accepted onto either lane of a belt so far
+type Accepted = BTreeMap<Item, u64>;
+
+/// All wires, combinators and circuit-connected belts of a world
+#[derive(Default)]
+pub struct CircuitSystem {
+    pub wires: Vec<Wire>,
+    pub combinators: HashMap<Coordinate, Combinator>,
+    pub belts: HashMap<Coordinate, BeltCircuit>,
+    /// What every connector output in the last tick
+    outputs: HashMap<Connector, Signals>,
+    /// Network index of every wired connector, per color
+    network_of: HashMap<(WireColor, Connector), usize>,
+    network_count: usize,
+    dirty: bool,
+    /// Value of every network as seen in the last tick
+    values: Vec<Signals>,
+    /// Items accepted onto every circuit-connected belt up to the last tick, to find
+    /// the items entering it for pulse mode
+    previous_accepted: HashMap<Coordinate, Accepted>,
+    /// Items that entered every circuit-connected belt since it was connected
+    passed: HashMap<Coordinate, Signals>,
+}
+
+#[allow(dead_code)]
+impl CircuitSystem {
+    pub fn connect(&mut self, color: WireColor, from: Connector, to: Connector) {
+        self.wires.push(Wire { color, from, to });
+        self.dirty = true;
+    }
+
+    pub fn add_combinator(&mut self, coordinate: Coordinate, combinator: Combinator) {
+        self.combinators.insert(coordinate, combinator);
+    }
+
+    fn rebuild_networks(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.network_of.clear();
+        self.network_count = 0;
+        for start in 0..self.wires.len() {
+            let color = self.wires[start].color;
+            if self
+                .network_of
+                .contains_key(&(color, self.wires[start].from))
+            {
+                continue;
+            }
+            let network = self.network_count;
+            self.network_count += 1;
+            let mut queue = vec![self.wires[start].from];
+            self.network_of
+                .insert((color, self.wires[start].from), network);
+            while let Some(current) = queue.pop() {
+                for wire in &self.wires {
+                    if wire.color != color {
+                        continue;
+                    }
+                    let other = if wire.from == current {
+                        wire.to
+                    } else if wire.to == current {
+                        wire.from
+                    } else {
+                        continue;
+                    };
+                    if let Entry::Vacant(entry) = self.network_of.entry((color, other)) {
+                        entry.insert(network);
+                        queue.push(other);
+                    }
+                }
+            }
+        }
+        self.dirty = false;
+    }
+
+    /// Signals on the network of `color` at `connector` in the last tick
+    pub fn network_signals(&self, color: WireColor, connector: Connector) -> Signals {
+        self.network_of
+            .get(&(color, connector))
+            .and_then(|network| self.values.get(*network))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Sum of the red and green networks at `connector`, which is what entities read
+    pub fn signals(&self, connector: Connector) -> Signals {
+        let mut signals = self.network_signals(WireColor::Red, connector);
+        add_signals(
+            &mut signals,
+            &self.network_signals(WireColor::Green, connector),
+        );
+        signals
+    }
+
+    /// Sums up the outputs of the last tick into the value of every network
+    fn update_networks(&mut self) {
+        self.rebuild_networks();
+        let mut values = vec![Signals::new(); self.network_count];
+        for (connector, signals) in &self.outputs {
+            for color in [WireColor::Red, WireColor::Green] {
+                if let Some(network) = self.network_of.get(&(color, *connector)) {
+                    add_signals(&mut values[*network], signals);
+                }
+            }
+        }
+        self.values = values;
+    }
+
+    /// What every connector output in the last tick, sorted by connector
+    pub fn last_outputs(&self) -> Vec<(Connector, &Signals)> {
+        let mut outputs: Vec<(Connector, &Signals)> = self
+            .outputs
+            .iter()
+            .map(|(connector, signals)| (*connector, signals))
+            .collect();
+        outputs.sort_by_key(|(c, _)| (c.coordinate.y, c.coordinate.x, c.terminal as u8));
+        outputs
+    }
+
+    /// Every belt the circuit system remembers, in coordinate order, with the items
+    /// accepted onto it up to the last tick and the items that entered it so far
+    pub fn belt_memory(&self) -> Vec<(Coordinate, Option<&Accepted>, Option<&Signals>)> {
+        let mut coordinates: Vec<Coordinate> = self
+            .previous_accepted
+            .keys()
+            .chain(self.passed.keys())
+            .copied()
+            .collect();
+        coordinates.sort_by_key(|c| (c.y, c.x));
+        coordinates.dedup();
+        coordinates
+            .into_iter()
+            .map(|c| (c, self.previous_accepted.get(&c), self.passed.get(&c)))
+            .collect()
+    }
+
+    /// Total of the items that entered the belt at `coordinate` so far, counted
+    /// the same way as pulse reads
+    pub fn items_passed(&self, coordinate: Coordinate) -> Signals {
+        self.passed.get(&coordinate).cloned().unwrap_or_default()
+    }
+}
+
+/// Items on both lanes of a belt
+fn held_items(items: &BeltItems) -> Signals {
+    let mut signals = Signals::new();
+    for (item, _, height) in items.iter().flatten().flatten() {
+        add_signals(
+            &mut signals,
+            &Signals::from([(Signal::Item(*item), i32::from(*height))]),
+        );
+    }
+    signals
+}
+
+/// Items accepted onto either lane of `belt` so far
+fn accepted_items(belt: &SingleBelt) -> Accepted {
+    let mut accepted = Accepted::new();
+    for lane in [&belt.left_lane, &belt.right_lane] {
+        for (item, count) in &lane.accepted_items {
+            *accepted.entry(*item).or_default() += count;
+        }
+    }
+    accepted
+}
+
+/// Items that entered a belt during the tick, from the items accepted onto it before
+/// and after. Lanes count every item they accept, from belts, inserters and drills,
+/// so an item landing where another one just was still counts. A belt seen for the
+/// first time counts everything on it as entering.
+fn entered_items(before: Option<&Accepted>, after: &Accepted, items: &BeltItems) -> Signals {
+    let Some(before) = before else {
+        return held_items(items);
+    };
+    let mut signals = Signals::new();
+    for (item, count) in after {
+        let entered = count.saturating_sub(before.get(item).copied().unwrap_or(0));
+        if entered > 0 {
+            let value = i32::try_from(entered).unwrap_or(i32::MAX);
+            add_signals(&mut signals, &Signals::from([(Signal::Item(*item), value)]));
+        }
+    }
+    signals
+}
+
+/// Every transport line of a set of belts, worked out in one pass.
+/// A line continues into the next belt unless that belt is fed from several
+/// belts, in which case only the one straight behind it belongs to its line.
+pub struct TransportLines {
+    /// Belts of every line, from its first belt to its last
+    lines: Vec<Vec<Coordinate>>,
+    line_of: HashMap<Coordinate, usize>,
+}
+
+impl TransportLines {
+    pub fn new(belts: &HashMap<Coordinate, SingleBelt>) -> Self {
+        let next_of = |coordinate: &Coordinate| {
+            let belt = &belts[coordinate];
+            let next = belt
+                .left_lane
+                .next_lane_coord
+                .or(belt.right_lane.next_lane_coord)?;
+            belts.contains_key(&next).then_some(next)
+        };
+        let mut feeders: HashMap<Coordinate, usize> = HashMap::new();
+        for next in belts.keys().filter_map(next_of) {
+            *feeders.entry(next).or_default() += 1;
+        }
+        // The belt after each belt on its line; at most one belt leads into another
+        let mut following: HashMap<Coordinate, Coordinate> = HashMap::new();
+        for (from, to) in belts.keys().filter_map(|c| Some((*c, next_of(c)?))) {
+            let straight = belts[&to]
+                .direction()
+                .is_some_and(|direction| to.neighbor(direction.opposite()) == from);
+            if feeders[&to] == 1 || straight {
+                following.insert(from, to);
+            }
+        }
+        let led_into: std::collections::HashSet<Coordinate> = following.values().copied().collect();
+
+        let mut coordinates: Vec<Coordinate> = belts.keys().copied().collect();
+        coordinates.sort_by_key(|c| (c.y, c.x));
+        // Lines with a first belt, then loops, which start anywhere
+        let firsts = coordinates.iter().filter(|c| !led_into.contains(c));
+        let mut lines = Self {
+            lines: Vec::new(),
+            line_of: HashMap::new(),
+        };
+        for &first in firsts.chain(&coordinates) {
+            if lines.line_of.contains_key(&first) {
+                continue;
+            }
+            let index = lines.lines.len();
+            let mut line = Vec::new();
+            let mut current = Some(first);
+            while let Some(coordinate) = current {
+                if lines.line_of.insert(coordinate, index).is_some() {
+                    break;
+                }
+                line.push(coordinate);
+                current = following.get(&coordinate).copied();
+            }
+            lines.lines.push(line);
+        }
+        lines
+    }
+
+    /// Index of the line through `coordinate`
+    pub fn line_of(&self, coordinate: Coordinate) -> Option<usize> {
+        self.line_of.get(&coordinate).copied()
+    }
+
+    /// Belts of the line through `coordinate`, from its first belt to its last
+    pub fn line(&self, coordinate: Coordinate) -> &[Coordinate] {
+        self.line_of(coordinate)
+            .map_or(&[], |index| self.lines[index].as_slice())
+    }
+}
+
+impl World {
+    /// Updates every circuit network from the outputs of the last tick, then lets
+    /// combinators and belts react to it. Conditions set here apply from the next tick.
+    pub fn tick_circuits(&mut self) {
+        let circuits = &mut self.circuits;
+        if circuits.wires.is_empty() && circuits.combinators.is_empty() && circuits.belts.is_empty()
+        {
+            return;
+        }
+        circuits.update_networks();
+
+        let mut outputs: HashMap<Connector, Signals> = HashMap::new();
+        for (coordinate, combinator) in &circuits.combinators {
+            let inputs = circuits.signals(combinator.input(*coordinate));
+            let signals = combinator.evaluate(&inputs);
+            add_signals(
+                outputs.entry(combinator.output(*coordinate)).or_default(),
+                &signals,
+            );
+        }
+
+        let mut previous_accepted = HashMap::new();
+        let mut enabled = Vec::new();
+        let mut lines: Option<TransportLines> = None;
+        let mut line_signals: HashMap<Option<usize>, Signals> = HashMap::new();
+        for (coordinate, settings) in &circuits.belts {
+            let Some(belt) = self.belts.get(coordinate) else {
+                continue;
+            };
+            let connector = Connector::main(*coordinate);
+            if let Some(condition) = settings.enable {
+                enabled.push((*coordinate, condition.holds(&circuits.signals(connector))));
+            }
+            let items = [belt.left_lane.stacks(), belt.right_lane.stacks()];
+            let accepted = accepted_items(belt);
+            let before = circuits.previous_accepted.get(coordinate);
+            let entered = entered_items(before, &accepted, &items);
+            add_signals(circuits.passed.entry(*coordinate).or_default(), &entered);
+            let read = match settings.read {
+                None => Signals::new(),
+                Some(BeltReadMode::Pulse) => entered,
+                Some(BeltReadMode::Hold) => held_items(&items),
+                Some(BeltReadMode::HoldAllBelts) => {
+                    // Lines are worked out once per tick and read once per line
+                    let lines = lines.get_or_insert_with(|| TransportLines::new(&self.belts));
+                    let line = lines.line_of(*coordinate);
+                    line_signals
+                        .entry(line)
+                        .or_insert_with(|| {
+                            let mut signals = Signals::new();
+                            for coordinate in lines.line(*coordinate) {
+                                let belt = &self.belts[coordinate];
+                                let items = [belt.left_lane.stacks(), belt.right_lane.stacks()];
+                                add_signals(&mut signals, &held_items(&items));
+                            }
+                            signals
+                        })
+                        .clone()
+                }
+            };
+            add_signals(outputs.entry(connector).or_default(), &read);
+            previous_accepted.insert(*coordinate, accepted);
+        }
+        for (coordinate, enabled) in enabled {
+            if let Some(belt) = self.belts.get_mut(&coordinate) {
+                belt.set_enabled(enabled);
+            }
+        }
+
+        circuits.previous_accepted = previous_accepted;
+        circuits.outputs = outputs;
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,427 @@
+use super::*;
+use crate::{
+    BeltType, Direction, SingleBelt,
+    inserter::{Inserter, InserterKind},
+    logistic::{ChestKind, LogisticChest},
+    power::{GeneratorKind, PoleKind},
+    quality::Quality,
+};
+use std::num::NonZeroUsize;
+
+fn item(id: usize) -> Item {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn signals(values: &[(Signal, i32)]) -> Signals {
+    values.iter().copied().collect()
+}
+
+const A: Signal = Signal::Virtual('A');
+const B: Signal = Signal::Virtual('B');
+
+#[test]
+fn test_arithmetic_each_keeps_signals_apart() {
+    let combinator = Combinator::Arithmetic {
+        left: SignalSpec::Each,
+        operation: ArithmeticOperation::Multiply,
+        right: Operand::Constant(2),
+        output: SignalSpec::Each,
+    };
+    let inputs = signals(&[(A, 3), (B, -4)]);
+    assert_eq!(combinator.evaluate(&inputs), signals(&[(A, 6), (B, -8)]));
+
+    let summed = Combinator::Arithmetic {
+        left: SignalSpec::Each,
+        operation: ArithmeticOperation::Multiply,
+        right: Operand::Constant(2),
+        output: SignalSpec::Signal(A),
+    };
+    assert_eq!(summed.evaluate(&inputs), signals(&[(A, -2)]));
+}
+
+#[test]
+fn test_arithmetic_operations_wrap_like_the_game() {
+    assert_eq!(ArithmeticOperation::Add.apply(i32::MAX, 1), i32::MIN);
+    assert_eq!(ArithmeticOperation::Divide.apply(7, 0), 0);
+    assert_eq!(ArithmeticOperation::Divide.apply(-7, 2), -3);
+    assert_eq!(ArithmeticOperation::Modulo.apply(-7, 2), -1);
+    assert_eq!(ArithmeticOperation::Power.apply(2, 10), 1024);
+    assert_eq!(ArithmeticOperation::ShiftLeft.apply(1, 33), 2);
+}
+
+#[test]
+fn test_decider_outputs() {
+    let inputs = signals(&[(A, 5), (B, 20)]);
+    let condition = Condition::new(SignalSpec::Each, Comparator::Greater, Operand::Constant(10));
+    let each = Combinator::Decider {
+        condition,
+        output: SignalSpec::Each,
+        copy_count: true,
+    };
+    assert_eq!(each.evaluate(&inputs), signals(&[(B, 20)]));
+
+    let everything = Combinator::Decider {
+        condition: Condition::new(SignalSpec::Signal(A), Comparator::Less, Operand::Signal(B)),
+        output: SignalSpec::Everything,
+        copy_count: false,
+    };
+    assert_eq!(everything.evaluate(&inputs), signals(&[(A, 1), (B, 1)]));
+
+    let failing = Combinator::Decider {
+        condition: Condition::new(
+            SignalSpec::Everything,
+            Comparator::Greater,
+            Operand::Constant(10),
+        ),
+        output: SignalSpec::Signal(B),
+        copy_count: true,
+    };
+    assert!(failing.evaluate(&inputs).is_empty());
+    // Everything holds for an empty network, Anything does not
+    assert!(condition_holds(SignalSpec::Everything, &Signals::new()));
+    assert!(!condition_holds(SignalSpec::Anything, &Signals::new()));
+}
+
+fn condition_holds(first: SignalSpec, inputs: &Signals) -> bool {
+    Condition::new(first, Comparator::Equal, Operand::Constant(0)).holds(inputs)
+}
+
+#[test]
+fn test_selector_modes() {
+    let inputs = signals(&[(A, 5), (B, 20), (Signal::Item(item(1)), 7)]);
+    let highest = Combinator::Selector(SelectorMode::Select {
+        highest_first: true,
+        index: 0,
+    });
+    assert_eq!(highest.evaluate(&inputs), signals(&[(B, 20)]));
+    let second_lowest = Combinator::Selector(SelectorMode::Select {
+        highest_first: false,
+        index: 1,
+    });
+    assert_eq!(
+        second_lowest.evaluate(&inputs),
+        signals(&[(Signal::Item(item(1)), 7)])
+    );
+    let count = Combinator::Selector(SelectorMode::Count { output: A });
+    assert_eq!(count.evaluate(&inputs), signals(&[(A, 3)]));
+}
+
+#[test]
+fn test_networks_sum_outputs_with_one_tick_delay() {
+    let mut world = World::new();
+    let constant = Coordinate::new(0, 0);
+    let doubler = Coordinate::new(1, 0);
+    world
+        .circuits
+        .add_combinator(constant, Combinator::Constant(signals(&[(A, 3)])));
+    world.circuits.add_combinator(
+        doubler,
+        Combinator::Arithmetic {
+            left: SignalSpec::Signal(A),
+            operation: ArithmeticOperation::Multiply,
+            right: Operand::Constant(2),
+            output: SignalSpec::Signal(B),
+        },
+    );
+    world.circuits.connect(
+        WireColor::Red,
+        Connector::main(constant),
+        Connector::input(doubler),
+    );
+    // The output feeds a separate green network
+    world.circuits.connect(
+        WireColor::Green,
+        Connector::output(doubler),
+        Connector::main(Coordinate::new(5, 5)),
+    );
+
+    world.tick();
+    assert!(world.circuits.signals(Connector::input(doubler)).is_empty());
+    world.tick();
+    assert_eq!(
+        world.circuits.signals(Connector::input(doubler)),
+        signals(&[(A, 3)])
+    );
+    assert!(
+        world
+            .circuits
+            .signals(Connector::output(doubler))
+            .is_empty()
+    );
+    world.tick();
+    assert_eq!(
+        world
+            .circuits
+            .network_signals(WireColor::Green, Connector::main(Coordinate::new(5, 5))),
+        signals(&[(B, 6)])
+    );
+    // Red and green do not mix
+    assert!(
+        world
+            .circuits
+            .network_signals(WireColor::Red, Connector::output(doubler))
+            .is_empty()
+    );
+}
+
+/// A single belt at (0, 0) feeding an open end at (1, 0), wired to a constant combinator
+fn circuit_belt(settings: BeltCircuit) -> World {
+    let mut world = World::new();
+    let coord = Coordinate::new(0, 0);
+    let next = Coordinate::new(1, 0);
+    world.add_belt(SingleBelt::new(
+        coord,
+        BeltType::Regular,
+        Some(next),
+        Some(next),
+    ));
+    world.add_belt(SingleBelt::new(next, BeltType::Regular, None, None));
+    world.circuits.belts.insert(coord, settings);
+    world.circuits.add_combinator(
+        Coordinate::new(0, 1),
+        Combinator::Constant(signals(&[(A, 1)])),
+    );
+    world.circuits.connect(
+        WireColor::Red,
+        Connector::main(coord),
+        Connector::main(Coordinate::new(0, 1)),
+    );
+    world
+}
+
+#[test]
+fn test_belt_condition_stops_movement() {
+    let mut world = circuit_belt(BeltCircuit {
+        read: None,
+        enable: Some(Condition::new(
+            SignalSpec::Signal(A),
+            Comparator::Equal,
+            Operand::Constant(0),
+        )),
+    });
+    let coord = Coordinate::new(0, 0);
+    world
+        .belts
+        .get_mut(&coord)
+        .expect("belt exists")
+        .left_lane
+        .items[0] = Some((item(1), 0));
+
+    // The constant reaches the belt after a tick, which disables it for the next one
+    world.tick();
+    world.tick();
+    let position = world.belts[&coord].left_lane.items[0].map(|(_, pos)| pos);
+    assert_eq!(position, Some(16));
+    for _ in 0..10 {
+        world.tick();
+    }
+    let position = world.belts[&coord].left_lane.items[0].map(|(_, pos)| pos);
+    assert_eq!(position, Some(16));
+}
+
+#[test]
+fn test_belt_reads_hold_and_pulse() {
+    let coord = Coordinate::new(0, 0);
+    // Items are put on the belt before the first and the third tick. Reads reach the
+    // network a tick later, and a pulse lasts for a single tick.
+    for (mode, expected) in [
+        (BeltReadMode::Hold, [0, 1, 1, 2]),
+        (BeltReadMode::Pulse, [0, 1, 0, 1]),
+    ] {
+        let mut world = circuit_belt(BeltCircuit {
+            read: Some(mode),
+            enable: None,
+        });
+        let mut seen = Vec::new();
+        for tick in 0..4 {
+            if tick % 2 == 0 {
+                let lane = &mut world.belts.get_mut(&coord).expect("belt exists").left_lane;
+                assert!(lane.accept_item(item(1 + tick), 0));
+            }
+            world.tick();
+            let inputs = world.circuits.signals(Connector::main(coord));
+            seen.push(
+                inputs
+                    .iter()
+                    .filter(|(signal, _)| matches!(signal, Signal::Item(_)))
+                    .map(|(_, value)| value)
+                    .sum::<i32>(),
+            );
+        }
+        assert_eq!(seen, expected, "{mode:?}");
+    }
+}
+
+#[test]
+fn test_pulse_counts_every_item_an_inserter_drops() {
+    let coord = Coordinate::new(0, 0);
+    let mut world = circuit_belt(BeltCircuit {
+        read: Some(BeltReadMode::Pulse),
+        enable: None,
+    });
+    world
+        .belts
+        .get_mut(&coord)
+        .expect("belt exists")
+        .set_enabled(false);
+    // The slow inserter takes each item off the stopped belt while the fast one waits
+    // to drop the next in the same tick, into the same slot at the same position
+    world.logistics.add_chest(
+        Coordinate::new(-2, 0),
+        LogisticChest::new(ChestKind::PassiveProvider).with_items(item(1), Quality::Normal, 50),
+    );
+    world.add_inserter(
+        Coordinate::new(-1, 0),
+        Inserter::new(InserterKind::Fast, Direction::East),
+    );
+    world.add_inserter(
+        Coordinate::new(0, -1),
+        Inserter::new(InserterKind::Basic, Direction::North),
+    );
+    world.logistics.add_chest(
+        Coordinate::new(0, -2),
+        LogisticChest::new(ChestKind::Storage),
+    );
+    world
+        .power
+        .add_pole(Coordinate::new(-1, -1), PoleKind::Small);
+    for x in [1, 2] {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, -1), GeneratorKind::SolarPanel);
+    }
+
+    let mut pulses = 0;
+    for _ in 0..1200 {
+        world.tick();
+        let inputs = world.circuits.signals(Connector::main(coord));
+        pulses += inputs.get(&Signal::Item(item(1))).copied().unwrap_or(0);
+    }
+    let belt = &world.belts[&coord];
+    let dropped = belt.left_lane.accepted + belt.right_lane.accepted;
+    assert!(dropped > 10);
+    assert_eq!(u64::try_from(pulses), Ok(dropped));
+}
+
+#[test]
+fn test_transport_line_skips_sideloads() {
+    let mut world = World::new();
+    let east = |x: i32| Some(Coordinate::new(x + 1, 0));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(0, 0),
+        BeltType::Regular,
+        east(0),
+        east(0),
+    ));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(1, 0),
+        BeltType::Regular,
+        east(1),
+        east(1),
+    ));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(2, 0),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    // Sideloads onto the middle belt from the south
+    let north = Some(Coordinate::new(1, 0));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(1, 1),
+        BeltType::Regular,
+        north,
+        north,
+    ));
+
+    let line = vec![
+        Coordinate::new(0, 0),
+        Coordinate::new(1, 0),
+        Coordinate::new(2, 0),
+    ];
+    let lines = TransportLines::new(&world.belts);
+    assert_eq!(lines.line(Coordinate::new(2, 0)), line);
+    assert_eq!(lines.line(Coordinate::new(0, 0)), line);
+    assert_eq!(lines.line(Coordinate::new(1, 1)), [Coordinate::new(1, 1)]);
+    assert_ne!(
+        lines.line_of(Coordinate::new(1, 1)),
+        lines.line_of(Coordinate::new(1, 0))
+    );
+
+    for (x, y) in [(0, 0), (2, 0), (1, 1)] {
+        let belt = world
+            .belts
+            .get_mut(&Coordinate::new(x, y))
+            .expect("belt exists");
+        belt.right_lane.items[0] = Some((item(1), 0));
+    }
+    world.circuits.belts.insert(
+        Coordinate::new(2, 0),
+        BeltCircuit {
+            read: Some(BeltReadMode::HoldAllBelts),
+            enable: None,
+        },
+    );
+    world.circuits.connect(
+        WireColor::Green,
+        Connector::main(Coordinate::new(2, 0)),
+        Connector::main(Coordinate::new(9, 9)),
+    );
+    world.tick();
+    world.tick();
+    assert_eq!(
+        world
+            .circuits
+            .signals(Connector::main(Coordinate::new(9, 9))),
+        signals(&[(Signal::Item(item(1)), 2)])
+    );
+}
+
+#[test]
+fn test_transport_lines_cover_loops() {
+    let mut world = World::new();
+    let corners = [(0, 0), (1, 0), (1, 1), (0, 1)];
+    for (index, (x, y)) in corners.into_iter().enumerate() {
+        let (nx, ny) = corners[(index + 1) % corners.len()];
+        let next = Some(Coordinate::new(nx, ny));
+        world.add_belt(SingleBelt::new(
+            Coordinate::new(x, y),
+            BeltType::Regular,
+            next,
+            next,
+        ));
+    }
+
+    let lines = TransportLines::new(&world.belts);
+    let mut line = lines.line(Coordinate::new(1, 1)).to_vec();
+    line.sort_by_key(|c| (c.y, c.x));
+    assert_eq!(
+        line,
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| Coordinate::new(x, y))
+    );
+}
+
+#[test]
+fn test_items_passed_counts_every_entering_item() {
+    let coord = Coordinate::new(0, 0);
+    let mut world = circuit_belt(BeltCircuit::default());
+    for tick in 0..40 {
+        if tick % 10 == 0 {
+            let lane = &mut world.belts.get_mut(&coord).expect("belt exists").right_lane;
+            assert!(lane.accept_item(item(2), 0));
+        }
+        world.tick();
+    }
+    assert_eq!(
+        world.circuits.items_passed(coord),
+        signals(&[(Signal::Item(item(2)), 4)])
+    );
+    // Items moving along the belt are only counted once
+    assert!(
+        world
+            .circuits
+            .items_passed(Coordinate::new(1, 0))
+            .is_empty()
+    );
+}
@@ -0,0 +1,498 @@
+//! Recipes and crafting machines.
+//!
+//! A machine crafts one recipe at a time: once all ingredients are present it
+//! consumes them, works for `energy / crafting_speed` seconds and then puts the
+//! products into its output inventory and output fluidboxes. Fluid ingredients and
+//! products go through [`FluidPort`]s, which exchange fluid with the pipe on their side.
+//!
+//! Item ingredients all have the machine's recipe quality. The products of each craft
+//! start at that quality and may go up with the machine's quality effect.
+
+use std::collections::HashMap;
+
+use super::{
+    Direction, Item, TICKS_PER_SECOND,
+    burner::Burner,
+    fluid::{Fluid, FluidBox},
+    modules::{Effect, Module},
+    quality::{Quality, QualityRng},
+};
+
+/// Capacity of the fluidbox of a machine's fluid port
+#[allow(dead_code)]
+pub const DEFAULT_PORT_CAPACITY: f64 = 100.0;
+
+/// How many crafts worth of products a machine holds before it stops working
+const OUTPUT_BUFFER_CRAFTS: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipe {
+    pub name: String,
+    /// Crafting time in seconds at crafting speed 1
+    pub energy: f64,
+    pub item_ingredients: Vec<(Item, u32)>,
+    pub fluid_ingredients: Vec<(Fluid, f64)>,
+    pub item_products: Vec<(Item, u32)>,
+    pub fluid_products: Vec<(Fluid, f64)>,
+}
+
+#[allow(dead_code)]
+impl Recipe {
+    pub fn new(name: &str, energy: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            energy,
+            item_ingredients: Vec::new(),
+            fluid_ingredients: Vec::new(),
+            item_products: Vec::new(),
+            fluid_products: Vec::new(),
+        }
+    }
+
+    pub fn item_ingredient(mut self, item: Item, count: u32) -> Self {
+        self.item_ingredients.push((item, count));
+        self
+    }
+
+    pub fn fluid_ingredient(mut self, fluid: Fluid, amount: f64) -> Self {
+        self.fluid_ingredients.push((fluid, amount));
+        self
+    }
+
+    pub fn item_product(mut self, item: Item, count: u32) -> Self {
+        self.item_products.push((item, count));
+        self
+    }
+
+    pub fn fluid_product(mut self, fluid: Fluid, amount: f64) -> Self {
+        self.fluid_products.push((fluid, amount));
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum FurnaceKind {
+    Stone,
+    Steel,
+    Electric,
+}
+
+impl FurnaceKind {
+    pub const fn crafting_speed(self) -> f64 {
+        match self {
+            Self::Stone => 1.0,
+            Self::Steel | Self::Electric => 2.0,
+        }
+    }
+
+    /// Power used while smelting, in watts, from fuel or from the electric network
+    pub const fn energy_usage(self) -> f64 {
+        match self {
+            Self::Stone | Self::Steel => 90_000.0,
+            Self::Electric => 180_000.0,
+        }
+    }
+
+    pub const fn burns_fuel(self) -> bool {
+        !matches!(self, Self::Electric)
+    }
+
+    pub const fn module_slots(self) -> usize {
+        match self {
+            Self::Stone | Self::Steel => 0,
+            Self::Electric => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineStatus {
+    /// Crafting, or about to start the next craft
+    Working,
+    NoRecipe,
+    /// Starved: waiting for ingredients
+    MissingIngredients,
+    /// Blocked: no room for the products of another craft
+    OutputFull,
+}
+
+/// A fluidbox on one side of a machine, connected to the pipe next to that side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FluidPort {
+    pub side: Direction,
+    pub fluidbox: FluidBox,
+}
+
+#[allow(dead_code)]
+impl FluidPort {
+    pub const fn new(side: Direction) -> Self {
+        Self {
+            side,
+            fluidbox: FluidBox::new(DEFAULT_PORT_CAPACITY),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CraftingMachine {
+    pub recipe: Option<Recipe>,
+    pub crafting_speed: f64,
+    /// Seconds of crafting done on the current craft, `None` while idle
+    pub progress: Option<f64>,
+    pub item_inputs: HashMap<Item, u32>,
+    pub item_outputs: HashMap<(Item, Quality), u32>,
+    /// One port per fluid ingredient of the recipe, in the same order
+    pub fluid_inputs: Vec<FluidPort>,
+    /// One port per fluid product of the recipe, in the same order
+    pub fluid_outputs: Vec<FluidPort>,
+    /// Number of finished crafts
+    pub crafts: u64,
+    /// Power drawn while crafting in watts, 0 for machines that need no power
+    pub energy_usage: f64,
+    /// Fuel for machines that burn it instead of using electricity
+    pub burner: Option<Burner>,
+    /// Recipes a furnace picks from by its input item; empty for other machines
+    pub smelting_recipes: Vec<Recipe>,
+    pub module_slots: usize,
+    pub modules: Vec<Module>,
+    /// Total effect of modules and beacons, kept up to date by the world
+    pub effect: Effect,
+    /// Progress towards the next bonus craft from productivity, from 0 to 1
+    pub bonus_progress: f64,
+    /// Quality of the machine itself, which scales its crafting speed
+    pub quality: Quality,
+    /// Quality of the item ingredients and the base quality of the products
+    pub recipe_quality: Quality,
+    pub rng: QualityRng,
+}
+
+#[allow(dead_code)]
+impl CraftingMachine {
+    pub fn new(crafting_speed: f64) -> Self {
+        Self {
+            recipe: None,
+            crafting_speed,
+            progress: None,
+            item_inputs: HashMap::new(),
+            item_outputs: HashMap::new(),
+            fluid_inputs: Vec::new(),
+            fluid_outputs: Vec::new(),
+            crafts: 0,
+            energy_usage: 0.0,
+            burner: None,
+            smelting_recipes: Vec::new(),
+            module_slots: 0,
+            modules: Vec::new(),
+            effect: Effect::NONE,
+            bonus_progress: 0.0,
+            quality: Quality::Normal,
+            recipe_quality: Quality::Normal,
+            rng: QualityRng::default(),
+        }
+    }
+
+    /// A furnace that chooses whichever of `recipes` takes the item it is given
+    pub fn furnace(kind: FurnaceKind, recipes: Vec<Recipe>) -> Self {
+        let mut furnace = Self::new(kind.crafting_speed()).with_energy_usage(kind.energy_usage());
+        if kind.burns_fuel() {
+            furnace.burner = Some(Burner::new());
+        }
+        furnace.smelting_recipes = recipes;
+        furnace.module_slots = kind.module_slots();
+        furnace
+    }
+
+    pub const fn with_module_slots(mut self, slots: usize) -> Self {
+        self.module_slots = slots;
+        self
+    }
+
+    /// Adds a module if a slot is free. The world picks up the new effect in
+    /// [`World::update_effects`](super::World::update_effects).
+    pub fn insert_module(&mut self, module: Module) -> bool {
+        if self.modules.len() >= self.module_slots {
+            return false;
+        }
+        self.modules.push(module);
+        true
+    }
+
+    pub const fn with_quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub const fn with_recipe_quality(mut self, quality: Quality) -> Self {
+        self.recipe_quality = quality;
+        self
+    }
+
+    pub const fn with_energy_usage(mut self, watts: f64) -> Self {
+        self.energy_usage = watts;
+        self
+    }
+
+    /// Power the machine asks for this tick: its full usage while crafting or able
+    /// to start, otherwise the idle drain of 1/30 of it. Burners have no drain.
+    pub fn power_demand(&self) -> f64 {
+        let working = self.progress.is_some()
+            || self
+                .recipe
+                .as_ref()
+                .is_some_and(|recipe| self.can_start(recipe));
+        if working {
+            self.energy_usage * self.effect.consumption_multiplier()
+        } else if self.burner.is_some() {
+            0.0
+        } else {
+            self.energy_usage / 30.0
+        }
+    }
+
+    /// Whether the machine is idle with empty input and output inventories
+    fn is_empty(&self) -> bool {
+        self.progress.is_none()
+            && self.item_inputs.values().all(|count| *count == 0)
+            && self.item_outputs.is_empty()
+    }
+
+    /// The smelting recipe a furnace switches to for `item`. Furnaces only switch
+    /// while empty.
+    fn furnace_recipe_for(&self, item: Item) -> Option<&Recipe> {
+        if !self.is_empty() {
+            return None;
+        }
+        self.smelting_recipes.iter().find(|recipe| {
+            recipe
+                .item_ingredients
+                .iter()
+                .any(|(ingredient, _)| *ingredient == item)
+        })
+    }
+
+    /// Whether an inserter may put `item` into the machine: it has to be an ingredient
+    /// of the recipe in the recipe quality, and at most two crafts worth of it are
+    /// stored. Empty furnaces also accept anything one of their recipes smelts, in
+    /// any quality.
+    pub fn accepts_item(&self, item: Item, quality: Quality) -> bool {
+        if self.furnace_recipe_for(item).is_some() {
+            return true;
+        }
+        quality == self.recipe_quality
+            && self.recipe.as_ref().is_some_and(|recipe| {
+                recipe.item_ingredients.iter().any(|(ingredient, count)| {
+                    *ingredient == item
+                        && self.item_inputs.get(&item).copied().unwrap_or(0) < 2 * count
+                })
+            })
+    }
+
+    pub fn with_recipe(mut self, recipe: Recipe) -> Self {
+        self.recipe = Some(recipe);
+        self
+    }
+
+    pub fn with_fluid_input(mut self, side: Direction) -> Self {
+        self.fluid_inputs.push(FluidPort::new(side));
+        self
+    }
+
+    pub fn with_fluid_output(mut self, side: Direction) -> Self {
+        self.fluid_outputs.push(FluidPort::new(side));
+        self
+    }
+
+    /// Adds items to the input inventory. Furnaces switch to the recipe and quality
+    /// of the item.
+    pub fn insert_item(&mut self, item: Item, quality: Quality, count: u32) {
+        if let Some(recipe) = self.furnace_recipe_for(item).cloned() {
+            if self.recipe.as_ref() != Some(&recipe) {
+                self.item_inputs.clear();
+            }
+            self.recipe = Some(recipe);
+            self.recipe_quality = quality;
+        }
+        *self.item_inputs.entry(item).or_default() += count;
+    }
+
+    /// Removes up to `count` items from the output inventory and returns how many were removed
+    pub fn take_output(&mut self, item: Item, quality: Quality, count: u32) -> u32 {
+        let Some(stored) = self.item_outputs.get_mut(&(item, quality)) else {
+            return 0;
+        };
+        let taken = count.min(*stored);
+        *stored -= taken;
+        if *stored == 0 {
+            self.item_outputs.remove(&(item, quality));
+        }
+        taken
+    }
+
+    /// Whether everything needed for one craft is present and there is room for the products
+    fn can_start(&self, recipe: &Recipe) -> bool {
+        self.has_ingredients(recipe) && self.has_room(recipe)
+    }
+
+    fn has_ingredients(&self, recipe: &Recipe) -> bool {
+        let items_present = recipe
+            .item_ingredients
+            .iter()
+            .all(|(item, count)| self.item_inputs.get(item).copied().unwrap_or(0) >= *count);
+        let fluids_present =
+            recipe
+                .fluid_ingredients
+                .iter()
+                .enumerate()
+                .all(|(i, (fluid, amount))| {
+                    self.fluid_inputs.get(i).is_some_and(|port| {
+                        port.fluidbox.fluid == Some(*fluid) && port.fluidbox.amount >= *amount
+                    })
+                });
+        items_present && fluids_present
+    }
+
+    /// Number of `item` in the outputs, of any quality
+    fn stored_output(&self, item: Item) -> u32 {
+        self.item_outputs
+            .iter()
+            .filter(|((output, _), _)| *output == item)
+            .map(|(_, stored)| stored)
+            .sum()
+    }
+
+    /// Whether the products of one more craft fit into the outputs
+    fn has_room(&self, recipe: &Recipe) -> bool {
+        let room_for_items = recipe
+            .item_products
+            .iter()
+            .all(|(item, count)| self.stored_output(*item) < count * OUTPUT_BUFFER_CRAFTS);
+        room_for_items && self.fluid_products_fit(recipe, self.crafts_due())
+    }
+
+    /// Items stored for every ingredient and product of the recipe, capped where
+    /// inserters stop adding ingredients and where the products stop the machine
+    pub fn buffer_levels(&self) -> Vec<u32> {
+        let Some(recipe) = &self.recipe else {
+            return Vec::new();
+        };
+        let inputs = recipe.item_ingredients.iter().map(|(item, count)| {
+            self.item_inputs
+                .get(item)
+                .copied()
+                .unwrap_or(0)
+                .min(2 * count)
+        });
+        let outputs = recipe
+            .item_products
+            .iter()
+            .map(|(item, count)| self.stored_output(*item).min(count * OUTPUT_BUFFER_CRAFTS));
+        inputs.chain(outputs).collect()
+    }
+
+    /// Crafts worth of products the next craft delivers, counting bonus crafts from
+    /// productivity
+    fn crafts_due(&self) -> f64 {
+        1.0 + (self.bonus_progress + self.effect.productivity + 1e-9).floor()
+    }
+
+    /// Whether the output ports have room for the fluid products of `crafts` crafts
+    fn fluid_products_fit(&self, recipe: &Recipe, crafts: f64) -> bool {
+        recipe
+            .fluid_products
+            .iter()
+            .enumerate()
+            .all(|(i, (fluid, amount))| {
+                self.fluid_outputs.get(i).is_some_and(|port| {
+                    port.fluidbox.accepts(*fluid) && port.fluidbox.free() >= amount * crafts
+                })
+            })
+    }
+
+    /// What the machine is doing at the moment
+    pub fn status(&self) -> MachineStatus {
+        let Some(recipe) = &self.recipe else {
+            return MachineStatus::NoRecipe;
+        };
+        if self.progress.is_some() || self.can_start(recipe) {
+            MachineStatus::Working
+        } else if !self.has_room(recipe) {
+            MachineStatus::OutputFull
+        } else {
+            MachineStatus::MissingIngredients
+        }
+    }
+
+    fn consume_ingredients(&mut self, recipe: &Recipe) {
+        for (item, count) in &recipe.item_ingredients {
+            if let Some(stored) = self.item_inputs.get_mut(item) {
+                *stored -= count;
+            }
+        }
+        for (port, (_, amount)) in self.fluid_inputs.iter_mut().zip(&recipe.fluid_ingredients) {
+            port.fluidbox.remove(*amount);
+        }
+    }
+
+    fn deliver_products(&mut self, recipe: &Recipe) {
+        let quality = self.recipe_quality.roll(self.effect.quality, &mut self.rng);
+        for (item, count) in &recipe.item_products {
+            *self.item_outputs.entry((*item, quality)).or_default() += count;
+        }
+        for (port, (fluid, amount)) in self.fluid_outputs.iter_mut().zip(&recipe.fluid_products) {
+            port.fluidbox.insert(*fluid, *amount);
+        }
+        self.crafts += 1;
+
+        self.bonus_progress += self.effect.productivity;
+        // A bonus craft whose fluids do not fit waits for the next craft instead of
+        // being lost
+        while self.bonus_progress + 1e-9 >= 1.0 && self.fluid_products_fit(recipe, 1.0) {
+            self.bonus_progress -= 1.0;
+            for (item, count) in &recipe.item_products {
+                *self.item_outputs.entry((*item, quality)).or_default() += count;
+            }
+            for (port, (fluid, amount)) in self.fluid_outputs.iter_mut().zip(&recipe.fluid_products)
+            {
+                port.fluidbox.insert(*fluid, *amount);
+            }
+        }
+    }
+
+    /// Advances crafting by one tick
+    pub fn tick(&mut self) {
+        self.tick_powered(1.0);
+    }
+
+    /// Advances crafting by one tick at `satisfaction` (0 to 1) of the usual speed,
+    /// as during a brownout
+    pub fn tick_powered(&mut self, satisfaction: f64) {
+        let Some(recipe) = self.recipe.take() else {
+            return;
+        };
+        if self.progress.is_none() && self.can_start(&recipe) {
+            self.consume_ingredients(&recipe);
+            self.progress = Some(0.0);
+        }
+        if let Some(progress) = self.progress {
+            let speed =
+                self.crafting_speed * self.quality.multiplier() * self.effect.speed_multiplier();
+            let progress = progress + speed * satisfaction / TICKS_PER_SECOND;
+            if progress + 1e-9 >= recipe.energy {
+                self.deliver_products(&recipe);
+                self.progress = None;
+                // Work done past the end of the craft goes into the next one
+                if self.can_start(&recipe) {
+                    self.consume_ingredients(&recipe);
+                    self.progress = Some((progress - recipe.energy).max(0.0));
+                }
+            } else {
+                self.progress = Some(progress);
+            }
+        }
+        self.recipe = Some(recipe);
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,225 @@
+use super::*;
+use crate::{
+    Coordinate, World,
+    fluid::{FluidEntity, FluidEntityKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn gear_recipe() -> Recipe {
+    Recipe::new("iron-gear-wheel", 0.5)
+        .item_ingredient(id(1), 2)
+        .item_product(id(2), 1)
+}
+
+#[test]
+fn test_machine_without_ingredients_stays_idle() {
+    let mut machine = CraftingMachine::new(1.0).with_recipe(gear_recipe());
+    for _ in 0..60 {
+        machine.tick();
+    }
+    assert_eq!(machine.crafts, 0);
+    assert_eq!(machine.progress, None);
+}
+
+#[test]
+fn test_craft_takes_energy_divided_by_speed() {
+    let mut machine = CraftingMachine::new(0.5).with_recipe(gear_recipe());
+    machine.insert_item(id(1), Quality::Normal, 2);
+
+    // 0.5 seconds at speed 0.5 is one second, i.e. 60 ticks
+    for _ in 0..59 {
+        machine.tick();
+    }
+    assert_eq!(machine.crafts, 0);
+    assert_eq!(machine.item_inputs[&id(1)], 0);
+    machine.tick();
+    assert_eq!(machine.crafts, 1);
+    assert_eq!(machine.take_output(id(2), Quality::Normal, 5), 1);
+    assert!(machine.item_outputs.is_empty());
+}
+
+#[test]
+fn test_work_past_the_end_of_a_craft_goes_into_the_next() {
+    let mut machine = CraftingMachine::new(0.7).with_recipe(gear_recipe());
+    machine.insert_item(id(1), Quality::Normal, 20);
+
+    // 0.5 seconds at speed 0.7 is 42.9 ticks, so seven crafts take 300 ticks, not 7 * 43
+    for _ in 0..300 {
+        machine.tick();
+    }
+    assert_eq!(machine.crafts, 7);
+}
+
+#[test]
+fn test_full_output_stops_machine() {
+    let mut machine = CraftingMachine::new(1.0).with_recipe(gear_recipe());
+    machine.insert_item(id(1), Quality::Normal, 100);
+    for _ in 0..60 * 60 {
+        machine.tick();
+    }
+    assert_eq!(machine.crafts, u64::from(OUTPUT_BUFFER_CRAFTS));
+    assert_eq!(machine.item_inputs[&id(1)], 100 - 2 * OUTPUT_BUFFER_CRAFTS);
+}
+
+#[test]
+fn test_bonus_fluid_waits_for_room() {
+    let recipe = Recipe::new("heavy-oil", 1.0)
+        .item_ingredient(id(1), 1)
+        .fluid_product(id(2), 40.0);
+    let mut machine = CraftingMachine::new(1.0)
+        .with_recipe(recipe)
+        .with_fluid_output(Direction::East);
+    machine.effect = Effect {
+        productivity: 0.5,
+        ..Effect::NONE
+    };
+    machine.insert_item(id(1), Quality::Normal, 10);
+
+    // The second craft comes with a bonus craft, 80 in all, but only 60 fit
+    for _ in 0..300 {
+        machine.tick();
+    }
+    assert_eq!(machine.crafts, 1);
+    let output = &mut machine.fluid_outputs[0].fluidbox;
+    assert!((output.amount - 40.0).abs() < 1e-9);
+
+    output.remove(100.0);
+    for _ in 0..60 {
+        machine.tick();
+    }
+    assert_eq!(machine.crafts, 2);
+    assert!((machine.fluid_outputs[0].fluidbox.amount - 80.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_fluid_recipe_needs_matching_port() {
+    let recipe = Recipe::new("lubricant", 1.0)
+        .fluid_ingredient(id(1), 10.0)
+        .fluid_product(id(2), 10.0);
+    let mut machine = CraftingMachine::new(1.0)
+        .with_recipe(recipe)
+        .with_fluid_input(Direction::West);
+    machine.fluid_inputs[0].fluidbox.insert(id(1), 100.0);
+    for _ in 0..120 {
+        machine.tick();
+    }
+    // No output port for the product, so the machine can never start
+    assert_eq!(machine.crafts, 0);
+}
+
+#[test]
+fn test_chemical_plant_fed_by_pipes() {
+    // Offshore pump -> pipe -> plant -> pipe, making "sulfuric acid" from water and items
+    let water = id(1);
+    let acid = id(2);
+    let mut world = World::new();
+    world.add_fluid_entity(
+        Coordinate::new(-2, 0),
+        FluidEntity::new(FluidEntityKind::OffshorePump {
+            direction: Direction::East,
+            fluid: water,
+        }),
+    );
+    world.add_fluid_entity(
+        Coordinate::new(-1, 0),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+    world.add_fluid_entity(
+        Coordinate::new(1, 0),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+
+    let recipe = Recipe::new("sulfuric-acid", 1.0)
+        .item_ingredient(id(10), 1)
+        .fluid_ingredient(water, 100.0)
+        .fluid_product(acid, 50.0);
+    let mut plant = CraftingMachine::new(1.0)
+        .with_recipe(recipe)
+        .with_fluid_input(Direction::West)
+        .with_fluid_output(Direction::East);
+    plant.insert_item(id(10), Quality::Normal, 1);
+    world.add_machine(Coordinate::new(0, 0), plant);
+
+    for _ in 0..120 {
+        world.tick();
+    }
+
+    assert_eq!(world.machines[&Coordinate::new(0, 0)].crafts, 1);
+    let output = world.fluids.entities[&Coordinate::new(1, 0)].fluidbox;
+    assert_eq!(output.fluid, Some(acid));
+    assert!((output.amount - 50.0).abs() < 1e-9);
+}
+
+fn smelting_recipes() -> Vec<Recipe> {
+    vec![
+        Recipe::new("iron-plate", 3.2)
+            .item_ingredient(id(1), 1)
+            .item_product(id(11), 1),
+        Recipe::new("copper-plate", 3.2)
+            .item_ingredient(id(2), 1)
+            .item_product(id(12), 1),
+    ]
+}
+
+#[test]
+fn test_furnace_picks_recipe_from_input() {
+    let mut furnace = CraftingMachine::furnace(FurnaceKind::Electric, smelting_recipes());
+    assert!(furnace.recipe.is_none());
+    assert!(furnace.accepts_item(id(2), Quality::Normal));
+    assert!(!furnace.accepts_item(id(3), Quality::Normal));
+
+    furnace.insert_item(id(2), Quality::Normal, 1);
+    assert_eq!(
+        furnace.recipe.as_ref().map(|r| r.name.as_str()),
+        Some("copper-plate")
+    );
+    // 3.2 seconds at crafting speed 2
+    for _ in 0..96 {
+        furnace.tick();
+    }
+    assert_eq!(furnace.crafts, 1);
+
+    // Plates in the output keep the furnace on its recipe
+    assert!(!furnace.accepts_item(id(1), Quality::Normal));
+    assert_eq!(furnace.take_output(id(12), Quality::Normal, 1), 1);
+    assert!(furnace.accepts_item(id(1), Quality::Normal));
+    furnace.insert_item(id(1), Quality::Normal, 1);
+    assert_eq!(
+        furnace.recipe.as_ref().map(|r| r.name.as_str()),
+        Some("iron-plate")
+    );
+}
+
+#[test]
+fn test_stone_furnace_burns_fuel() {
+    let mut world = World::new();
+    world.add_fuel(id(9), 4_000_000.0);
+    let coordinate = Coordinate::new(0, 0);
+    world.add_machine(
+        coordinate,
+        CraftingMachine::furnace(FurnaceKind::Stone, smelting_recipes()),
+    );
+    assert!(world.insert_into(coordinate, id(1), Quality::Normal));
+    assert!(world.insert_into(coordinate, id(1), Quality::Normal));
+    for _ in 0..600 {
+        world.tick();
+    }
+    // No fuel, no plates
+    assert_eq!(world.machines[&coordinate].crafts, 0);
+
+    // Coal is not a smelting ingredient, so it goes to the burner
+    assert!(world.insert_into(coordinate, id(9), Quality::Normal));
+    for _ in 0..2 * 192 {
+        world.tick();
+    }
+    let furnace = &world.machines[&coordinate];
+    assert_eq!(furnace.crafts, 2);
+    assert_eq!(furnace.burner.map(|burner| burner.fuel_count()), Some(0));
+    // 90 kW for 6.4 seconds, 576 kJ out of a single coal
+    let remaining = furnace.burner.map_or(0.0, |burner| burner.remaining);
+    assert!((remaining - 3_424_000.0).abs() < 1e-3);
+}
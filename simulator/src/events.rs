@@ -0,0 +1,318 @@
+//! Structured events emitted while ticking a [`World`](super::World).
+//!
+//! Events are only collected while at least one subscriber is registered, so an
+//! unobserved world pays nothing for them. Each subscriber has its own buffer and
+//! filter, and drains its events with [`EventLog::take`].
+
+use std::fmt;
+
+use super::{Coordinate, Item, SingleBeltLane};
+
+/// Something that happened to an item during a tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// The item moved forward along its lane
+    Moved {
+        coordinate: Coordinate,
+        is_left: bool,
+        item: Item,
+        /// Items in the stack that moved
+        height: u8,
+        from: u32,
+        to: u32,
+    },
+    /// The item could not move at all this tick
+    Stalled {
+        coordinate: Coordinate,
+        is_left: bool,
+        item: Item,
+        position: u32,
+    },
+    /// The item left the end of its lane and was accepted by the next one
+    Transferred {
+        item: Item,
+        /// Items in the stack that was transferred
+        height: u8,
+        source: Coordinate,
+        source_is_left: bool,
+        target: Coordinate,
+        target_is_left: bool,
+        position: u32,
+    },
+    /// A lane of the target belt had no room for the item
+    TransferRejected {
+        item: Item,
+        source: Coordinate,
+        target: Coordinate,
+        target_is_left: bool,
+        position: u32,
+    },
+    /// The item left its lane but nothing accepted it, so it is gone from the world
+    Dropped {
+        item: Item,
+        /// Items in the stack that was lost
+        height: u8,
+        source: Coordinate,
+        target: Coordinate,
+    },
+}
+
+/// An event together with the tick it happened in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    /// Number of the tick, starting at 1 for the first call to `World::tick`
+    pub tick: u64,
+    pub kind: EventKind,
+}
+
+impl Event {
+    /// The item the event is about
+    pub const fn item(&self) -> Item {
+        match self.kind {
+            EventKind::Moved { item, .. }
+            | EventKind::Stalled { item, .. }
+            | EventKind::Transferred { item, .. }
+            | EventKind::TransferRejected { item, .. }
+            | EventKind::Dropped { item, .. } => item,
+        }
+    }
+
+    /// Whether the event happened at `coordinate`, either as source or as target
+    pub fn involves(&self, coordinate: Coordinate) -> bool {
+        match self.kind {
+            EventKind::Moved { coordinate: c, .. } | EventKind::Stalled { coordinate: c, .. } => {
+                c == coordinate
+            }
+            EventKind::Transferred { source, target, .. }
+            | EventKind::TransferRejected { source, target, .. }
+            | EventKind::Dropped { source, target, .. } => {
+                source == coordinate || target == coordinate
+            }
+        }
+    }
+}
+
+const fn side(is_left: bool) -> &'static str {
+    if is_left { "left" } else { "right" }
+}
+
+/// Mentions stacks of more than one item
+fn write_stack(f: &mut fmt::Formatter<'_>, height: u8) -> fmt::Result {
+    if height > 1 {
+        write!(f, " as a stack of {height}")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tick {}: item {} ", self.tick, self.item())?;
+        match self.kind {
+            EventKind::Moved {
+                coordinate,
+                is_left,
+                height,
+                from,
+                to,
+                ..
+            } => {
+                write!(
+                    f,
+                    "moved from {from} to {to} on ({}, {}) {} lane",
+                    coordinate.x,
+                    coordinate.y,
+                    side(is_left)
+                )?;
+                write_stack(f, height)
+            }
+            EventKind::Stalled {
+                coordinate,
+                is_left,
+                position,
+                ..
+            } => write!(
+                f,
+                "stalled at {position} on ({}, {}) {} lane",
+                coordinate.x,
+                coordinate.y,
+                side(is_left)
+            ),
+            EventKind::Transferred {
+                height,
+                source,
+                source_is_left,
+                target,
+                target_is_left,
+                position,
+                ..
+            } => {
+                write!(
+                    f,
+                    "transferred from ({}, {}) {} lane to ({}, {}) {} lane at {position}",
+                    source.x,
+                    source.y,
+                    side(source_is_left),
+                    target.x,
+                    target.y,
+                    side(target_is_left)
+                )?;
+                write_stack(f, height)
+            }
+            EventKind::TransferRejected {
+                source,
+                target,
+                target_is_left,
+                position,
+                ..
+            } => write!(
+                f,
+                "from ({}, {}) rejected by ({}, {}) {} lane at {position}",
+                source.x,
+                source.y,
+                target.x,
+                target.y,
+                side(target_is_left)
+            ),
+            EventKind::Dropped {
+                height,
+                source,
+                target,
+                ..
+            } => {
+                write!(
+                    f,
+                    "dropped between ({}, {}) and ({}, {})",
+                    source.x, source.y, target.x, target.y
+                )?;
+                write_stack(f, height)
+            }
+        }
+    }
+}
+
+/// Selects which events a subscriber receives. The default filter matches everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    pub coordinate: Option<Coordinate>,
+    pub item: Option<Item>,
+}
+
+impl EventFilter {
+    /// Only events happening at `coordinate`
+    #[allow(dead_code)]
+    pub const fn at(mut self, coordinate: Coordinate) -> Self {
+        self.coordinate = Some(coordinate);
+        self
+    }
+
+    /// Only events about `item`
+    #[allow(dead_code)]
+    pub const fn item(mut self, item: Item) -> Self {
+        self.item = Some(item);
+        self
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        self.coordinate.is_none_or(|c| event.involves(c))
+            && self.item.is_none_or(|item| event.item() == item)
+    }
+}
+
+/// Handle returned by [`EventLog::subscribe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriberId(usize);
+
+struct Subscriber {
+    id: SubscriberId,
+    filter: EventFilter,
+    events: Vec<Event>,
+}
+
+/// Collects events for all subscribers of a world
+#[derive(Default)]
+pub struct EventLog {
+    subscribers: Vec<Subscriber>,
+    next_id: usize,
+}
+
+impl EventLog {
+    pub fn subscribe(&mut self, filter: EventFilter) -> SubscriberId {
+        let id = SubscriberId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push(Subscriber {
+            id,
+            filter,
+            events: Vec::new(),
+        });
+        id
+    }
+
+    /// Removes the subscriber, discarding any events it has not taken yet
+    #[allow(dead_code)]
+    pub fn unsubscribe(&mut self, id: SubscriberId) {
+        self.subscribers.retain(|subscriber| subscriber.id != id);
+    }
+
+    /// Returns all events received by the subscriber since the last call, oldest first
+    pub fn take(&mut self, id: SubscriberId) -> Vec<Event> {
+        self.subscribers
+            .iter_mut()
+            .find(|subscriber| subscriber.id == id)
+            .map(|subscriber| std::mem::take(&mut subscriber.events))
+            .unwrap_or_default()
+    }
+
+    /// Whether anyone is listening. Callers skip building events when this is false.
+    pub const fn is_active(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    pub fn emit(&mut self, tick: u64, kind: EventKind) {
+        let event = Event { tick, kind };
+        for subscriber in &mut self.subscribers {
+            if subscriber.filter.matches(&event) {
+                subscriber.events.push(event);
+            }
+        }
+    }
+
+    /// Emits a move or stall event for every item that stayed on the lane during its tick.
+    /// Items keep their slot while moving along a lane, so slots are compared one to one;
+    /// slots that were emptied belong to items that left the lane and are reported
+    /// when the transfer is applied.
+    pub fn emit_lane_tick(
+        &mut self,
+        tick: u64,
+        coordinate: Coordinate,
+        is_left: bool,
+        before: &[Option<(Item, u32)>],
+        lane: &SingleBeltLane,
+    ) {
+        for (slot, (before, after)) in before.iter().zip(&lane.items).enumerate() {
+            let (Some((item, from)), Some((_, to))) = (before, after) else {
+                continue;
+            };
+            let kind = if from == to {
+                EventKind::Stalled {
+                    coordinate,
+                    is_left,
+                    item: *item,
+                    position: *to,
+                }
+            } else {
+                EventKind::Moved {
+                    coordinate,
+                    is_left,
+                    item: *item,
+                    height: lane.stack_height(slot),
+                    from: *from,
+                    to: *to,
+                }
+            };
+            self.emit(tick, kind);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
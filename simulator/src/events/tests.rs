@@ -0,0 +1,326 @@
+use super::*;
+use crate::{BeltType, SingleBelt, World, quality::Quality};
+use std::num::NonZeroUsize;
+
+fn item(id: usize) -> Item {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn line_world() -> (World, Coordinate, Coordinate) {
+    let mut world = World::new();
+    let coord1 = Coordinate::new(0, 0);
+    let coord2 = Coordinate::new(1, 0);
+    world.add_belt(SingleBelt::new(coord2, BeltType::Regular, None, None));
+    world.add_belt(SingleBelt::new(
+        coord1,
+        BeltType::Regular,
+        Some(coord2),
+        Some(coord2),
+    ));
+    (world, coord1, coord2)
+}
+
+#[test]
+fn test_no_subscribers_no_events() {
+    let (mut world, coord1, _) = line_world();
+    world
+        .get_lane_mut(coord1, true)
+        .expect("Belt not found")
+        .items[0] = Some((item(1), 10));
+    assert!(!world.events.is_active());
+    world.tick();
+    assert_eq!(world.ticks, 1);
+}
+
+#[test]
+fn test_moved_and_stalled_events() {
+    let (mut world, _, coord2) = line_world();
+    let lane = world.get_lane_mut(coord2, true).expect("Belt not found");
+    lane.items[0] = Some((item(1), 255));
+    lane.items[1] = Some((item(2), 100));
+    let id = world.events.subscribe(EventFilter::default());
+
+    world.tick();
+
+    let mut events = world.events.take(id);
+    events.sort_by_key(Event::item);
+    assert_eq!(
+        events,
+        vec![
+            Event {
+                tick: 1,
+                kind: EventKind::Stalled {
+                    coordinate: coord2,
+                    is_left: true,
+                    item: item(1),
+                    position: 255,
+                },
+            },
+            Event {
+                tick: 1,
+                kind: EventKind::Moved {
+                    coordinate: coord2,
+                    is_left: true,
+                    item: item(2),
+                    height: 1,
+                    from: 100,
+                    to: 108,
+                },
+            },
+        ]
+    );
+    // Events are handed out only once
+    assert!(world.events.take(id).is_empty());
+}
+
+#[test]
+fn test_transfer_event_has_source_and_target() {
+    let (mut world, coord1, coord2) = line_world();
+    world
+        .get_lane_mut(coord1, false)
+        .expect("Belt not found")
+        .items[0] = Some((item(3), 250));
+    let id = world.events.subscribe(EventFilter::default());
+
+    world.tick();
+
+    assert_eq!(
+        world.events.take(id),
+        vec![Event {
+            tick: 1,
+            kind: EventKind::Transferred {
+                item: item(3),
+                height: 1,
+                source: coord1,
+                source_is_left: false,
+                target: coord2,
+                target_is_left: true,
+                position: 2,
+            },
+        }]
+    );
+}
+
+#[test]
+fn test_transfer_event_has_the_stack_height() {
+    let (mut world, coord1, coord2) = line_world();
+    assert!(
+        world
+            .get_lane_mut(coord1, true)
+            .expect("Belt not found")
+            .accept_stack(item(3), Quality::Normal, 4, 250)
+    );
+    let id = world.events.subscribe(EventFilter::default());
+
+    world.tick();
+
+    let events = world.events.take(id);
+    assert_eq!(events.len(), 1);
+    assert_eq!(
+        events[0].kind,
+        EventKind::Transferred {
+            item: item(3),
+            height: 4,
+            source: coord1,
+            source_is_left: true,
+            target: coord2,
+            target_is_left: true,
+            position: 2,
+        }
+    );
+    assert_eq!(
+        events[0].to_string(),
+        "tick 1: item 3 transferred from (0, 0) left lane to (1, 0) left lane at 2 as a stack of 4"
+    );
+}
+
+#[test]
+fn test_rejected_and_dropped_items_are_reported() {
+    let (mut world, coord1, coord2) = line_world();
+    // Fill both lanes of the target so nothing fits at the start
+    for is_left in [true, false] {
+        let lane = world.get_lane_mut(coord2, is_left).expect("Belt not found");
+        for (slot, pos) in [0, 64, 128, 192, 255].into_iter().enumerate() {
+            lane.items[slot] = Some((item(10 + slot), pos));
+        }
+    }
+    world
+        .get_lane_mut(coord1, true)
+        .expect("Belt not found")
+        .items[0] = Some((item(1), 250));
+    let id = world.events.subscribe(EventFilter::default().item(item(1)));
+
+    world.tick();
+
+    let kinds: Vec<EventKind> = world.events.take(id).into_iter().map(|e| e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            EventKind::TransferRejected {
+                item: item(1),
+                source: coord1,
+                target: coord2,
+                target_is_left: true,
+                position: 2,
+            },
+            EventKind::TransferRejected {
+                item: item(1),
+                source: coord1,
+                target: coord2,
+                target_is_left: false,
+                position: 2,
+            },
+            EventKind::Dropped {
+                item: item(1),
+                height: 1,
+                source: coord1,
+                target: coord2,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_rejected_carry_is_reported_once() {
+    let warp = BeltType::Modded {
+        positions_per_tick: 200,
+    };
+    let mut world = World::new();
+    let first = Coordinate::new(0, 0);
+    let short = Coordinate::new(1, 0);
+    let last = Coordinate::new(2, 0);
+    let mut belt = SingleBelt::new(first, warp, Some(short), Some(short));
+    belt.left_lane.items[0] = Some((item(1), 250));
+    world.add_belt(belt);
+    let mut belt = SingleBelt::new(short, warp, Some(last), Some(last)).with_lane_lengths(100, 100);
+    belt.left_lane.items[0] = Some((item(2), 98));
+    belt.set_enabled(false);
+    world.add_belt(belt);
+    let mut belt = SingleBelt::new(last, warp, None, None);
+    for lane in [&mut belt.left_lane, &mut belt.right_lane] {
+        for (slot, pos) in [0, 64, 128, 192, 255].into_iter().enumerate() {
+            lane.items[slot] = Some((item(3), pos));
+        }
+    }
+    belt.set_enabled(false);
+    world.add_belt(belt);
+    let id = world.events.subscribe(EventFilter::default().item(item(1)));
+
+    // Neither lane after the short one has room and its left lane is blocked at the
+    // end, so the stack ends up on its right lane
+    world.tick();
+
+    let kinds: Vec<EventKind> = world.events.take(id).into_iter().map(|e| e.kind).collect();
+    let rejected = |source, target, target_is_left, position| EventKind::TransferRejected {
+        item: item(1),
+        source,
+        target,
+        target_is_left,
+        position,
+    };
+    assert_eq!(
+        kinds,
+        vec![
+            rejected(short, last, true, 94),
+            rejected(short, last, false, 94),
+            rejected(first, short, true, 194),
+            EventKind::Transferred {
+                item: item(1),
+                height: 1,
+                source: first,
+                source_is_left: true,
+                target: short,
+                target_is_left: false,
+                position: 194,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_transfer_to_missing_belt_is_dropped() {
+    let mut world = World::new();
+    let coord = Coordinate::new(0, 0);
+    let missing = Coordinate::new(1, 0);
+    let mut belt = SingleBelt::new(coord, BeltType::Regular, Some(missing), None);
+    belt.left_lane.items[0] = Some((item(1), 250));
+    world.add_belt(belt);
+    let id = world.events.subscribe(EventFilter::default());
+
+    world.tick();
+
+    assert_eq!(
+        world.events.take(id),
+        vec![Event {
+            tick: 1,
+            kind: EventKind::Dropped {
+                item: item(1),
+                height: 1,
+                source: coord,
+                target: missing,
+            },
+        }]
+    );
+}
+
+#[test]
+fn test_filters_by_coordinate_and_item() {
+    let (mut world, coord1, coord2) = line_world();
+    world
+        .get_lane_mut(coord1, true)
+        .expect("Belt not found")
+        .items[0] = Some((item(1), 10));
+    world
+        .get_lane_mut(coord2, true)
+        .expect("Belt not found")
+        .items[0] = Some((item(2), 10));
+
+    let at_first = world.events.subscribe(EventFilter::default().at(coord1));
+    let second_item = world.events.subscribe(EventFilter::default().item(item(2)));
+    let nothing = world
+        .events
+        .subscribe(EventFilter::default().at(coord1).item(item(2)));
+
+    world.tick();
+
+    let events = world.events.take(at_first);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].item(), item(1));
+    let events = world.events.take(second_item);
+    assert_eq!(events.len(), 1);
+    assert!(events[0].involves(coord2));
+    assert!(world.events.take(nothing).is_empty());
+}
+
+#[test]
+fn test_unsubscribe_stops_collection() {
+    let (mut world, coord1, _) = line_world();
+    world
+        .get_lane_mut(coord1, true)
+        .expect("Belt not found")
+        .items[0] = Some((item(1), 10));
+    let id = world.events.subscribe(EventFilter::default());
+    world.events.unsubscribe(id);
+    assert!(!world.events.is_active());
+    world.tick();
+    assert!(world.events.take(id).is_empty());
+}
+
+#[test]
+fn test_event_display() {
+    let event = Event {
+        tick: 7,
+        kind: EventKind::Moved {
+            coordinate: Coordinate::new(2, -1),
+            is_left: false,
+            item: item(5),
+            height: 1,
+            from: 10,
+            to: 18,
+        },
+    };
+    assert_eq!(
+        event.to_string(),
+        "tick 7: item 5 moved from 10 to 18 on (2, -1) right lane"
+    );
+}
@@ -0,0 +1,499 @@
+//! Fluid simulation: pipes, underground pipes, storage tanks, pumps and offshore pumps.
+//!
+//! Every fluid entity owns a [`FluidBox`]. How fluid moves between boxes depends on
+//! the [`FluidModel`]:
+//!
+//! - [`FluidModel::Segment`] follows Factorio 2.0: all directly connected pipes and
+//!   tanks form one segment that shares its contents evenly (relative to capacity).
+//!   Fluid only "moves" through pumps, which transfer between segments.
+//! - [`FluidModel::Flow`] follows Factorio 1.1: fluid flows between neighbouring
+//!   boxes every tick, driven by the difference in fill level and the flow of the
+//!   previous tick, so long pipelines lose throughput.
+//!
+//! Boxes are always the source of truth. In the segment model the contents of a segment
+//! are gathered from and written back to its boxes, so switching models keeps all fluid.
+
+use std::collections::HashMap;
+
+use super::{Coordinate, Direction, TICKS_PER_SECOND};
+
+// Temp
+pub type Fluid = std::num::NonZeroUsize;
+
+/// Maximum number of tiles between the two ends of an underground pipe
+pub const UNDERGROUND_PIPE_MAX_DISTANCE: i32 = 10;
+
+/// Share of last tick's flow that carries over into the next one (1.1 model)
+const FLOW_MOMENTUM: f64 = 0.59;
+/// Share of the fill level difference that flows per tick (1.1 model)
+const FLOW_PRESSURE: f64 = 0.4;
+/// Fill level of a full box used for pressure calculations (1.1 model)
+const FLOW_LEVEL_SCALE: f64 = 100.0;
+
+/// Amounts below this are treated as empty to avoid endless tiny flows
+const EPSILON: f64 = 1e-9;
+
+/// A container holding up to `capacity` units of a single fluid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FluidBox {
+    pub fluid: Option<Fluid>,
+    pub amount: f64,
+    pub capacity: f64,
+}
+
+#[allow(dead_code)]
+impl FluidBox {
+    pub const fn new(capacity: f64) -> Self {
+        Self {
+            fluid: None,
+            amount: 0.0,
+            capacity,
+        }
+    }
+
+    /// Whether `fluid` may be added, i.e. the box is empty or already holds it
+    pub fn accepts(&self, fluid: Fluid) -> bool {
+        self.fluid.is_none_or(|current| current == fluid)
+    }
+
+    pub fn free(&self) -> f64 {
+        (self.capacity - self.amount).max(0.0)
+    }
+
+    /// Adds up to `amount` of `fluid` and returns how much was added
+    pub fn insert(&mut self, fluid: Fluid, amount: f64) -> f64 {
+        if !self.accepts(fluid) {
+            return 0.0;
+        }
+        let added = amount.min(self.free()).max(0.0);
+        if added > 0.0 {
+            self.fluid = Some(fluid);
+            self.amount += added;
+        }
+        added
+    }
+
+    /// Removes up to `amount` and returns how much was removed
+    pub fn remove(&mut self, amount: f64) -> f64 {
+        let removed = amount.min(self.amount).max(0.0);
+        self.amount -= removed;
+        if self.amount <= EPSILON {
+            self.amount = 0.0;
+            self.fluid = None;
+        }
+        removed
+    }
+
+    fn level(&self) -> f64 {
+        if self.capacity > 0.0 {
+            self.amount / self.capacity * FLOW_LEVEL_SCALE
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The kinds of fluid entities, with the game's default capacities and speeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum FluidEntityKind {
+    Pipe,
+    /// Connects above ground on the side opposite to `direction` and underground
+    /// to the nearest underground pipe facing back towards it
+    UndergroundPipe {
+        direction: Direction,
+    },
+    StorageTank,
+    /// Moves fluid from the tile behind it to the tile in front of it
+    Pump {
+        direction: Direction,
+    },
+    /// Produces `fluid` out of thin water and outputs it in `direction`
+    OffshorePump {
+        direction: Direction,
+        fluid: Fluid,
+    },
+}
+
+#[allow(dead_code)]
+impl FluidEntityKind {
+    pub const fn default_capacity(self) -> f64 {
+        match self {
+            Self::StorageTank => 25_000.0,
+            Self::Pipe
+            | Self::UndergroundPipe { .. }
+            | Self::Pump { .. }
+            | Self::OffshorePump { .. } => 100.0,
+        }
+    }
+
+    /// Units per tick moved by pumps, 1200 per second for both kinds
+    pub const fn pumping_speed(self) -> f64 {
+        match self {
+            Self::Pump { .. } | Self::OffshorePump { .. } => 1200.0 / TICKS_PER_SECOND,
+            _ => 0.0,
+        }
+    }
+
+    /// Whether the entity has a pipe connection towards `side`
+    fn connects_on(self, side: Direction) -> bool {
+        match self {
+            Self::Pipe | Self::StorageTank => true,
+            Self::UndergroundPipe { direction } => side == direction.opposite(),
+            // Pumps move fluid on their own instead of being part of a segment
+            Self::Pump { .. } | Self::OffshorePump { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FluidEntity {
+    pub kind: FluidEntityKind,
+    pub fluidbox: FluidBox,
+}
+
+#[allow(dead_code)]
+impl FluidEntity {
+    pub const fn new(kind: FluidEntityKind) -> Self {
+        Self {
+            kind,
+            fluidbox: FluidBox::new(kind.default_capacity()),
+        }
+    }
+}
+
+/// Which fluid mechanics to simulate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FluidModel {
+    /// Factorio 2.0 fluid segments
+    #[default]
+    Segment,
+    /// Factorio 1.1 per-box flow
+    Flow,
+}
+
+/// All fluid entities of a world
+#[derive(Default)]
+pub struct FluidSystem {
+    pub entities: HashMap<Coordinate, FluidEntity>,
+    pub model: FluidModel,
+    /// Members of every segment, rebuilt whenever entities change
+    segments: Vec<Vec<Coordinate>>,
+    segment_of: HashMap<Coordinate, usize>,
+    dirty: bool,
+    /// Flow of the previous tick for every connection, keyed by (from, to) (1.1 model)
+    flows: HashMap<(Coordinate, Coordinate), f64>,
+}
+
+impl FluidSystem {
+    pub fn add(&mut self, coordinate: Coordinate, entity: FluidEntity) {
+        self.entities.insert(coordinate, entity);
+        self.dirty = true;
+    }
+
+    /// Flow of the previous tick for every connection, in coordinate order
+    pub fn flows(&self) -> Vec<((Coordinate, Coordinate), f64)> {
+        let mut flows: Vec<_> = self.flows.iter().map(|(key, flow)| (*key, *flow)).collect();
+        flows.sort_by_key(|((from, to), _)| (from.y, from.x, to.y, to.x));
+        flows
+    }
+
+    #[allow(dead_code)]
+    pub fn remove(&mut self, coordinate: Coordinate) -> Option<FluidEntity> {
+        self.dirty = true;
+        self.entities.remove(&coordinate)
+    }
+
+    /// Pipe-like entities connected to the one at `coordinate`
+    pub fn connections(&self, coordinate: Coordinate) -> Vec<Coordinate> {
+        let Some(entity) = self.entities.get(&coordinate) else {
+            return Vec::new();
+        };
+        let mut connected: Vec<Coordinate> = Direction::ALL
+            .into_iter()
+            .filter(|&side| entity.kind.connects_on(side))
+            .map(|side| (side, coordinate.neighbor(side)))
+            .filter(|(side, neighbor)| {
+                self.entities
+                    .get(neighbor)
+                    .is_some_and(|other| other.kind.connects_on(side.opposite()))
+            })
+            .map(|(_, neighbor)| neighbor)
+            .collect();
+        if let FluidEntityKind::UndergroundPipe { direction } = entity.kind {
+            connected.extend(self.underground_partner(coordinate, direction));
+        }
+        connected
+    }
+
+    fn underground_partner(&self, start: Coordinate, direction: Direction) -> Option<Coordinate> {
+        let mut coordinate = start;
+        for _ in 0..UNDERGROUND_PIPE_MAX_DISTANCE {
+            coordinate = coordinate.neighbor(direction);
+            if let Some(FluidEntity {
+                kind: FluidEntityKind::UndergroundPipe { direction: other },
+                ..
+            }) = self.entities.get(&coordinate)
+            {
+                // The first underground pipe in the way decides, like in game
+                return (*other == direction.opposite()).then_some(coordinate);
+            }
+        }
+        None
+    }
+
+    /// Coordinate of the pipe-like entity feeding a pump or being fed by it on `side`
+    pub fn pump_port(&self, coordinate: Coordinate, side: Direction) -> Option<Coordinate> {
+        let neighbor = coordinate.neighbor(side);
+        self.entities
+            .get(&neighbor)
+            .is_some_and(|other| other.kind.connects_on(side.opposite()))
+            .then_some(neighbor)
+    }
+
+    fn rebuild_segments(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.segments.clear();
+        self.segment_of.clear();
+
+        let mut coords: Vec<Coordinate> = self.entities.keys().copied().collect();
+        // Stable segment numbering regardless of HashMap order
+        coords.sort_by_key(|c| (c.y, c.x));
+        for start in coords {
+            let kind = self.entities[&start].kind;
+            let is_pipe_like = Direction::ALL
+                .into_iter()
+                .any(|side| kind.connects_on(side));
+            if !is_pipe_like || self.segment_of.contains_key(&start) {
+                continue;
+            }
+            let index = self.segments.len();
+            let mut members = vec![start];
+            let mut fluid = self.entities[&start].fluidbox.fluid;
+            self.segment_of.insert(start, index);
+            let mut next = 0;
+            while next < members.len() {
+                for neighbor in self.connections(members[next]) {
+                    // Boxes holding another fluid are not connected, so fluids never mix
+                    let other = self.entities[&neighbor].fluidbox.fluid;
+                    if other.is_some_and(|other| fluid.is_some_and(|fluid| fluid != other)) {
+                        continue;
+                    }
+                    if let std::collections::hash_map::Entry::Vacant(entry) =
+                        self.segment_of.entry(neighbor)
+                    {
+                        entry.insert(index);
+                        members.push(neighbor);
+                        fluid = fluid.or(other);
+                    }
+                }
+                next += 1;
+            }
+            self.segments.push(members);
+        }
+        self.dirty = false;
+    }
+
+    /// Fluid, amount and capacity of a segment, summed over its boxes. Segments never
+    /// hold more than one fluid, see `rebuild_segments`.
+    fn segment_contents(&self, index: usize) -> (Option<Fluid>, f64, f64) {
+        self.segments[index]
+            .iter()
+            .map(|c| self.entities[c].fluidbox)
+            .fold((None, 0.0, 0.0), |(fluid, amount, capacity), fluidbox| {
+                (
+                    fluid.or(fluidbox.fluid),
+                    amount + fluidbox.amount,
+                    capacity + fluidbox.capacity,
+                )
+            })
+    }
+
+    /// Spreads `amount` over the boxes of a segment in proportion to their capacity
+    fn set_segment_contents(&mut self, index: usize, fluid: Option<Fluid>, amount: f64) {
+        let (_, _, capacity) = self.segment_contents(index);
+        let fill = if capacity > 0.0 && amount > EPSILON {
+            (amount / capacity).min(1.0)
+        } else {
+            0.0
+        };
+        for coordinate in &self.segments[index] {
+            if let Some(entity) = self.entities.get_mut(coordinate) {
+                entity.fluidbox.amount = entity.fluidbox.capacity * fill;
+                entity.fluidbox.fluid = if fill > 0.0 { fluid } else { None };
+            }
+        }
+    }
+
+    /// Fluid and amount available at `coordinate`. In the segment model this is
+    /// the content of the whole segment.
+    pub fn contents(&mut self, coordinate: Coordinate) -> Option<(Option<Fluid>, f64)> {
+        self.rebuild_segments();
+        match (self.model, self.segment_of.get(&coordinate)) {
+            (FluidModel::Segment, Some(&index)) => {
+                let (fluid, amount, _) = self.segment_contents(index);
+                Some((fluid, amount))
+            }
+            _ => self
+                .entities
+                .get(&coordinate)
+                .map(|entity| (entity.fluidbox.fluid, entity.fluidbox.amount)),
+        }
+    }
+
+    /// Where the fluid at `coordinate` is stored: the first box of its segment in the
+    /// segment model, the box itself otherwise. Entities with the same key draw on the
+    /// same fluid.
+    pub fn storage_key(&mut self, coordinate: Coordinate) -> Coordinate {
+        self.rebuild_segments();
+        match (self.model, self.segment_of.get(&coordinate)) {
+            (FluidModel::Segment, Some(&index)) => self.segments[index][0],
+            _ => coordinate,
+        }
+    }
+
+    /// Removes up to `amount` of `fluid` from the entity at `coordinate` (or its segment)
+    /// and returns how much was removed
+    pub fn take(&mut self, coordinate: Coordinate, fluid: Fluid, amount: f64) -> f64 {
+        self.rebuild_segments();
+        match (self.model, self.segment_of.get(&coordinate)) {
+            (FluidModel::Segment, Some(&index)) => {
+                let (current, total, _) = self.segment_contents(index);
+                if current != Some(fluid) {
+                    return 0.0;
+                }
+                let taken = amount.min(total).max(0.0);
+                self.set_segment_contents(index, current, total - taken);
+                taken
+            }
+            _ => match self.entities.get_mut(&coordinate) {
+                Some(entity) if entity.fluidbox.fluid == Some(fluid) => {
+                    entity.fluidbox.remove(amount)
+                }
+                _ => 0.0,
+            },
+        }
+    }
+
+    /// Adds up to `amount` of `fluid` to the entity at `coordinate` (or its segment)
+    /// and returns how much was added
+    pub fn put(&mut self, coordinate: Coordinate, fluid: Fluid, amount: f64) -> f64 {
+        self.rebuild_segments();
+        match (self.model, self.segment_of.get(&coordinate)) {
+            (FluidModel::Segment, Some(&index)) => {
+                let (current, total, capacity) = self.segment_contents(index);
+                if current.is_some_and(|current| current != fluid) {
+                    return 0.0;
+                }
+                let added = amount.min(capacity - total).max(0.0);
+                self.set_segment_contents(index, Some(fluid), total + added);
+                added
+            }
+            _ => self
+                .entities
+                .get_mut(&coordinate)
+                .map_or(0.0, |entity| entity.fluidbox.insert(fluid, amount)),
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.rebuild_segments();
+        if self.model == FluidModel::Flow {
+            self.flow();
+        }
+        self.pump();
+    }
+
+    /// Runs all pumps and offshore pumps in a fixed order
+    fn pump(&mut self) {
+        let mut pumps: Vec<(Coordinate, FluidEntityKind)> = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| entity.kind.pumping_speed() > 0.0)
+            .map(|(c, entity)| (*c, entity.kind))
+            .collect();
+        pumps.sort_by_key(|(c, _)| (c.y, c.x));
+
+        for (coordinate, kind) in pumps {
+            let speed = kind.pumping_speed();
+            match kind {
+                FluidEntityKind::OffshorePump { direction, fluid } => {
+                    if let Some(output) = self.pump_port(coordinate, direction) {
+                        self.put(output, fluid, speed);
+                    }
+                }
+                FluidEntityKind::Pump { direction } => {
+                    let input = self.pump_port(coordinate, direction.opposite());
+                    let output = self.pump_port(coordinate, direction);
+                    let (Some(input), Some(output)) = (input, output) else {
+                        continue;
+                    };
+                    let Some((Some(fluid), available)) = self.contents(input) else {
+                        continue;
+                    };
+                    let moved = self.put(output, fluid, speed.min(available));
+                    self.take(input, fluid, moved);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// One step of the 1.1 flow model over every pipe connection
+    fn flow(&mut self) {
+        let mut coords: Vec<Coordinate> = self.entities.keys().copied().collect();
+        coords.sort_by_key(|c| (c.y, c.x));
+
+        // Wanted flows are computed from the state at the start of the tick so fluid
+        // does not race down a whole pipeline within a single tick
+        let mut wanted_flows = Vec::new();
+        for &from in &coords {
+            for to in self.connections(from) {
+                // Each connection is handled once, from its smaller end
+                if (to.y, to.x) < (from.y, from.x) {
+                    continue;
+                }
+                let a = self.entities[&from].fluidbox;
+                let b = self.entities[&to].fluidbox;
+                let previous = self.flows.get(&(from, to)).copied().unwrap_or(0.0);
+                let wanted =
+                    previous.mul_add(FLOW_MOMENTUM, (a.level() - b.level()) * FLOW_PRESSURE);
+                wanted_flows.push((from, to, wanted));
+            }
+        }
+
+        let mut flows = HashMap::new();
+        for (from, to, wanted) in wanted_flows {
+            let (source, target, amount) = if wanted >= 0.0 {
+                (from, to, wanted)
+            } else {
+                (to, from, -wanted)
+            };
+            let source_box = self.entities[&source].fluidbox;
+            let target_box = self.entities[&target].fluidbox;
+            let Some(fluid) = source_box.fluid else {
+                continue;
+            };
+            if !target_box.accepts(fluid) {
+                continue;
+            }
+            let moved = amount.min(source_box.amount).min(target_box.free());
+            if moved <= EPSILON {
+                continue;
+            }
+            if let Some(entity) = self.entities.get_mut(&source) {
+                entity.fluidbox.remove(moved);
+            }
+            if let Some(entity) = self.entities.get_mut(&target) {
+                entity.fluidbox.insert(fluid, moved);
+            }
+            flows.insert((from, to), moved.copysign(wanted));
+        }
+        self.flows = flows;
+    }
+}
+
+#[cfg(test)]
+mod tests;
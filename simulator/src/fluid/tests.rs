@@ -0,0 +1,252 @@
+use super::*;
+use std::num::NonZeroUsize;
+
+fn fluid(id: usize) -> Fluid {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn pipe_line(system: &mut FluidSystem, from: i32, to: i32) {
+    for x in from..=to {
+        system.add(
+            Coordinate::new(x, 0),
+            FluidEntity::new(FluidEntityKind::Pipe),
+        );
+    }
+}
+
+fn total(system: &FluidSystem) -> f64 {
+    system
+        .entities
+        .values()
+        .map(|entity| entity.fluidbox.amount)
+        .sum()
+}
+
+fn amount_at(system: &FluidSystem, x: i32, y: i32) -> f64 {
+    system.entities[&Coordinate::new(x, y)].fluidbox.amount
+}
+
+#[test]
+fn test_fluidbox_insert_and_remove() {
+    let mut fluidbox = FluidBox::new(100.0);
+    assert!((fluidbox.insert(fluid(1), 70.0) - 70.0).abs() < 1e-9);
+    // Only the free space is filled
+    assert!((fluidbox.insert(fluid(1), 70.0) - 30.0).abs() < 1e-9);
+    // A box never mixes fluids
+    assert!(fluidbox.insert(fluid(2), 10.0).abs() < 1e-9);
+    assert!((fluidbox.remove(150.0) - 100.0).abs() < 1e-9);
+    assert_eq!(fluidbox.fluid, None);
+    assert!(fluidbox.accepts(fluid(2)));
+}
+
+#[test]
+fn test_segment_shares_contents() {
+    let mut system = FluidSystem::default();
+    pipe_line(&mut system, 0, 4);
+
+    let added = system.put(Coordinate::new(0, 0), fluid(1), 250.0);
+    assert!((added - 250.0).abs() < 1e-9);
+    // The whole segment is filled evenly, no flow needed
+    for x in 0..=4 {
+        assert!((amount_at(&system, x, 0) - 50.0).abs() < 1e-9);
+    }
+    let (current, amount) = system.contents(Coordinate::new(4, 0)).expect("Pipe exists");
+    assert_eq!(current, Some(fluid(1)));
+    assert!((amount - 250.0).abs() < 1e-9);
+
+    // Capacity of a segment is the sum of its boxes
+    assert!((system.put(Coordinate::new(2, 0), fluid(1), 1000.0) - 250.0).abs() < 1e-9);
+    assert!(system.put(Coordinate::new(2, 0), fluid(2), 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_pipes_with_different_fluids_do_not_join() {
+    let mut system = FluidSystem::default();
+    for x in [0, 2] {
+        system.add(
+            Coordinate::new(x, 0),
+            FluidEntity::new(FluidEntityKind::Pipe),
+        );
+    }
+    system.put(Coordinate::new(0, 0), fluid(1), 50.0);
+    system.put(Coordinate::new(2, 0), fluid(2), 30.0);
+    system.add(
+        Coordinate::new(1, 0),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+
+    // The new pipe joins the water, the steam stays on its own
+    let water = system.contents(Coordinate::new(1, 0)).expect("Pipe exists");
+    assert_eq!(water.0, Some(fluid(1)));
+    assert!((water.1 - 50.0).abs() < 1e-9);
+    let steam = system.contents(Coordinate::new(2, 0)).expect("Pipe exists");
+    assert_eq!(steam.0, Some(fluid(2)));
+    assert!((steam.1 - 30.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_storage_tank_joins_segment() {
+    let mut system = FluidSystem::default();
+    pipe_line(&mut system, 0, 0);
+    system.add(
+        Coordinate::new(1, 0),
+        FluidEntity::new(FluidEntityKind::StorageTank),
+    );
+    system.put(Coordinate::new(0, 0), fluid(1), 2510.0);
+    // Filled relative to capacity: 10% of both the pipe and the tank
+    assert!((amount_at(&system, 0, 0) - 10.0).abs() < 1e-9);
+    assert!((amount_at(&system, 1, 0) - 2500.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_offshore_pump_fills_pipes() {
+    let mut system = FluidSystem::default();
+    system.add(
+        Coordinate::new(-1, 0),
+        FluidEntity::new(FluidEntityKind::OffshorePump {
+            direction: Direction::East,
+            fluid: fluid(1),
+        }),
+    );
+    pipe_line(&mut system, 0, 9);
+
+    system.tick();
+    assert!((total(&system) - 20.0).abs() < 1e-9);
+    for _ in 0..100 {
+        system.tick();
+    }
+    // Ten pipes hold 1000 units and the offshore pump stops once they are full
+    assert!((total(&system) - 1000.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_pump_moves_between_segments_in_one_direction() {
+    let mut system = FluidSystem::default();
+    pipe_line(&mut system, 0, 1);
+    system.add(
+        Coordinate::new(2, 0),
+        FluidEntity::new(FluidEntityKind::Pump {
+            direction: Direction::East,
+        }),
+    );
+    pipe_line(&mut system, 3, 4);
+
+    // Pumps separate segments, so fluid on the input side stays there without pumping
+    system.put(Coordinate::new(0, 0), fluid(1), 200.0);
+    assert!(amount_at(&system, 3, 0).abs() < 1e-9);
+
+    system.tick();
+    let (_, moved) = system.contents(Coordinate::new(4, 0)).expect("Pipe exists");
+    assert!((moved - 20.0).abs() < 1e-9);
+
+    // Nothing flows back against the pump
+    let mut reversed = FluidSystem::default();
+    pipe_line(&mut reversed, 0, 1);
+    reversed.add(
+        Coordinate::new(2, 0),
+        FluidEntity::new(FluidEntityKind::Pump {
+            direction: Direction::East,
+        }),
+    );
+    pipe_line(&mut reversed, 3, 4);
+    reversed.put(Coordinate::new(4, 0), fluid(1), 200.0);
+    reversed.tick();
+    assert!(amount_at(&reversed, 0, 0).abs() < 1e-9);
+}
+
+#[test]
+fn test_underground_pipes_connect_within_range() {
+    let mut system = FluidSystem::default();
+    let entrance = Coordinate::new(0, 0);
+    system.add(
+        entrance,
+        FluidEntity::new(FluidEntityKind::UndergroundPipe {
+            direction: Direction::East,
+        }),
+    );
+    let exit = Coordinate::new(UNDERGROUND_PIPE_MAX_DISTANCE, 0);
+    system.add(
+        exit,
+        FluidEntity::new(FluidEntityKind::UndergroundPipe {
+            direction: Direction::West,
+        }),
+    );
+    // A pipe crossing over the underground section is not connected to it
+    system.add(
+        Coordinate::new(3, 0),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+
+    assert_eq!(system.connections(entrance), vec![exit]);
+    assert!(system.connections(Coordinate::new(3, 0)).is_empty());
+
+    let too_far = Coordinate::new(UNDERGROUND_PIPE_MAX_DISTANCE + 1, 5);
+    system.add(
+        Coordinate::new(0, 5),
+        FluidEntity::new(FluidEntityKind::UndergroundPipe {
+            direction: Direction::East,
+        }),
+    );
+    system.add(
+        too_far,
+        FluidEntity::new(FluidEntityKind::UndergroundPipe {
+            direction: Direction::West,
+        }),
+    );
+    assert!(system.connections(too_far).is_empty());
+}
+
+#[test]
+fn test_flow_model_spreads_gradually() {
+    let mut system = FluidSystem {
+        model: FluidModel::Flow,
+        ..FluidSystem::default()
+    };
+    pipe_line(&mut system, 0, 4);
+    system.put(Coordinate::new(0, 0), fluid(1), 100.0);
+    assert!((amount_at(&system, 0, 0) - 100.0).abs() < 1e-9);
+
+    system.tick();
+    assert!(amount_at(&system, 1, 0) > 0.0);
+    assert!(amount_at(&system, 4, 0).abs() < 1e-9);
+    assert!((total(&system) - 100.0).abs() < 1e-6);
+
+    for _ in 0..200 {
+        system.tick();
+    }
+    // Fluid is conserved and eventually reaches the end of the line
+    assert!((total(&system) - 100.0).abs() < 1e-6);
+    assert!(amount_at(&system, 4, 0) > 10.0);
+}
+
+#[test]
+fn test_flow_model_pump_throughput() {
+    let mut system = FluidSystem {
+        model: FluidModel::Flow,
+        ..FluidSystem::default()
+    };
+    system.add(
+        Coordinate::new(-1, 0),
+        FluidEntity::new(FluidEntityKind::OffshorePump {
+            direction: Direction::East,
+            fluid: fluid(1),
+        }),
+    );
+    pipe_line(&mut system, 0, 2);
+    system.tick();
+    assert!((amount_at(&system, 0, 0) - 20.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_switching_models_keeps_fluid() {
+    let mut system = FluidSystem::default();
+    pipe_line(&mut system, 0, 3);
+    system.put(Coordinate::new(0, 0), fluid(1), 120.0);
+    system.model = FluidModel::Flow;
+    for _ in 0..10 {
+        system.tick();
+    }
+    system.model = FluidModel::Segment;
+    let (_, amount) = system.contents(Coordinate::new(3, 0)).expect("Pipe exists");
+    assert!((amount - 120.0).abs() < 1e-6);
+}
@@ -0,0 +1,375 @@
+//! Inserters, which move items between belts, machines, chests and stopped trains.
+//!
+//! An inserter picks up from the tile behind it and drops onto the tile in front of
+//! it. A full cycle is a half turn towards the drop tile and a half turn back, at a
+//! rotation speed that depends on the kind of inserter and on how well it is powered.
+//! Stack inserters carry several items of one kind at a time and drop them onto belts
+//! as a single stack.
+
+use super::{
+    Coordinate, Direction, Item, MAX_STACK_HEIGHT, SingleBeltLane, World, burner::Burner,
+    quality::Quality,
+};
+
+/// Position on a belt lane where inserters drop items
+pub const DROP_POSITION: u32 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum InserterKind {
+    Burner,
+    Basic,
+    Fast,
+    Stack,
+}
+
+impl InserterKind {
+    /// Rotation speed in turns per tick
+    pub const fn rotation_speed(self) -> f64 {
+        match self {
+            Self::Burner => 0.01,
+            Self::Basic => 0.014,
+            Self::Fast | Self::Stack => 0.04,
+        }
+    }
+
+    /// Power drawn while moving, in watts, from fuel for burner inserters
+    pub const fn energy_usage(self) -> f64 {
+        match self {
+            Self::Burner => 94_200.0,
+            Self::Basic => 13_200.0,
+            Self::Fast => 46_700.0,
+            Self::Stack => 132_600.0,
+        }
+    }
+
+    /// Power drawn while waiting, in watts
+    pub const fn drain(self) -> f64 {
+        match self {
+            Self::Burner => 0.0,
+            Self::Basic => 400.0,
+            Self::Fast => 1_400.0,
+            Self::Stack => 2_800.0,
+        }
+    }
+
+    /// Most items carried at once
+    pub const fn hand_size(self) -> u8 {
+        match self {
+            Self::Stack => MAX_STACK_HEIGHT,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Inserter {
+    pub kind: InserterKind,
+    /// Direction from the pickup tile to the drop tile
+    pub direction: Direction,
+    pub held: Option<(Item, Quality)>,
+    /// Number of `held` items in the hand
+    pub held_count: u8,
+    /// Turns away from the pickup tile, 0.5 is above the drop tile
+    pub angle: f64,
+    /// Number of items dropped so far
+    pub moved: u64,
+    /// Fuel of burner inserters, which refuel themselves from fuel they pick up
+    pub burner: Option<Burner>,
+    /// Quality of the inserter itself, which scales its rotation speed
+    pub quality: Quality,
+}
+
+#[allow(dead_code)]
+impl Inserter {
+    pub const fn new(kind: InserterKind, direction: Direction) -> Self {
+        Self {
+            kind,
+            direction,
+            held: None,
+            held_count: 0,
+            angle: 0.0,
+            moved: 0,
+            burner: match kind {
+                InserterKind::Burner => Some(Burner::new()),
+                _ => None,
+            },
+            quality: Quality::Normal,
+        }
+    }
+
+    pub const fn with_quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub const fn pickup(&self, coordinate: Coordinate) -> Coordinate {
+        coordinate.neighbor(self.direction.opposite())
+    }
+
+    pub const fn drop_target(&self, coordinate: Coordinate) -> Coordinate {
+        coordinate.neighbor(self.direction)
+    }
+
+    /// Whether the hand is moving, i.e. carrying an item or on its way back
+    pub fn is_active(&self) -> bool {
+        self.held.is_some() || self.angle > 0.0
+    }
+
+    /// Power the inserter asks for this tick. Waiting inserters only draw their drain.
+    pub fn power_demand(&self) -> f64 {
+        if self.is_active() {
+            self.kind.energy_usage()
+        } else {
+            self.kind.drain()
+        }
+    }
+}
+
+/// Slot of the `wanted` stack closest to the end of `lane`
+fn front_stack(lane: &SingleBeltLane, wanted: (Item, Quality)) -> Option<(u32, usize)> {
+    lane.items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, slot)| {
+            let (item, position) = (*slot)?;
+            (wanted == (item, lane.quality(index))).then_some((position, index))
+        })
+        .max()
+}
+
+/// Removes up to `limit` `wanted` items from the stacks closest to the end of either
+/// lane. Returns how many were taken.
+fn take_from_lane(lanes: [&mut SingleBeltLane; 2], wanted: (Item, Quality), limit: u8) -> u8 {
+    let [left, right] = lanes;
+    let mut count = 0;
+    while count < limit {
+        // The left lane goes first when both fronts are level
+        let (lane, index) = match (front_stack(left, wanted), front_stack(right, wanted)) {
+            (Some(l), Some(r)) if r.0 > l.0 => (&mut *right, r.1),
+            (Some(l), _) => (&mut *left, l.1),
+            (None, Some(r)) => (&mut *right, r.1),
+            (None, None) => break,
+        };
+        count += lane.take_from_stack(index, limit - count);
+    }
+    count
+}
+
+/// Kinds of items on both lanes, the one closest to the end first
+fn lane_offers(lanes: [&SingleBeltLane; 2]) -> Vec<(Item, Quality)> {
+    let mut stacks: Vec<(u32, usize, (Item, Quality))> = lanes
+        .iter()
+        .enumerate()
+        .flat_map(|(lane_index, lane)| {
+            lane.items
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, slot)| {
+                    slot.map(|(item, position)| (position, lane_index, (item, lane.quality(index))))
+                })
+        })
+        .collect();
+    // The left lane goes first when both fronts are level
+    stacks.sort_by_key(|&(position, lane_index, _)| (std::cmp::Reverse(position), lane_index));
+    stacks.into_iter().map(|(_, _, key)| key).collect()
+}
+
+/// Whether an inserter dropping in `drop` direction puts items on the left lane of a
+/// belt facing `belt`. Inserters always use the far lane; from behind or in front of
+/// the belt, they use the right one.
+pub const fn drops_on_left_lane(belt: Option<Direction>, drop: Direction) -> bool {
+    let Some(belt) = belt else {
+        return false;
+    };
+    let (bx, by) = belt.offset();
+    let (dx, dy) = drop.offset();
+    // With y pointing south, a negative cross product means `drop` is
+    // counterclockwise of `belt`, so the far lane is the left one
+    bx * dy - by * dx < 0
+}
+
+impl World {
+    #[allow(dead_code)]
+    pub fn add_inserter(&mut self, coordinate: Coordinate, inserter: Inserter) {
+        self.inserters.insert(coordinate, inserter);
+    }
+
+    /// Kinds of items the entity at `from` offers to inserters, the one taken first
+    /// first
+    fn offers(&self, from: Coordinate) -> Vec<(Item, Quality)> {
+        if let Some(belt) = self.belts.get(&from) {
+            return lane_offers([&belt.left_lane, &belt.right_lane]);
+        }
+        if let Some(vehicle) = self.rails.vehicle_at(from) {
+            return vehicle.item_kinds();
+        }
+        if let Some(chest) = self.logistics.chests.get(&from) {
+            return chest.items.keys().copied().collect();
+        }
+        let Some(machine) = self.machines.get(&from) else {
+            return Vec::new();
+        };
+        let mut outputs: Vec<(Item, Quality)> = machine.item_outputs.keys().copied().collect();
+        outputs.sort_unstable();
+        outputs
+    }
+
+    /// Whether the entity at `to` takes the item now. Belts take anything, once there
+    /// is room.
+    fn accepts_drop(&self, to: Coordinate, (item, quality): (Item, Quality)) -> bool {
+        if self.belts.contains_key(&to) {
+            return true;
+        }
+        let fuel_value = self.fuels.get(&item).copied();
+        if let Some(vehicle) = self.rails.vehicle_at(to) {
+            return vehicle.accepts_item(item, fuel_value);
+        }
+        if let Some(chest) = self.logistics.chests.get(&to) {
+            return chest.has_room();
+        }
+        self.can_insert_into(to, item, quality)
+    }
+
+    /// Takes up to `limit` `wanted` items from the entity at `from`. Returns how many
+    /// were taken.
+    fn pick_up(&mut self, from: Coordinate, wanted: (Item, Quality), limit: u8) -> u8 {
+        let (item, quality) = wanted;
+        if let Some(belt) = self.belts.get_mut(&from) {
+            return take_from_lane([&mut belt.left_lane, &mut belt.right_lane], wanted, limit);
+        }
+        let mut count = 0;
+        if let Some(vehicle) = self.rails.vehicle_at_mut(from) {
+            while count < limit && vehicle.take(item, quality) {
+                count += 1;
+            }
+        } else if let Some(chest) = self.logistics.chests.get_mut(&from) {
+            while count < limit && chest.take(item, quality) {
+                count += 1;
+            }
+        } else if let Some(machine) = self.machines.get_mut(&from) {
+            let taken = machine.take_output(item, quality, u32::from(limit));
+            count = u8::try_from(taken).unwrap_or(limit);
+        }
+        count
+    }
+
+    /// Drops `count` items onto the entity at `to`: as one stack onto a belt, or one at a
+    /// time anywhere else. Returns how many were dropped.
+    fn drop_items(
+        &mut self,
+        to: Coordinate,
+        direction: Direction,
+        (item, quality): (Item, Quality),
+        count: u8,
+    ) -> u8 {
+        if let Some(belt) = self.belts.get_mut(&to) {
+            let lane = if drops_on_left_lane(belt.direction(), direction) {
+                &mut belt.left_lane
+            } else {
+                &mut belt.right_lane
+            };
+            // One stack per free spot that keeps its distance to the items around it
+            let mut dropped = 0;
+            while dropped < count
+                && lane
+                    .accept_position(DROP_POSITION)
+                    .is_some_and(|position| lane.fits_at(position))
+            {
+                let height = (count - dropped).min(MAX_STACK_HEIGHT);
+                if !lane.accept_stack(item, quality, height, DROP_POSITION) {
+                    break;
+                }
+                dropped += height;
+            }
+            return dropped;
+        }
+        let mut dropped = 0;
+        while dropped < count && self.drop_item(to, (item, quality)) {
+            dropped += 1;
+        }
+        dropped
+    }
+
+    /// Puts one item into the vehicle, chest or machine at `to`
+    fn drop_item(&mut self, to: Coordinate, (item, quality): (Item, Quality)) -> bool {
+        let fuel_value = self.fuels.get(&item).copied();
+        if let Some(vehicle) = self.rails.vehicle_at_mut(to) {
+            return vehicle.insert_item(item, quality, fuel_value);
+        }
+        if let Some(chest) = self.logistics.chests.get_mut(&to) {
+            return chest.insert_item(item, quality);
+        }
+        self.insert_into(to, item, quality)
+    }
+
+    /// Moves every inserter's hand by one tick, scaled by the power satisfaction
+    /// returned by `satisfaction` for its coordinate, or by its fuel for burner inserters
+    pub fn tick_inserters(&mut self, satisfaction: impl Fn(Coordinate) -> f64) {
+        let mut coordinates: Vec<Coordinate> = self.inserters.keys().copied().collect();
+        coordinates.sort_by_key(|c| (c.y, c.x));
+        for coordinate in coordinates {
+            let Some(mut inserter) = self.inserters.get(&coordinate).copied() else {
+                continue;
+            };
+            let demand = inserter.power_demand();
+            let factor = inserter
+                .burner
+                .as_mut()
+                .map_or_else(|| satisfaction(coordinate), |burner| burner.burn(demand));
+            let step = inserter.kind.rotation_speed() * inserter.quality.multiplier() * factor;
+            match inserter.held {
+                Some(item) => {
+                    inserter.angle = (inserter.angle + step).min(0.5);
+                    if inserter.angle >= 0.5 {
+                        let dropped = self.drop_items(
+                            inserter.drop_target(coordinate),
+                            inserter.direction,
+                            item,
+                            inserter.held_count,
+                        );
+                        inserter.held_count -= dropped;
+                        inserter.moved += u64::from(dropped);
+                        if inserter.held_count == 0 {
+                            inserter.held = None;
+                        }
+                    }
+                }
+                None if inserter.angle > 0.0 => {
+                    inserter.angle = (inserter.angle - step).max(0.0);
+                }
+                // Picking up needs power too, like every other part of the swing
+                None if step > 0.0 => {
+                    // Only items the drop target takes are picked up, and fuel for a
+                    // burner inserter that has none
+                    let from = inserter.pickup(coordinate);
+                    let to = inserter.drop_target(coordinate);
+                    let needs_fuel = inserter.burner.is_some_and(|burner| burner.fuel.is_none());
+                    let wanted = self.offers(from).into_iter().find(|&(item, quality)| {
+                        (needs_fuel && self.fuels.contains_key(&item))
+                            || self.accepts_drop(to, (item, quality))
+                    });
+                    let count = wanted.map_or(0, |wanted| {
+                        self.pick_up(from, wanted, inserter.kind.hand_size())
+                    });
+                    inserter.held = wanted.filter(|_| count > 0);
+                    inserter.held_count = count;
+                    // A burner inserter without fuel keeps the fuel it picked up
+                    if let (Some((item, _)), Some(burner)) = (inserter.held, &mut inserter.burner)
+                        && burner.fuel.is_none()
+                        && let Some(&fuel_value) = self.fuels.get(&item)
+                        && burner.insert_fuel(item, 1, fuel_value) == 1
+                    {
+                        inserter.held = None;
+                        inserter.held_count = 0;
+                    }
+                }
+                None => {}
+            }
+            self.inserters.insert(coordinate, inserter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
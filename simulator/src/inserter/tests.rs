@@ -0,0 +1,321 @@
+use super::*;
+use crate::{
+    BeltType, SingleBelt,
+    crafting::{CraftingMachine, Recipe},
+    power::{GeneratorKind, PoleKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+/// A belt at (0, 0) with one item, an inserter at (1, 0) and an assembler at (2, 0),
+/// powered by `panels` solar panels
+fn belt_to_machine(panels: i32) -> World {
+    let mut world = World::new();
+    let mut belt = SingleBelt::new(Coordinate::new(0, 0), BeltType::Regular, None, None);
+    belt.left_lane.items[0] = Some((id(1), 200));
+    world.add_belt(belt);
+    world.add_inserter(
+        Coordinate::new(1, 0),
+        Inserter::new(InserterKind::Basic, Direction::East),
+    );
+    world.add_machine(
+        Coordinate::new(2, 0),
+        CraftingMachine::new(1.0).with_recipe(
+            Recipe::new("iron-gear-wheel", 0.5)
+                .item_ingredient(id(1), 2)
+                .item_product(id(2), 1),
+        ),
+    );
+    world.power.add_pole(Coordinate::new(1, 1), PoleKind::Small);
+    for x in 0..panels {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, 2), GeneratorKind::SolarPanel);
+    }
+    world
+}
+
+fn inserted(world: &World) -> u32 {
+    world.machines[&Coordinate::new(2, 0)]
+        .item_inputs
+        .get(&id(1))
+        .copied()
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_inserter_moves_item_in_half_a_turn() {
+    let mut world = belt_to_machine(1);
+    world.tick();
+    assert_eq!(
+        world.inserters[&Coordinate::new(1, 0)].held,
+        Some((id(1), Quality::Normal))
+    );
+    assert!(world.belts[&Coordinate::new(0, 0)].left_lane.items[0].is_none());
+
+    // 0.5 turns at 0.014 turns per tick
+    for _ in 0..35 {
+        world.tick();
+    }
+    assert_eq!(inserted(&world), 0);
+    world.tick();
+    assert_eq!(inserted(&world), 1);
+
+    let inserter = world.inserters[&Coordinate::new(1, 0)];
+    assert_eq!(inserter.held, None);
+    assert_eq!(inserter.moved, 1);
+    // The hand swings back before it picks up again
+    for _ in 0..36 {
+        world.tick();
+    }
+    assert!(world.inserters[&Coordinate::new(1, 0)].angle.abs() < 1e-9);
+}
+
+#[test]
+fn test_brownout_slows_inserter() {
+    let mut world = belt_to_machine(1);
+    // 6.6 kW for an inserter that needs 13.2 kW while moving
+    world.power.daylight = 0.11;
+    for _ in 0..60 {
+        world.tick();
+    }
+    assert_eq!(inserted(&world), 0);
+    for _ in 0..20 {
+        world.tick();
+    }
+    assert_eq!(inserted(&world), 1);
+}
+
+#[test]
+fn test_unpowered_inserter_does_nothing() {
+    let mut world = belt_to_machine(0);
+    for _ in 0..60 {
+        world.tick();
+    }
+    assert_eq!(world.inserters[&Coordinate::new(1, 0)].held, None);
+    assert!(world.belts[&Coordinate::new(0, 0)].left_lane.items[0].is_some());
+}
+
+#[test]
+fn test_inserter_drops_on_far_lane() {
+    // Belt facing north: dropping east reaches over to the right lane
+    assert!(!drops_on_left_lane(Some(Direction::North), Direction::East));
+    assert!(drops_on_left_lane(Some(Direction::North), Direction::West));
+    assert!(drops_on_left_lane(Some(Direction::East), Direction::North));
+    // From behind the belt, or onto a belt without a direction
+    assert!(!drops_on_left_lane(
+        Some(Direction::North),
+        Direction::North
+    ));
+    assert!(!drops_on_left_lane(None, Direction::South));
+}
+
+#[test]
+fn test_inserter_takes_machine_output_onto_belt() {
+    let mut world = World::new();
+    let mut machine = CraftingMachine::new(1.0);
+    machine.item_outputs.insert((id(3), Quality::Normal), 1);
+    world.add_machine(Coordinate::new(0, 0), machine);
+    world.add_inserter(
+        Coordinate::new(0, 1),
+        Inserter::new(InserterKind::Fast, Direction::South),
+    );
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(0, 2),
+        BeltType::Regular,
+        Some(Coordinate::new(1, 2)),
+        Some(Coordinate::new(1, 2)),
+    ));
+    world.power.add_pole(Coordinate::new(1, 1), PoleKind::Small);
+    world
+        .power
+        .generators
+        .insert(Coordinate::new(2, 1), GeneratorKind::SolarPanel);
+
+    // Pick up, then 0.5 turns at 0.04 turns per tick
+    for _ in 0..14 {
+        world.tick();
+    }
+    let belt = &world.belts[&Coordinate::new(0, 2)];
+    // The belt faces east, so dropping south puts the item on the right lane
+    assert_eq!(belt.right_lane.items[0], Some((id(3), DROP_POSITION)));
+    assert!(
+        world.machines[&Coordinate::new(0, 0)]
+            .item_outputs
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_burner_inserter_fuels_itself() {
+    let mut world = belt_to_machine(0);
+    world.add_fuel(id(9), 4_000_000.0);
+    world.add_inserter(
+        Coordinate::new(1, 0),
+        Inserter::new(InserterKind::Burner, Direction::East),
+    );
+    let lane = &mut world
+        .belts
+        .get_mut(&Coordinate::new(0, 0))
+        .expect("belt exists")
+        .right_lane;
+    lane.items[0] = Some((id(9), 255));
+
+    // The coal is taken first and kept as fuel
+    world.tick();
+    let inserter = world.inserters[&Coordinate::new(1, 0)];
+    assert_eq!(inserter.held, None);
+    assert_eq!(inserter.burner.map(|burner| burner.fuel_count()), Some(1));
+
+    // Then the ore goes over at 0.01 turns per tick
+    world.tick();
+    assert_eq!(
+        world.inserters[&Coordinate::new(1, 0)].held,
+        Some((id(1), Quality::Normal))
+    );
+    for _ in 0..50 {
+        world.tick();
+    }
+    assert_eq!(inserted(&world), 1);
+}
+
+#[test]
+fn test_stack_inserter_fills_its_hand_from_the_belt() {
+    let mut world = belt_to_machine(3);
+    world.add_inserter(
+        Coordinate::new(1, 0),
+        Inserter::new(InserterKind::Stack, Direction::East),
+    );
+    let belt = world
+        .belts
+        .get_mut(&Coordinate::new(0, 0))
+        .expect("belt exists");
+    belt.left_lane.items[1] = Some((id(1), 100));
+    belt.left_lane.items[2] = Some((id(1), 20));
+    belt.right_lane.items[0] = Some((id(1), 250));
+    belt.right_lane.items[1] = Some((id(2), 150));
+
+    // The four iron plates closest to the end of either lane, but not the gear
+    world.tick();
+    let inserter = world.inserters[&Coordinate::new(1, 0)];
+    assert_eq!(inserter.held, Some((id(1), Quality::Normal)));
+    assert_eq!(inserter.held_count, 4);
+    let belt = &world.belts[&Coordinate::new(0, 0)];
+    assert_eq!(belt.left_lane.item_count(), 0);
+    assert_eq!(belt.right_lane.items[1], Some((id(2), 158)));
+
+    // All four go into the assembler at once
+    for _ in 0..13 {
+        world.tick();
+    }
+    let inserter = world.inserters[&Coordinate::new(1, 0)];
+    assert_eq!((inserter.held, inserter.held_count), (None, 0));
+    assert_eq!(inserter.moved, 4);
+}
+
+/// An assembler at (0, 0) with 6 gears done, a powered stack inserter at (0, 1) and
+/// a turbo belt at (0, 2)
+fn machine_to_belt() -> World {
+    let mut world = World::new();
+    let mut machine = CraftingMachine::new(1.0);
+    machine.item_outputs.insert((id(3), Quality::Normal), 6);
+    world.add_machine(Coordinate::new(0, 0), machine);
+    world.add_inserter(
+        Coordinate::new(0, 1),
+        Inserter::new(InserterKind::Stack, Direction::South),
+    );
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(0, 2),
+        BeltType::Turbo,
+        None,
+        None,
+    ));
+    world.power.add_pole(Coordinate::new(1, 1), PoleKind::Small);
+    for y in 0..3 {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(2, y), GeneratorKind::SolarPanel);
+    }
+    world
+}
+
+#[test]
+fn test_stack_inserter_drops_a_stack_onto_the_belt() {
+    let mut world = machine_to_belt();
+    for _ in 0..14 {
+        world.tick();
+    }
+    let lane = &world.belts[&Coordinate::new(0, 2)].right_lane;
+    assert_eq!(lane.items[0], Some((id(3), DROP_POSITION)));
+    assert_eq!(lane.stack_height(0), 4);
+    assert_eq!(lane.item_count(), 4);
+    assert_eq!(
+        world.machines[&Coordinate::new(0, 0)].item_outputs[&(id(3), Quality::Normal)],
+        2
+    );
+}
+
+#[test]
+fn test_stack_inserter_waits_for_room_on_the_belt() {
+    let mut world = machine_to_belt();
+    let lane = &mut world
+        .belts
+        .get_mut(&Coordinate::new(0, 2))
+        .expect("belt exists")
+        .right_lane;
+    lane.items[0] = Some((id(9), DROP_POSITION + 30));
+    lane.enabled = false;
+
+    // The gear ahead is too close to the drop position
+    for _ in 0..20 {
+        world.tick();
+    }
+    let inserter = world.inserters[&Coordinate::new(0, 1)];
+    assert_eq!(inserter.held_count, 4);
+    assert_eq!(world.belt_item_count(Coordinate::new(0, 2)), 1);
+
+    // Once it moves on, the whole hand goes down as one stack
+    world
+        .belts
+        .get_mut(&Coordinate::new(0, 2))
+        .expect("belt exists")
+        .right_lane
+        .enabled = true;
+    for _ in 0..3 {
+        world.tick();
+    }
+    assert_eq!(world.inserters[&Coordinate::new(0, 1)].held_count, 0);
+    assert_eq!(world.belt_item_count(Coordinate::new(0, 2)), 5);
+}
+
+#[test]
+fn test_inserter_only_picks_up_what_the_target_takes() {
+    let mut world = belt_to_machine(1);
+    let belt = world
+        .belts
+        .get_mut(&Coordinate::new(0, 0))
+        .expect("belt exists");
+    belt.right_lane.items[0] = Some((id(5), 250));
+
+    // The assembler has no use for item 5, even though it is closest to the end
+    world.tick();
+    assert_eq!(
+        world.inserters[&Coordinate::new(1, 0)].held,
+        Some((id(1), Quality::Normal))
+    );
+    assert!(world.belts[&Coordinate::new(0, 0)].right_lane.items[0].is_some());
+
+    // With only item 5 left, the inserter waits with an empty hand
+    for _ in 0..100 {
+        world.tick();
+    }
+    let inserter = world.inserters[&Coordinate::new(1, 0)];
+    assert_eq!(inserter.held, None);
+    assert_eq!(inserter.moved, 1);
+    assert!(world.belts[&Coordinate::new(0, 0)].right_lane.items[0].is_some());
+}
@@ -0,0 +1,818 @@
+//! Logistic networks: roboports, logistic and construction robots, and logistic chests.
+//!
+//! Roboports whose logistic areas overlap or touch form a network, and a chest belongs
+//! to the network of the first roboport whose logistic area covers it. Every tick the
+//! robots resting in a network's roboports are sent out one item at a time: to fill
+//! requester and buffer chests from the closest chest that has the item, trying active
+//! providers first, then storage, passive provider and buffer chests, and to empty
+//! active providers into storage chests. Construction robots fetch the item of a ghost
+//! in the construction area of a roboport from the same chests and build it.
+//!
+//! Robots fly straight towards their target and use energy for every tile flown and
+//! while flying at all. When their battery runs low they fly to the closest roboport
+//! and queue for one of its charging slots; without any energy left they crawl at a
+//! fifth of their speed. Robots without a job return to the closest roboport, where
+//! they charge first if their battery is low. Roboports charge robots from a buffer
+//! they fill from the electric network.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::{Coordinate, Item, TICKS_PER_SECOND, World, quality::Quality};
+
+/// Tiles covered in every direction by the logistic area of a roboport
+pub const LOGISTIC_RADIUS: i32 = 25;
+/// Tiles covered in every direction by the construction area of a roboport
+pub const CONSTRUCTION_RADIUS: i32 = 55;
+/// 48 slots of 50 items
+pub const CHEST_CAPACITY: u32 = 2_400;
+/// Energy a robot holds when fully charged, in joules
+pub const ROBOT_BATTERY: f64 = 1_500_000.0;
+/// Energy used for every tile flown, in joules
+const ENERGY_PER_TILE: f64 = 5_000.0;
+/// Power used while flying, in watts
+const FLYING_DRAIN: f64 = 3_000.0;
+/// Robots look for a roboport once their battery is below this fraction
+const LOW_BATTERY: f64 = 0.2;
+/// Fraction of their speed robots fly at without energy
+const NO_ENERGY_SPEED: f64 = 0.2;
+/// Energy a roboport can store for charging robots, in joules
+pub const ROBOPORT_BUFFER: f64 = 100_000_000.0;
+/// Most power a roboport draws to refill its buffer, in watts
+const ROBOPORT_MAX_INPUT: f64 = 5_000_000.0;
+/// Power a roboport always draws, in watts
+const ROBOPORT_DRAIN: f64 = 50_000.0;
+pub const CHARGING_SLOTS: usize = 4;
+/// Power of each charging slot, in watts
+const CHARGING_POWER: f64 = 500_000.0;
+
+/// Distances below this count as arrived
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RobotKind {
+    Logistic,
+    Construction,
+}
+
+impl RobotKind {
+    /// Speed in tiles per tick
+    pub const fn speed(self) -> f64 {
+        match self {
+            Self::Logistic => 0.05,
+            Self::Construction => 0.06,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum ChestKind {
+    /// Sends everything to storage chests
+    ActiveProvider,
+    /// Offers its items to requests
+    PassiveProvider,
+    /// Keeps what active providers send and offers it to requests
+    Storage,
+    /// Asks for items
+    Requester,
+    /// Asks for items and offers them to requester chests
+    Buffer,
+}
+
+impl ChestKind {
+    /// Order in which chests are emptied for requests, `None` for chests that are not
+    const fn provider_rank(self) -> Option<u8> {
+        match self {
+            Self::ActiveProvider => Some(0),
+            Self::Storage => Some(1),
+            Self::PassiveProvider => Some(2),
+            Self::Buffer => Some(3),
+            Self::Requester => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogisticChest {
+    pub kind: ChestKind,
+    pub items: BTreeMap<(Item, Quality), u32>,
+    /// Items a requester or buffer chest asks for, of any quality
+    pub requests: BTreeMap<Item, u32>,
+    /// Number of items robots brought so far
+    pub delivered: u64,
+}
+
+#[allow(dead_code)]
+impl LogisticChest {
+    pub const fn new(kind: ChestKind) -> Self {
+        Self {
+            kind,
+            items: BTreeMap::new(),
+            requests: BTreeMap::new(),
+            delivered: 0,
+        }
+    }
+
+    pub fn with_request(mut self, item: Item, count: u32) -> Self {
+        self.requests.insert(item, count);
+        self
+    }
+
+    pub fn with_items(mut self, item: Item, quality: Quality, count: u32) -> Self {
+        *self.items.entry((item, quality)).or_insert(0) += count;
+        self
+    }
+
+    pub fn item_count(&self) -> u32 {
+        self.items.values().sum()
+    }
+
+    /// Number of `item` in the chest, of any quality
+    pub fn count(&self, item: Item) -> u32 {
+        self.items
+            .iter()
+            .filter(|((stored, _), _)| *stored == item)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Whether there is room for one more item
+    pub fn has_room(&self) -> bool {
+        self.item_count() < CHEST_CAPACITY
+    }
+
+    /// Puts one item into the chest. Returns whether there was room.
+    pub fn insert_item(&mut self, item: Item, quality: Quality) -> bool {
+        if !self.has_room() {
+            return false;
+        }
+        *self.items.entry((item, quality)).or_insert(0) += 1;
+        true
+    }
+
+    /// Takes one item of `item` and `quality`. Returns whether there was one.
+    pub fn take(&mut self, item: Item, quality: Quality) -> bool {
+        let Some(count) = self.items.get_mut(&(item, quality)) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.items.remove(&(item, quality));
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roboport {
+    /// Energy in the buffer, in joules
+    pub energy: f64,
+    /// Robots waiting for a charging slot, by index
+    pub queue: VecDeque<usize>,
+    /// Robots in a charging slot, by index
+    pub charging: Vec<usize>,
+}
+
+impl Roboport {
+    pub const fn new() -> Self {
+        Self {
+            energy: 0.0,
+            queue: VecDeque::new(),
+            charging: Vec::new(),
+        }
+    }
+
+    /// Power the roboport asks for this tick: its drain and what its buffer can take
+    pub fn power_demand(&self) -> f64 {
+        ROBOPORT_DRAIN + ROBOPORT_MAX_INPUT.min((ROBOPORT_BUFFER - self.energy) * TICKS_PER_SECOND)
+    }
+}
+
+/// An item to move from one chest to another chest or a ghost
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Job {
+    pub from: Coordinate,
+    pub to: Coordinate,
+    pub item: Item,
+    pub quality: Quality,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RobotState {
+    /// Resting in a roboport, ready for a job
+    Parked(Coordinate),
+    Flying,
+    /// Waiting for a charging slot of a roboport
+    Queued(Coordinate),
+    Charging(Coordinate),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Robot {
+    pub kind: RobotKind,
+    pub position: (f64, f64),
+    /// Energy in the battery, in joules
+    pub energy: f64,
+    pub cargo: Option<(Item, Quality)>,
+    pub job: Option<Job>,
+    pub state: RobotState,
+    /// Tiles flown so far
+    pub distance: f64,
+}
+
+/// Middle of the tile at `coordinate`
+fn position(coordinate: Coordinate) -> (f64, f64) {
+    (f64::from(coordinate.x), f64::from(coordinate.y))
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+const fn covers(roboport: Coordinate, coordinate: Coordinate, radius: i32) -> bool {
+    (roboport.x - coordinate.x).abs() <= radius && (roboport.y - coordinate.y).abs() <= radius
+}
+
+/// Orders `a` and `b` by their distance to `to`, then by coordinate
+fn closer(to: Coordinate, a: Coordinate, b: Coordinate) -> std::cmp::Ordering {
+    distance(position(a), position(to))
+        .total_cmp(&distance(position(b), position(to)))
+        .then((a.y, a.x).cmp(&(b.y, b.x)))
+}
+
+/// Items robots are on their way to bring to or take from every chest
+#[derive(Default)]
+struct Reservations {
+    incoming: HashMap<Coordinate, u32>,
+    incoming_items: HashMap<(Coordinate, Item), u32>,
+    outgoing: HashMap<(Coordinate, Item, Quality), u32>,
+}
+
+impl Reservations {
+    fn add(&mut self, job: Job, picked_up: bool) {
+        *self.incoming.entry(job.to).or_insert(0) += 1;
+        *self.incoming_items.entry((job.to, job.item)).or_insert(0) += 1;
+        if !picked_up {
+            *self
+                .outgoing
+                .entry((job.from, job.item, job.quality))
+                .or_insert(0) += 1;
+        }
+    }
+
+    fn incoming(&self, to: Coordinate) -> u32 {
+        self.incoming.get(&to).copied().unwrap_or(0)
+    }
+
+    fn incoming_item(&self, to: Coordinate, item: Item) -> u32 {
+        self.incoming_items.get(&(to, item)).copied().unwrap_or(0)
+    }
+
+    fn outgoing(&self, from: Coordinate, item: Item, quality: Quality) -> u32 {
+        self.outgoing
+            .get(&(from, item, quality))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// All logistic entities of a world
+#[derive(Debug, Default)]
+pub struct LogisticSystem {
+    pub roboports: HashMap<Coordinate, Roboport>,
+    pub chests: HashMap<Coordinate, LogisticChest>,
+    pub robots: Vec<Robot>,
+    /// Items to be built by construction robots
+    pub ghosts: HashMap<Coordinate, Item>,
+    /// Ghosts built so far, in the order they were built
+    pub built: Vec<(Coordinate, Item, Quality)>,
+    /// Network index of every roboport
+    network_of: HashMap<Coordinate, usize>,
+    /// Roboports in coordinate order
+    roboport_order: Vec<Coordinate>,
+    /// Network index of every chest inside a network
+    chest_network: HashMap<Coordinate, usize>,
+}
+
+#[allow(dead_code)]
+impl LogisticSystem {
+    /// Adds a roboport with fully charged robots resting in it
+    pub fn add_roboport(
+        &mut self,
+        coordinate: Coordinate,
+        logistic_robots: usize,
+        construction_robots: usize,
+    ) {
+        self.roboports.insert(coordinate, Roboport::new());
+        let kinds = std::iter::repeat_n(RobotKind::Logistic, logistic_robots).chain(
+            std::iter::repeat_n(RobotKind::Construction, construction_robots),
+        );
+        for kind in kinds {
+            self.robots.push(Robot {
+                kind,
+                position: position(coordinate),
+                energy: ROBOT_BATTERY,
+                cargo: None,
+                job: None,
+                state: RobotState::Parked(coordinate),
+                distance: 0.0,
+            });
+        }
+        self.rebuild_networks();
+    }
+
+    pub fn add_chest(&mut self, coordinate: Coordinate, chest: LogisticChest) {
+        self.chests.insert(coordinate, chest);
+        match self.network_at(coordinate, LOGISTIC_RADIUS) {
+            Some(network) => self.chest_network.insert(coordinate, network),
+            None => self.chest_network.remove(&coordinate),
+        };
+    }
+
+    pub fn add_ghost(&mut self, coordinate: Coordinate, item: Item) {
+        self.ghosts.insert(coordinate, item);
+    }
+
+    fn sorted_roboports(&self) -> Vec<Coordinate> {
+        self.roboport_order.clone()
+    }
+
+    /// Works out the networks of all roboports and chests again
+    fn rebuild_networks(&mut self) {
+        let mut roboports: Vec<Coordinate> = self.roboports.keys().copied().collect();
+        roboports.sort_by_key(|c| (c.y, c.x));
+        self.network_of.clear();
+        let mut networks = 0;
+        for &start in &roboports {
+            if self.network_of.contains_key(&start) {
+                continue;
+            }
+            self.network_of.insert(start, networks);
+            let mut queue = vec![start];
+            while let Some(current) = queue.pop() {
+                for &other in &roboports {
+                    if !self.network_of.contains_key(&other)
+                        && covers(current, other, 2 * LOGISTIC_RADIUS)
+                    {
+                        self.network_of.insert(other, networks);
+                        queue.push(other);
+                    }
+                }
+            }
+            networks += 1;
+        }
+        self.roboport_order = roboports;
+        self.chest_network = self
+            .chests
+            .keys()
+            .filter_map(|c| Some((*c, self.network_at(*c, LOGISTIC_RADIUS)?)))
+            .collect();
+    }
+
+    /// Network of the first roboport within `radius` of `coordinate`
+    pub fn network_at(&self, coordinate: Coordinate, radius: i32) -> Option<usize> {
+        self.roboport_order
+            .iter()
+            .find(|roboport| covers(**roboport, coordinate, radius))
+            .and_then(|roboport| self.network_of.get(roboport).copied())
+    }
+
+    /// Closest roboport to `from`, the first one in coordinate order on a tie
+    fn nearest_roboport(&self, from: (f64, f64)) -> Option<Coordinate> {
+        self.roboport_order
+            .iter()
+            .copied()
+            .min_by(|a, b| distance(from, position(*a)).total_cmp(&distance(from, position(*b))))
+    }
+
+    /// Network of the chest at `coordinate`, `None` if it is outside of every network
+    fn chest_network(&self, coordinate: Coordinate) -> Option<usize> {
+        self.chest_network.get(&coordinate).copied()
+    }
+
+    /// Empty robots of `kind` resting in a roboport of `network`, the one closest to
+    /// `near`
+    fn find_robot(&self, kind: RobotKind, network: usize, near: Coordinate) -> Option<usize> {
+        let mut best: Option<(f64, usize)> = None;
+        for (index, robot) in self.robots.iter().enumerate() {
+            let RobotState::Parked(roboport) = robot.state else {
+                continue;
+            };
+            if robot.kind != kind
+                || robot.cargo.is_some()
+                || self.network_of.get(&roboport) != Some(&network)
+            {
+                continue;
+            }
+            let distance = distance(position(roboport), position(near));
+            if best.is_none_or(|(best, _)| distance < best) {
+                best = Some((distance, index));
+            }
+        }
+        best.map(|(_, index)| index)
+    }
+
+    /// Chest of `network` to take `item` from for a chest or ghost at `to`: the best
+    /// ranked provider, then the closest one
+    fn find_source(
+        &self,
+        network: usize,
+        item: Item,
+        to: Coordinate,
+        reserved: &Reservations,
+    ) -> Option<(Coordinate, Quality)> {
+        // Buffer chests do not feed each other
+        let to_buffer = self
+            .chests
+            .get(&to)
+            .is_some_and(|target| target.kind == ChestKind::Buffer);
+        let mut sources: Vec<(u8, Coordinate, Quality)> = self
+            .chests
+            .iter()
+            .filter_map(|(coordinate, chest)| {
+                let rank = chest.kind.provider_rank()?;
+                if *coordinate == to
+                    || (to_buffer && chest.kind == ChestKind::Buffer)
+                    || self.chest_network(*coordinate) != Some(network)
+                {
+                    return None;
+                }
+                let quality = chest.items.iter().find_map(|((stored, quality), count)| {
+                    let taken = reserved.outgoing(*coordinate, item, *quality);
+                    (*stored == item && *count > taken).then_some(*quality)
+                })?;
+                Some((rank, *coordinate, quality))
+            })
+            .collect();
+        sources.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| closer(to, a.1, b.1)));
+        sources
+            .first()
+            .map(|(_, coordinate, quality)| (*coordinate, *quality))
+    }
+
+    /// Storage chest of `network` closest to `from` with room for one more item
+    fn find_storage(
+        &self,
+        network: usize,
+        from: Coordinate,
+        reserved: &Reservations,
+    ) -> Option<Coordinate> {
+        let mut storage: Vec<Coordinate> = self
+            .chests
+            .iter()
+            .filter(|(coordinate, chest)| {
+                chest.kind == ChestKind::Storage
+                    && chest.item_count() + reserved.incoming(**coordinate) < CHEST_CAPACITY
+                    && self.chest_network(**coordinate) == Some(network)
+            })
+            .map(|(coordinate, _)| *coordinate)
+            .collect();
+        storage.sort_by(|a, b| closer(from, *a, *b));
+        storage.first().copied()
+    }
+
+    /// Sends `robot` off to do `job`
+    fn assign(&mut self, robot: usize, job: Job, reserved: &mut Reservations) {
+        self.robots[robot].job = Some(job);
+        self.robots[robot].state = RobotState::Flying;
+        reserved.add(job, false);
+    }
+
+    /// Sends robots to bring what the requester or buffer chest at `to` is missing
+    fn dispatch_requests(&mut self, to: Coordinate, reserved: &mut Reservations) {
+        let chest = &self.chests[&to];
+        if !matches!(chest.kind, ChestKind::Requester | ChestKind::Buffer) {
+            return;
+        }
+        let Some(network) = self.chest_network(to) else {
+            return;
+        };
+        let requests: Vec<(Item, u32)> = chest
+            .requests
+            .iter()
+            .map(|(item, count)| (*item, *count))
+            .collect();
+        for (item, wanted) in requests {
+            loop {
+                let chest = &self.chests[&to];
+                if chest.count(item) + reserved.incoming_item(to, item) >= wanted
+                    || chest.item_count() + reserved.incoming(to) >= CHEST_CAPACITY
+                {
+                    break;
+                }
+                let Some((from, quality)) = self.find_source(network, item, to, reserved) else {
+                    break;
+                };
+                let Some(robot) = self.find_robot(RobotKind::Logistic, network, from) else {
+                    break;
+                };
+                let job = Job {
+                    from,
+                    to,
+                    item,
+                    quality,
+                };
+                self.assign(robot, job, reserved);
+            }
+        }
+    }
+
+    /// Sends construction robots to every ghost nobody is building yet
+    fn dispatch_ghosts(&mut self, reserved: &mut Reservations) {
+        let mut ghosts: Vec<(Coordinate, Item)> =
+            self.ghosts.iter().map(|(c, item)| (*c, *item)).collect();
+        ghosts.sort_by_key(|(c, _)| (c.y, c.x));
+        for (to, item) in ghosts {
+            if reserved.incoming(to) > 0 {
+                continue;
+            }
+            let Some(network) = self.network_at(to, CONSTRUCTION_RADIUS) else {
+                continue;
+            };
+            let Some((from, quality)) = self.find_source(network, item, to, reserved) else {
+                continue;
+            };
+            let Some(robot) = self.find_robot(RobotKind::Construction, network, from) else {
+                continue;
+            };
+            let job = Job {
+                from,
+                to,
+                item,
+                quality,
+            };
+            self.assign(robot, job, reserved);
+        }
+    }
+
+    /// Sends robots to empty the active provider chest at `from` into storage
+    fn dispatch_active_provider(&mut self, from: Coordinate, reserved: &mut Reservations) {
+        let chest = &self.chests[&from];
+        if chest.kind != ChestKind::ActiveProvider {
+            return;
+        }
+        let Some(network) = self.chest_network(from) else {
+            return;
+        };
+        let items: Vec<((Item, Quality), u32)> = chest
+            .items
+            .iter()
+            .map(|(key, count)| (*key, *count))
+            .collect();
+        for ((item, quality), count) in items {
+            for _ in reserved.outgoing(from, item, quality)..count {
+                let Some(to) = self.find_storage(network, from, reserved) else {
+                    return;
+                };
+                let Some(robot) = self.find_robot(RobotKind::Logistic, network, from) else {
+                    return;
+                };
+                let job = Job {
+                    from,
+                    to,
+                    item,
+                    quality,
+                };
+                self.assign(robot, job, reserved);
+            }
+        }
+    }
+
+    /// Gives `robot`, standing at `at` with cargo nobody took, a job to bring it to the
+    /// closest storage chest with room. Without one it has no job and flies home to the
+    /// nearest roboport, where it waits for room in storage.
+    fn store_cargo(&mut self, robot: usize, at: Coordinate, reserved: &mut Reservations) {
+        self.robots[robot].job = None;
+        let Some((item, quality)) = self.robots[robot].cargo else {
+            return;
+        };
+        let storage = self
+            .network_at(at, LOGISTIC_RADIUS)
+            .and_then(|network| self.find_storage(network, at, reserved));
+        if let Some(to) = storage {
+            let job = Job {
+                from: at,
+                to,
+                item,
+                quality,
+            };
+            self.robots[robot].job = Some(job);
+            reserved.add(job, true);
+        }
+    }
+
+    /// Jobs of all robots
+    fn reservations(&self) -> Reservations {
+        let mut reserved = Reservations::default();
+        for robot in &self.robots {
+            if let Some(job) = robot.job {
+                reserved.add(job, robot.cargo.is_some());
+            }
+        }
+        reserved
+    }
+
+    /// Hands out jobs to resting robots: storing what they still carry first, then
+    /// requests, then ghosts, then emptying active providers
+    fn dispatch(&mut self) {
+        let mut reserved = self.reservations();
+        for index in 0..self.robots.len() {
+            let robot = &self.robots[index];
+            if let (RobotState::Parked(roboport), Some(_), None) =
+                (robot.state, robot.cargo, robot.job)
+            {
+                self.store_cargo(index, roboport, &mut reserved);
+                if self.robots[index].job.is_some() {
+                    self.robots[index].state = RobotState::Flying;
+                }
+            }
+        }
+        let mut chests: Vec<Coordinate> = self.chests.keys().copied().collect();
+        chests.sort_by_key(|c| (c.y, c.x));
+        for &to in &chests {
+            self.dispatch_requests(to, &mut reserved);
+        }
+        self.dispatch_ghosts(&mut reserved);
+        for &from in &chests {
+            self.dispatch_active_provider(from, &mut reserved);
+        }
+    }
+
+    /// Picks up or drops off the item of the job of `robot` at the end of its flight.
+    /// A robot whose chest has nothing left gives up the job. One whose chest or ghost
+    /// does not take what it carries brings it to storage instead.
+    fn arrive(&mut self, robot: usize, job: Job) {
+        let cargo = self.robots[robot].cargo;
+        match cargo {
+            None => {
+                let taken = self
+                    .chests
+                    .get_mut(&job.from)
+                    .is_some_and(|chest| chest.take(job.item, job.quality));
+                if taken {
+                    self.robots[robot].cargo = Some((job.item, job.quality));
+                } else {
+                    self.robots[robot].job = None;
+                }
+            }
+            Some((item, quality)) => {
+                let done = if self.robots[robot].kind == RobotKind::Construction {
+                    self.ghosts.remove(&job.to).is_some_and(|_| {
+                        self.built.push((job.to, item, quality));
+                        true
+                    })
+                } else {
+                    self.chests.get_mut(&job.to).is_some_and(|chest| {
+                        let accepted = chest.insert_item(item, quality);
+                        chest.delivered += u64::from(accepted);
+                        accepted
+                    })
+                };
+                if done {
+                    self.robots[robot].cargo = None;
+                    self.robots[robot].job = None;
+                } else {
+                    let mut reserved = self.reservations();
+                    self.store_cargo(robot, job.to, &mut reserved);
+                }
+            }
+        }
+    }
+
+    /// Moves a flying robot one tick towards its target
+    fn tick_robot(&mut self, index: usize) {
+        let robot = &self.robots[index];
+        if robot.state != RobotState::Flying {
+            return;
+        }
+        let low = robot.energy < LOW_BATTERY * ROBOT_BATTERY;
+        let target = match robot.job {
+            Some(job) if !low => Some((
+                if robot.cargo.is_some() {
+                    job.to
+                } else {
+                    job.from
+                },
+                Some(job),
+            )),
+            // Also for a robot without a job, which goes home
+            _ => self.nearest_roboport(robot.position).map(|c| (c, None)),
+        };
+        let Some((target, job)) = target else {
+            return;
+        };
+
+        let robot = &mut self.robots[index];
+        let speed = if robot.energy > 0.0 {
+            robot.kind.speed()
+        } else {
+            robot.kind.speed() * NO_ENERGY_SPEED
+        };
+        let left = distance(robot.position, position(target));
+        let step = speed.min(left);
+        if left > 0.0 {
+            let (x, y) = position(target);
+            robot.position.0 += (x - robot.position.0) * step / left;
+            robot.position.1 += (y - robot.position.1) * step / left;
+        }
+        robot.distance += step;
+        robot.energy = step
+            .mul_add(
+                -ENERGY_PER_TILE,
+                robot.energy - FLYING_DRAIN / TICKS_PER_SECOND,
+            )
+            .max(0.0);
+        if left - step > EPSILON {
+            return;
+        }
+        robot.position = position(target);
+        match job {
+            Some(job) => self.arrive(index, job),
+            None if robot.energy < LOW_BATTERY * ROBOT_BATTERY => {
+                robot.state = RobotState::Queued(target);
+                if let Some(roboport) = self.roboports.get_mut(&target) {
+                    roboport.queue.push_back(index);
+                }
+            }
+            None => robot.state = RobotState::Parked(target),
+        }
+    }
+
+    /// Fills the free charging slots of every roboport from its queue and charges the
+    /// robots in them. Charged robots carry on with their job or rest in the roboport.
+    fn charge(&mut self) {
+        for coordinate in self.sorted_roboports() {
+            let Some(roboport) = self.roboports.get_mut(&coordinate) else {
+                continue;
+            };
+            while roboport.charging.len() < CHARGING_SLOTS
+                && let Some(index) = roboport.queue.pop_front()
+            {
+                roboport.charging.push(index);
+                self.robots[index].state = RobotState::Charging(coordinate);
+            }
+            let robots = &mut self.robots;
+            roboport.charging.retain(|&index| {
+                let robot = &mut robots[index];
+                let energy = (CHARGING_POWER / TICKS_PER_SECOND)
+                    .min(ROBOT_BATTERY - robot.energy)
+                    .min(roboport.energy);
+                robot.energy += energy;
+                roboport.energy -= energy;
+                if robot.energy < ROBOT_BATTERY {
+                    return true;
+                }
+                robot.state = if robot.job.is_some() {
+                    RobotState::Flying
+                } else {
+                    RobotState::Parked(coordinate)
+                };
+                false
+            });
+        }
+    }
+
+    /// Fills roboport buffers with the power they got, given by `satisfaction` for their
+    /// coordinate, then hands out jobs, moves every robot and charges them
+    pub fn tick(&mut self, satisfaction: impl Fn(Coordinate) -> f64) {
+        for coordinate in self.sorted_roboports() {
+            if let Some(roboport) = self.roboports.get_mut(&coordinate) {
+                let received = roboport.power_demand() * satisfaction(coordinate);
+                roboport.energy = (roboport.energy
+                    + (received - ROBOPORT_DRAIN) / TICKS_PER_SECOND)
+                    .clamp(0.0, ROBOPORT_BUFFER);
+            }
+        }
+        self.dispatch();
+        for index in 0..self.robots.len() {
+            self.tick_robot(index);
+        }
+        self.charge();
+    }
+}
+
+impl World {
+    #[allow(dead_code)]
+    pub fn add_roboport(
+        &mut self,
+        coordinate: Coordinate,
+        logistic_robots: usize,
+        construction_robots: usize,
+    ) {
+        self.logistics
+            .add_roboport(coordinate, logistic_robots, construction_robots);
+    }
+
+    #[allow(dead_code)]
+    pub fn add_logistic_chest(&mut self, coordinate: Coordinate, chest: LogisticChest) {
+        self.logistics.add_chest(coordinate, chest);
+    }
+
+    #[allow(dead_code)]
+    pub fn add_ghost(&mut self, coordinate: Coordinate, item: Item) {
+        self.logistics.add_ghost(coordinate, item);
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,363 @@
+use super::*;
+use crate::{
+    BeltType, Direction, SingleBelt,
+    inserter::{Inserter, InserterKind},
+    power::{GeneratorKind, PoleKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn run(world: &mut World, ticks: usize) {
+    for _ in 0..ticks {
+        world.tick();
+    }
+}
+
+fn chest(world: &World, x: i32, y: i32) -> &LogisticChest {
+    &world.logistics.chests[&Coordinate::new(x, y)]
+}
+
+#[test]
+fn test_requester_is_filled_from_provider() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 2, 0);
+    world.add_logistic_chest(
+        Coordinate::new(5, 0),
+        LogisticChest::new(ChestKind::PassiveProvider).with_items(id(1), Quality::Normal, 10),
+    );
+    world.add_logistic_chest(
+        Coordinate::new(10, 0),
+        LogisticChest::new(ChestKind::Requester).with_request(id(1), 4),
+    );
+    run(&mut world, 2_000);
+    assert_eq!(chest(&world, 10, 0).count(id(1)), 4);
+    assert_eq!(chest(&world, 10, 0).delivered, 4);
+    assert_eq!(chest(&world, 5, 0).count(id(1)), 6);
+    assert!(
+        world
+            .logistics
+            .robots
+            .iter()
+            .all(|robot| robot.state == RobotState::Parked(Coordinate::new(0, 0)))
+    );
+}
+
+#[test]
+fn test_robots_fly_at_their_speed() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 1, 0);
+    world.add_logistic_chest(
+        Coordinate::new(3, 4),
+        LogisticChest::new(ChestKind::PassiveProvider).with_items(id(1), Quality::Normal, 1),
+    );
+    world.add_logistic_chest(
+        Coordinate::new(3, 0),
+        LogisticChest::new(ChestKind::Requester).with_request(id(1), 1),
+    );
+    // 5 tiles to the provider at 0.05 tiles per tick
+    run(&mut world, 99);
+    assert!(world.logistics.robots[0].cargo.is_none());
+    run(&mut world, 1);
+    assert_eq!(
+        world.logistics.robots[0].cargo,
+        Some((id(1), Quality::Normal))
+    );
+    // Then 4 tiles on to the requester
+    run(&mut world, 80);
+    assert_eq!(chest(&world, 3, 0).count(id(1)), 1);
+    let robot = &world.logistics.robots[0];
+    assert!((robot.distance - 9.0).abs() < 1e-9);
+    let used = ROBOT_BATTERY - robot.energy;
+    assert!((used - 9.0f64.mul_add(ENERGY_PER_TILE, 180.0 * FLYING_DRAIN / 60.0)).abs() < 1e-6);
+}
+
+#[test]
+fn test_cargo_nobody_takes_goes_to_storage() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 1, 0);
+    world.add_logistic_chest(
+        Coordinate::new(3, 4),
+        LogisticChest::new(ChestKind::PassiveProvider).with_items(id(1), Quality::Normal, 1),
+    );
+    world.add_logistic_chest(
+        Coordinate::new(3, 0),
+        LogisticChest::new(ChestKind::Requester).with_request(id(1), 1),
+    );
+    run(&mut world, 100);
+    assert!(world.logistics.robots[0].cargo.is_some());
+
+    // The requester fills up with something else while the robot is on its way
+    if let Some(requester) = world.logistics.chests.get_mut(&Coordinate::new(3, 0)) {
+        requester
+            .items
+            .insert((id(2), Quality::Normal), CHEST_CAPACITY);
+    }
+    run(&mut world, 200);
+    // Without storage it waits in the roboport with what it carries
+    let robot = &world.logistics.robots[0];
+    assert_eq!(robot.state, RobotState::Parked(Coordinate::new(0, 0)));
+    assert_eq!(robot.cargo, Some((id(1), Quality::Normal)));
+    assert_eq!(robot.job, None);
+
+    world.add_logistic_chest(
+        Coordinate::new(0, 3),
+        LogisticChest::new(ChestKind::Storage),
+    );
+    run(&mut world, 100);
+    assert_eq!(chest(&world, 0, 3).count(id(1)), 1);
+    assert_eq!(chest(&world, 3, 0).count(id(1)), 0);
+    let robot = &world.logistics.robots[0];
+    assert_eq!((robot.cargo, robot.job), (None, None));
+}
+
+#[test]
+fn test_active_providers_come_first() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 1, 0);
+    world.add_logistic_chest(
+        Coordinate::new(2, 0),
+        LogisticChest::new(ChestKind::PassiveProvider).with_items(id(1), Quality::Normal, 5),
+    );
+    world.add_logistic_chest(
+        Coordinate::new(0, 8),
+        LogisticChest::new(ChestKind::ActiveProvider).with_items(id(1), Quality::Normal, 1),
+    );
+    world.add_logistic_chest(
+        Coordinate::new(4, 0),
+        LogisticChest::new(ChestKind::Requester).with_request(id(1), 1),
+    );
+    run(&mut world, 1_000);
+    assert_eq!(chest(&world, 4, 0).count(id(1)), 1);
+    assert_eq!(chest(&world, 0, 8).item_count(), 0);
+    assert_eq!(chest(&world, 2, 0).item_count(), 5);
+}
+
+#[test]
+fn test_active_providers_empty_into_storage() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 3, 0);
+    world.add_logistic_chest(
+        Coordinate::new(4, 0),
+        LogisticChest::new(ChestKind::ActiveProvider)
+            .with_items(id(1), Quality::Normal, 2)
+            .with_items(id(2), Quality::Rare, 1),
+    );
+    world.add_logistic_chest(
+        Coordinate::new(8, 0),
+        LogisticChest::new(ChestKind::Storage),
+    );
+    // Out of reach of the roboport
+    world.add_logistic_chest(
+        Coordinate::new(-30, 0),
+        LogisticChest::new(ChestKind::Storage),
+    );
+    run(&mut world, 1_000);
+    assert_eq!(chest(&world, 4, 0).item_count(), 0);
+    let storage = chest(&world, 8, 0);
+    assert_eq!(storage.count(id(1)), 2);
+    assert_eq!(storage.items[&(id(2), Quality::Rare)], 1);
+    assert_eq!(chest(&world, -30, 0).item_count(), 0);
+}
+
+#[test]
+fn test_separate_networks_do_not_share() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 1, 0);
+    world.add_roboport(Coordinate::new(100, 0), 1, 0);
+    world.add_logistic_chest(
+        Coordinate::new(5, 0),
+        LogisticChest::new(ChestKind::PassiveProvider).with_items(id(1), Quality::Normal, 1),
+    );
+    world.add_logistic_chest(
+        Coordinate::new(95, 0),
+        LogisticChest::new(ChestKind::Requester).with_request(id(1), 1),
+    );
+    assert_eq!(
+        world
+            .logistics
+            .network_at(Coordinate::new(5, 0), LOGISTIC_RADIUS),
+        Some(0)
+    );
+    assert_eq!(
+        world
+            .logistics
+            .network_at(Coordinate::new(95, 0), LOGISTIC_RADIUS),
+        Some(1)
+    );
+    run(&mut world, 100);
+    assert_eq!(chest(&world, 95, 0).item_count(), 0);
+
+    // A roboport in between joins them
+    world.add_roboport(Coordinate::new(50, 0), 0, 0);
+    assert_eq!(
+        world
+            .logistics
+            .network_at(Coordinate::new(95, 0), LOGISTIC_RADIUS),
+        Some(0)
+    );
+    run(&mut world, 3_000);
+    assert_eq!(chest(&world, 95, 0).item_count(), 1);
+}
+
+#[test]
+fn test_construction_robots_build_ghosts() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 0, 1);
+    world.add_logistic_chest(
+        Coordinate::new(2, 0),
+        LogisticChest::new(ChestKind::Storage).with_items(id(3), Quality::Normal, 5),
+    );
+    // Outside of the logistic area but inside the construction area
+    world.add_ghost(Coordinate::new(40, 0), id(3));
+    world.add_ghost(Coordinate::new(0, 3), id(4));
+    run(&mut world, 2_000);
+    assert_eq!(
+        world.logistics.built,
+        vec![(Coordinate::new(40, 0), id(3), Quality::Normal)]
+    );
+    assert_eq!(chest(&world, 2, 0).count(id(3)), 4);
+    assert_eq!(world.logistics.ghosts.len(), 1);
+}
+
+#[test]
+fn test_robots_queue_for_charging_slots() {
+    let mut logistics = LogisticSystem::default();
+    let roboport = Coordinate::new(0, 0);
+    logistics.add_roboport(roboport, 6, 0);
+    logistics
+        .roboports
+        .get_mut(&roboport)
+        .expect("roboport")
+        .energy = ROBOPORT_BUFFER;
+    for robot in &mut logistics.robots {
+        robot.state = RobotState::Flying;
+        robot.energy = ROBOT_BATTERY * 0.1;
+    }
+    logistics.tick(|_| 0.0);
+    assert_eq!(logistics.roboports[&roboport].charging, vec![0, 1, 2, 3]);
+    assert_eq!(logistics.roboports[&roboport].queue, [4, 5]);
+    assert_eq!(logistics.robots[4].state, RobotState::Queued(roboport));
+
+    // 90% of a battery takes 162 ticks at 500 kW
+    for _ in 0..165 {
+        logistics.tick(|_| 0.0);
+    }
+    assert_eq!(logistics.robots[0].state, RobotState::Parked(roboport));
+    assert_eq!(logistics.roboports[&roboport].charging, vec![4, 5]);
+    for _ in 0..165 {
+        logistics.tick(|_| 0.0);
+    }
+    assert!(
+        logistics
+            .robots
+            .iter()
+            .all(|robot| robot.state == RobotState::Parked(roboport)
+                && robot.energy == ROBOT_BATTERY)
+    );
+}
+
+#[test]
+fn test_low_robots_charge_before_their_job() {
+    let mut logistics = LogisticSystem::default();
+    let roboport = Coordinate::new(0, 0);
+    logistics.add_roboport(roboport, 1, 0);
+    logistics.add_chest(
+        Coordinate::new(5, 0),
+        LogisticChest::new(ChestKind::PassiveProvider).with_items(id(1), Quality::Normal, 1),
+    );
+    logistics.add_chest(
+        Coordinate::new(-5, 0),
+        LogisticChest::new(ChestKind::Requester).with_request(id(1), 1),
+    );
+    logistics.robots[0].energy = ROBOT_BATTERY * 0.1;
+    // Without energy in the roboport the robot waits in its slot
+    for _ in 0..100 {
+        logistics.tick(|_| 0.0);
+    }
+    assert_eq!(logistics.robots[0].state, RobotState::Charging(roboport));
+    assert!(logistics.robots[0].job.is_some());
+    assert_eq!(logistics.robots[0].position, (0.0, 0.0));
+
+    for _ in 0..1_000 {
+        logistics.tick(|_| 1.0);
+    }
+    assert_eq!(logistics.chests[&Coordinate::new(-5, 0)].delivered, 1);
+}
+
+#[test]
+fn test_robots_without_energy_crawl() {
+    let mut logistics = LogisticSystem::default();
+    logistics.add_roboport(Coordinate::new(0, 0), 1, 0);
+    logistics.robots[0].state = RobotState::Flying;
+    logistics.robots[0].position = (10.0, 0.0);
+    logistics.robots[0].energy = 0.0;
+    logistics.tick(|_| 0.0);
+    assert!((logistics.robots[0].position.0 - 9.99).abs() < 1e-9);
+}
+
+#[test]
+fn test_roboports_draw_power() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 0, 0);
+    world.add_roboport(Coordinate::new(0, 10), 0, 0);
+    world.power.add_pole(Coordinate::new(1, 1), PoleKind::Small);
+    world
+        .power
+        .generators
+        .insert(Coordinate::new(2, 2), GeneratorKind::SolarPanel);
+    run(&mut world, 60);
+    let stats = world.power.stats[0];
+    assert!((stats.demand - ROBOPORT_MAX_INPUT - ROBOPORT_DRAIN).abs() < 1e-6);
+    // 60 kW from the panel leaves 10 kW for the buffer after the drain
+    let energy = world.logistics.roboports[&Coordinate::new(0, 0)].energy;
+    assert!((energy - 10_000.0).abs() < 1e-6);
+    assert!(
+        world.logistics.roboports[&Coordinate::new(0, 10)]
+            .energy
+            .abs()
+            < 1e-9
+    );
+}
+
+#[test]
+fn test_inserters_use_logistic_chests() {
+    let mut world = World::new();
+    world.add_roboport(Coordinate::new(0, 0), 1, 0);
+    world.add_logistic_chest(
+        Coordinate::new(3, 0),
+        LogisticChest::new(ChestKind::PassiveProvider).with_items(id(1), Quality::Normal, 3),
+    );
+    world.add_logistic_chest(
+        Coordinate::new(0, 3),
+        LogisticChest::new(ChestKind::Requester).with_request(id(1), 1),
+    );
+    // Empties the requester onto a belt, so robots keep refilling it
+    world.add_inserter(
+        Coordinate::new(0, 4),
+        Inserter::new(InserterKind::Fast, Direction::South),
+    );
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(0, 5),
+        BeltType::Regular,
+        Some(Coordinate::new(1, 5)),
+        Some(Coordinate::new(1, 5)),
+    ));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(1, 5),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    world.power.add_pole(Coordinate::new(1, 3), PoleKind::Small);
+    world
+        .power
+        .generators
+        .insert(Coordinate::new(2, 4), GeneratorKind::SolarPanel);
+    run(&mut world, 2_000);
+    assert_eq!(world.inserters[&Coordinate::new(0, 4)].moved, 3);
+    assert_eq!(chest(&world, 0, 3).delivered, 3);
+    assert_eq!(chest(&world, 3, 0).item_count(), 0);
+}
@@ -1,8 +1,40 @@
-use std::{collections::HashMap, num::NonZeroUsize};
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    ops::Range,
+};
+
+mod ascii;
+mod belt_tiers;
+mod burner;
+mod checksum;
+mod circuit;
+mod crafting;
+mod events;
+mod fluid;
+mod inserter;
+mod logistic;
+mod mining;
+mod modules;
+mod png;
+mod power;
+mod quality;
+mod rail;
+mod ratio;
+mod replay;
+mod schedule;
+mod slots;
+mod snapshot;
+mod steady_state;
+mod tui;
+mod validation;
 
 // Temp
 type Item = NonZeroUsize;
 
+/// The game runs at a fixed 60 updates per second
+const TICKS_PER_SECOND: f64 = 60.0;
+
 /// Represents a 2D coordinate in the world grid
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Coordinate {
@@ -11,7 +43,7 @@ struct Coordinate {
 }
 
 /// Represents a direction for belt connections
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(dead_code)]
 enum Direction {
     North,
@@ -21,6 +53,8 @@ enum Direction {
 }
 
 impl Direction {
+    const ALL: [Self; 4] = [Self::North, Self::East, Self::South, Self::West];
+
     const fn offset(self) -> (i32, i32) {
         match self {
             Self::North => (0, -1),
@@ -29,6 +63,27 @@ impl Direction {
             Self::West => (-1, 0),
         }
     }
+
+    const fn opposite(self) -> Self {
+        match self {
+            Self::North => Self::South,
+            Self::South => Self::North,
+            Self::East => Self::West,
+            Self::West => Self::East,
+        }
+    }
+
+    /// Returns the direction pointing from `from` to the adjacent tile `to`,
+    /// or `None` if the two coordinates are not orthogonal neighbours
+    const fn between(from: Coordinate, to: Coordinate) -> Option<Self> {
+        match (to.x - from.x, to.y - from.y) {
+            (0, -1) => Some(Self::North),
+            (0, 1) => Some(Self::South),
+            (1, 0) => Some(Self::East),
+            (-1, 0) => Some(Self::West),
+            _ => None,
+        }
+    }
 }
 
 impl Coordinate {
@@ -46,7 +101,7 @@ impl Coordinate {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum BeltType {
     /// Also known as a yellow belt
     Regular,
@@ -59,27 +114,24 @@ enum BeltType {
     /// Also known as a green belt
     #[allow(dead_code)]
     Turbo,
+    /// A tier added by a mod, see [`belt_tiers`]
+    Modded { positions_per_tick: u32 },
 }
 
 impl BeltType {
     #[allow(dead_code)]
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
     const fn tiles_traveled_per_second(self) -> f32 {
-        match self {
-            Self::Regular => 1.875,
-            Self::Fast => 3.75,
-            Self::Express => 5.625,
-            Self::Turbo => 7.5,
-        }
+        self.positions_per_tick() as f32 * TICKS_PER_SECOND as f32 / LANE_LENGTH as f32
     }
 
-    #[allow(dead_code)]
-    const fn item_throughput_per_second_one_lane(self) -> f32 {
-        match self {
-            Self::Regular => 7.5,
-            Self::Fast => 15.0,
-            Self::Express => 22.5,
-            Self::Turbo => 30.0,
-        }
+    /// Items per second on one lane with `stack_height` items in every slot, 1 for
+    /// unstacked belts
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    const fn item_throughput_per_second_one_lane(self, stack_height: u8) -> f32 {
+        let slots =
+            self.positions_per_tick() as f32 * TICKS_PER_SECOND as f32 / ITEM_SPACING as f32;
+        slots * stack_height as f32
     }
 
     const fn positions_per_tick(self) -> u32 {
@@ -88,37 +140,97 @@ impl BeltType {
             Self::Fast => 16,
             Self::Express => 24,
             Self::Turbo => 32,
+            Self::Modded { positions_per_tick } => positions_per_tick,
         }
     }
 }
 
+/// Number of discrete positions along the lane of a straight belt
+const LANE_LENGTH: u32 = 256;
+/// Smallest distance between two items on a lane, so a compressed lane holds 4 items
+const ITEM_SPACING: u32 = 64;
+/// Most items a single slot of a lane holds when stacked
+const MAX_STACK_HEIGHT: u8 = 4;
+
+/// Items a compressed lane of `length` positions holds: one at the end and one every
+/// [`ITEM_SPACING`] positions behind it
+const fn compressed_items(length: u32) -> u32 {
+    length.div_ceil(ITEM_SPACING)
+}
+
 struct SingleBeltLane {
-    // A belt lane can have a maximum of 5 items on it at any time.
-    // The tuple stores the item and its relative position on the belt (0 to 255).
-    // The way belts are simulated in the game is that items can be on one of 256 discrete positions on the belt.
+    // A belt lane has a fixed number of slots, 5 on a straight belt.
+    // The tuple stores the item and its relative position on the lane (0 to length - 1).
+    // The way belts are simulated in the game is that items can be on one of 256 discrete positions on a straight belt.
     // To see more, check
     // - Factorio wiki/Belt transport system
     // - Factorio wiki/Transport Belts/Physics
-    // This uses a fixed-size array for performance reasons.
-    items: [Option<(Item, u32)>; 5],
+    // Straight belts keep their slots inline for performance reasons, see `slots`.
+    items: slots::Slots<Option<(Item, u32)>>,
+    /// Quality of the item in the slot of the same index in `items`. Only the lane's
+    /// own methods change it, so it is reset together with its slot.
+    qualities: slots::Slots<quality::Quality>,
+    /// Items stacked on top of the item in the slot of the same index in `items`,
+    /// so a slot holds one more than this. Kept in step with `items` like `qualities`.
+    stacked: slots::Slots<u8>,
+    /// Number of discrete positions along the lane, [`LANE_LENGTH`] on a straight belt.
+    /// Never 0, so `length - 1` is always the last position.
+    length: u32,
     belt_type: BeltType,
     /// Coordinate of the next lane in the chain
     next_lane_coord: Option<Coordinate>,
+    /// Disabled lanes, e.g. by a circuit condition, keep all items where they are
+    enabled: bool,
+    /// Number of items accepted onto the lane so far, from belts, inserters and drills
+    accepted: u64,
+    /// `accepted` split by item, in the order the items first arrived
+    accepted_items: Vec<(Item, u64)>,
 }
 
 impl SingleBeltLane {
-    const fn new(belt_type: BeltType, next_lane_coord: Option<Coordinate>) -> Self {
+    fn new(belt_type: BeltType, next_lane_coord: Option<Coordinate>) -> Self {
         Self {
-            items: [None, None, None, None, None],
+            items: slots::Slots::new(slots::INLINE_SLOTS),
+            qualities: slots::Slots::new(slots::INLINE_SLOTS),
+            stacked: slots::Slots::new(slots::INLINE_SLOTS),
+            length: LANE_LENGTH,
             belt_type,
             next_lane_coord,
+            enabled: true,
+            accepted: 0,
+            accepted_items: Vec::new(),
         }
     }
 
+    /// Changes the lane to `length` positions, with one slot more than the items a
+    /// compressed lane of that length holds. Items on the lane are removed.
+    ///
+    /// # Panics
+    ///
+    /// If `length` is 0.
+    #[allow(dead_code)]
+    fn with_length(self, length: u32) -> Self {
+        assert!(length > 0, "a lane needs at least one position");
+        let capacity = usize::try_from(compressed_items(length)).unwrap_or(usize::MAX);
+        Self { length, ..self }.with_capacity(capacity + 1)
+    }
+
+    /// Changes the number of slots of the lane. Items on the lane are removed.
+    #[allow(dead_code)]
+    fn with_capacity(mut self, capacity: usize) -> Self {
+        self.items = slots::Slots::new(capacity);
+        self.qualities = slots::Slots::new(capacity);
+        self.stacked = slots::Slots::new(capacity);
+        self
+    }
+
     /// Returns items that should be transferred to the next lane
-    /// Returns a list of (item, position) tuples
-    fn tick_and_get_transfers(&mut self) -> Vec<(Item, u32)> {
+    /// Returns a list of (item, position, quality, stack height) tuples
+    fn tick_and_get_transfers(&mut self) -> Vec<(Item, u32, quality::Quality, u8)> {
         let mut transfers = Vec::new();
+        if !self.enabled {
+            return transfers;
+        }
         let positions_per_tick = self.belt_type.positions_per_tick();
 
         // Collect all items with their array indices
@@ -138,29 +250,33 @@ impl SingleBeltLane {
 
         // Process items from front to back
         for (idx, _item, current_pos) in &items_with_idx {
-            let desired_position = current_pos + positions_per_tick;
+            let desired_position = current_pos.saturating_add(positions_per_tick);
             let mut can_move_to = desired_position;
 
-            // Check if moving would violate spacing with any item ahead
-            for &(_check_idx, _, ahead_pos) in &new_positions {
-                // ahead_pos is where an item ahead will be after this tick
-                if ahead_pos > *current_pos {
-                    // Check if moving to desired_position would be too close
-                    if desired_position + 64 > ahead_pos {
-                        // Would violate spacing - move as close as possible while maintaining 64-gap
-                        // This allows items to compact when the front item stops
-                        // But never move backward - stay at current position if that would happen
-                        let max_forward = ahead_pos.saturating_sub(64);
-                        can_move_to = max_forward.max(*current_pos);
-                        break;
-                    }
-                }
+            // Only the nearest item ahead limits this one. On belts moving more than
+            // ITEM_SPACING positions a tick, items further ahead can be in reach too,
+            // but they never leave less room than the nearest one.
+            // ahead_pos is where that item will be after this tick
+            let nearest_ahead = new_positions
+                .iter()
+                .map(|&(_, _, ahead_pos)| ahead_pos)
+                .filter(|ahead_pos| ahead_pos > current_pos)
+                .min();
+            if let Some(ahead_pos) = nearest_ahead
+                && desired_position.saturating_add(ITEM_SPACING) > ahead_pos
+            {
+                // Would violate spacing - move as close as possible while keeping the gap
+                // This allows items to compact when the front item stops
+                // But never move backward - stay at current position if that would happen
+                let max_forward = ahead_pos.saturating_sub(ITEM_SPACING);
+                can_move_to = max_forward.max(*current_pos);
             }
 
             // Store the calculated position for spacing checks
-            // For items beyond 255 without next lane, store 255 for spacing
-            let spacing_pos = if can_move_to > 255 && self.next_lane_coord.is_none() {
-                255
+            // For items beyond the end without next lane, store the last position for spacing
+            let last = self.length - 1;
+            let spacing_pos = if can_move_to > last && self.next_lane_coord.is_none() {
+                last
             } else {
                 can_move_to
             };
@@ -171,15 +287,20 @@ impl SingleBeltLane {
         // Apply the new positions
         for (idx, new_pos, _) in new_positions {
             if let Some((item, position)) = &mut self.items[idx] {
-                if new_pos > 255 {
+                if new_pos >= self.length {
                     // Transfer to next lane
-                    let target_position = new_pos - 256;
+                    let target_position = new_pos - self.length;
                     if self.next_lane_coord.is_some() {
-                        transfers.push((*item, target_position));
-                        self.items[idx] = None;
+                        transfers.push((
+                            *item,
+                            target_position,
+                            self.qualities[idx],
+                            self.stack_height(idx),
+                        ));
+                        self.empty_slot(idx);
                     } else {
-                        // No next lane, clamp to 255
-                        *position = 255;
+                        // No next lane, clamp to the last position
+                        *position = self.length - 1;
                     }
                 } else {
                     *position = new_pos;
@@ -190,30 +311,194 @@ impl SingleBeltLane {
         transfers
     }
 
-    /// Attempts to accept an item from a previous lane
+    /// Attempts to accept a normal quality item from a previous lane
     /// Returns true if successful, false if there's no space
     fn accept_item(&mut self, item: Item, target_position: u32) -> bool {
-        // Check if target position respects the 64 position gap rule
-        let mut adjusted_position = target_position.min(255);
+        self.accept_item_of_quality(item, quality::Quality::Normal, target_position)
+    }
+
+    /// Attempts to accept an item of any quality
+    fn accept_item_of_quality(
+        &mut self,
+        item: Item,
+        quality: quality::Quality,
+        target_position: u32,
+    ) -> bool {
+        self.accept_stack(item, quality, 1, target_position)
+    }
+
+    /// Attempts to accept a stack of `height` items into a single slot. Heights of 0
+    /// or above [`MAX_STACK_HEIGHT`] are rejected.
+    fn accept_stack(
+        &mut self,
+        item: Item,
+        quality: quality::Quality,
+        height: u8,
+        target_position: u32,
+    ) -> bool {
+        if !(1..=MAX_STACK_HEIGHT).contains(&height) {
+            return false;
+        }
+        let Some(position) = self.accept_position(target_position) else {
+            return false;
+        };
+        let Some(empty) = self.items.iter().position(Option::is_none) else {
+            return false;
+        };
+        self.fill_slot(empty, (item, position), quality, height);
+        self.accepted += u64::from(height);
+        match self
+            .accepted_items
+            .iter_mut()
+            .find(|(kind, _)| *kind == item)
+        {
+            Some((_, count)) => *count += u64::from(height),
+            None => self.accepted_items.push((item, u64::from(height))),
+        }
+        true
+    }
+
+    /// Number of items of `item` accepted onto the lane so far
+    fn accepted_of(&self, item: Item) -> u64 {
+        self.accepted_items
+            .iter()
+            .find(|(kind, _)| *kind == item)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    /// Puts a stack of `height` items into `slot`, replacing whatever was there
+    fn fill_slot(&mut self, slot: usize, item: (Item, u32), quality: quality::Quality, height: u8) {
+        self.items[slot] = Some(item);
+        self.qualities[slot] = quality;
+        self.stacked[slot] = height - 1;
+    }
 
-        // Check distance to existing items
+    /// Empties `slot` along with its quality and stack height
+    fn empty_slot(&mut self, slot: usize) {
+        self.items[slot] = None;
+        self.qualities[slot] = quality::Quality::Normal;
+        self.stacked[slot] = 0;
+    }
+
+    /// Removes every item from the lane
+    fn clear(&mut self) {
+        for slot in 0..self.items.len() {
+            self.empty_slot(slot);
+        }
+    }
+
+    /// Quality of the item in `slot`
+    fn quality(&self, slot: usize) -> quality::Quality {
+        self.qualities[slot]
+    }
+
+    /// Number of items in `slot`, 0 if it is empty
+    fn stack_height(&self, slot: usize) -> u8 {
+        if self.items[slot].is_some() {
+            self.stacked[slot] + 1
+        } else {
+            0
+        }
+    }
+
+    /// Removes up to `count` items from the stack in `slot`, emptying the slot when the
+    /// last one goes. Returns how many were removed.
+    fn take_from_stack(&mut self, slot: usize, count: u8) -> u8 {
+        let height = self.stack_height(slot);
+        let taken = count.min(height);
+        if taken == height {
+            self.empty_slot(slot);
+        } else {
+            self.stacked[slot] -= taken;
+        }
+        taken
+    }
+
+    /// Items of every slot with their position and stack height
+    fn stacks(&self) -> slots::Slots<Option<(Item, u32, u8)>> {
+        let mut stacks = slots::Slots::new(self.items.len());
+        for (slot, stack) in stacks.iter_mut().enumerate() {
+            *stack = self.items[slot].map(|(item, pos)| (item, pos, self.stack_height(slot)));
+        }
+        stacks
+    }
+
+    /// Where an item offered at `target_position` ends up: pushed forward until it is
+    /// [`ITEM_SPACING`] ahead of the items behind it. Items ahead of it are not checked,
+    /// use [`Self::fits_at`] for that. `None` if that is past the end of the lane or
+    /// every slot is taken.
+    fn accept_position(&self, target_position: u32) -> Option<u32> {
+        let mut adjusted_position = target_position.min(self.length - 1);
         for (_, pos) in self.items.iter().flatten() {
-            if *pos < adjusted_position {
-                let distance = adjusted_position - pos;
-                if distance < 64 {
-                    adjusted_position = pos + 64;
-                }
+            if *pos < adjusted_position && adjusted_position - pos < ITEM_SPACING {
+                adjusted_position = pos + ITEM_SPACING;
             }
         }
+        (adjusted_position < self.length && self.items.iter().any(Option::is_none))
+            .then_some(adjusted_position)
+    }
+
+    /// Number of items on the lane, counting every item of a stack
+    fn item_count(&self) -> usize {
+        (0..self.items.len())
+            .map(|slot| usize::from(self.stack_height(slot)))
+            .sum()
+    }
 
-        if adjusted_position <= 255 {
-            // Find an empty slot
-            if let Some(empty_slot) = self.items.iter_mut().find(|slot| slot.is_none()) {
-                *empty_slot = Some((item, adjusted_position));
-                return true;
+    /// Positions of all items, from the start of the lane to its end
+    #[allow(dead_code)]
+    fn positions(&self) -> Vec<u32> {
+        let mut positions: Vec<u32> = self.items.iter().flatten().map(|(_, pos)| *pos).collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    /// Stretches of the lane no item takes up, from the start of the lane to its end.
+    /// Every item takes up the [`ITEM_SPACING`] positions from where it is onwards.
+    #[allow(dead_code)]
+    fn gaps(&self) -> Vec<Range<u32>> {
+        let mut gaps = Vec::new();
+        let mut start = 0;
+        for position in self.positions() {
+            if position > start {
+                gaps.push(start..position);
             }
+            start = start.max(position + ITEM_SPACING);
+        }
+        if start < self.length {
+            gaps.push(start..self.length);
         }
-        false
+        gaps
+    }
+
+    /// Free positions from `position` up to the next item, or up to the end of the lane.
+    /// Zero where an item takes up the lane.
+    #[allow(dead_code)]
+    fn free_space_at(&self, position: u32) -> u32 {
+        self.gaps()
+            .into_iter()
+            .find(|gap| gap.contains(&position))
+            .map_or(0, |gap| gap.end - position)
+    }
+
+    /// Whether an item placed right at `position` keeps [`ITEM_SPACING`] to every item
+    /// on the lane and has a free slot
+    fn fits_at(&self, position: u32) -> bool {
+        position < self.length
+            && self.items.iter().any(Option::is_none)
+            && self
+                .items
+                .iter()
+                .flatten()
+                .all(|(_, pos)| pos.abs_diff(position) >= ITEM_SPACING)
+    }
+
+    /// Occupied slots on the lane relative to a fully compressed lane, 1 when
+    /// compressed. A stack counts once, however high it is.
+    fn compression(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let count = self.items.iter().flatten().count() as f64;
+        count / f64::from(compressed_items(self.length))
     }
 }
 
@@ -224,7 +509,7 @@ struct SingleBelt {
 }
 
 impl SingleBelt {
-    const fn new(
+    fn new(
         coordinate: Coordinate,
         belt_type: BeltType,
         left_next: Option<Coordinate>,
@@ -236,17 +521,78 @@ impl SingleBelt {
             coordinate,
         }
     }
+
+    /// Changes the lengths of both lanes, e.g. for the inner and outer lane of a curve.
+    /// Items on the lanes are removed.
+    ///
+    /// # Panics
+    ///
+    /// If either length is 0.
+    #[allow(dead_code)]
+    fn with_lane_lengths(mut self, left: u32, right: u32) -> Self {
+        self.left_lane = self.left_lane.with_length(left);
+        self.right_lane = self.right_lane.with_length(right);
+        self
+    }
+
+    /// Enables or disables movement on both lanes
+    const fn set_enabled(&mut self, enabled: bool) {
+        self.left_lane.enabled = enabled;
+        self.right_lane.enabled = enabled;
+    }
+
+    /// Direction the belt is facing, derived from where its lanes feed into.
+    /// Belts at the end of a line have no next lane and therefore no known direction.
+    fn direction(&self) -> Option<Direction> {
+        self.left_lane
+            .next_lane_coord
+            .or(self.right_lane.next_lane_coord)
+            .and_then(|next| Direction::between(self.coordinate, next))
+    }
 }
 
 /// The world contains all belts organized by their coordinates
 struct World {
     belts: HashMap<Coordinate, SingleBelt>,
+    fluids: fluid::FluidSystem,
+    machines: HashMap<Coordinate, crafting::CraftingMachine>,
+    inserters: HashMap<Coordinate, inserter::Inserter>,
+    resources: HashMap<Coordinate, mining::Resource>,
+    drills: HashMap<Coordinate, mining::MiningDrill>,
+    boilers: HashMap<Coordinate, burner::Boiler>,
+    beacons: HashMap<Coordinate, modules::Beacon>,
+    /// Fuel value in joules of every item that can be burnt
+    fuels: HashMap<Item, f64>,
+    power: power::ElectricSystem,
+    circuits: circuit::CircuitSystem,
+    rails: rail::RailSystem,
+    logistics: logistic::LogisticSystem,
+    /// Belt tiers added by mods, which belts can be changed to by name
+    belt_tiers: belt_tiers::BeltTiers,
+    /// Number of ticks simulated so far
+    ticks: u64,
+    events: events::EventLog,
 }
 
 impl World {
     fn new() -> Self {
         Self {
             belts: HashMap::new(),
+            fluids: fluid::FluidSystem::default(),
+            machines: HashMap::new(),
+            inserters: HashMap::new(),
+            resources: HashMap::new(),
+            drills: HashMap::new(),
+            boilers: HashMap::new(),
+            beacons: HashMap::new(),
+            fuels: HashMap::new(),
+            power: power::ElectricSystem::new(),
+            circuits: circuit::CircuitSystem::default(),
+            rails: rail::RailSystem::default(),
+            logistics: logistic::LogisticSystem::default(),
+            belt_tiers: belt_tiers::BeltTiers::default(),
+            ticks: 0,
+            events: events::EventLog::default(),
         }
     }
 
@@ -255,6 +601,34 @@ impl World {
     }
 
     #[allow(dead_code)]
+    fn add_fluid_entity(&mut self, coordinate: Coordinate, entity: fluid::FluidEntity) {
+        self.fluids.add(coordinate, entity);
+    }
+
+    #[allow(dead_code)]
+    fn add_machine(&mut self, coordinate: Coordinate, mut machine: crafting::CraftingMachine) {
+        // Each machine rolls quality on its own sequence
+        machine.rng = quality::QualityRng::new(
+            (u64::from(coordinate.x.cast_unsigned()) << 32)
+                | u64::from(coordinate.y.cast_unsigned()),
+        );
+        self.machines.insert(coordinate, machine);
+        self.update_effects();
+    }
+
+    /// Smallest and largest corner of the bounding box of all belts,
+    /// or `None` for an empty world
+    fn bounds(&self) -> Option<(Coordinate, Coordinate)> {
+        let mut coords = self.belts.keys();
+        let first = *coords.next()?;
+        Some(coords.fold((first, first), |(min, max), c| {
+            (
+                Coordinate::new(min.x.min(c.x), min.y.min(c.y)),
+                Coordinate::new(max.x.max(c.x), max.y.max(c.y)),
+            )
+        }))
+    }
+
     fn get_lane_mut(&mut self, coord: Coordinate, is_left: bool) -> Option<&mut SingleBeltLane> {
         self.belts.get_mut(&coord).map(|belt| {
             if is_left {
@@ -265,40 +639,340 @@ impl World {
         })
     }
 
-    /// Tick all belts in the world
+    /// Lane of the belt at `coord`
+    fn lane(&self, coord: Coordinate, is_left: bool) -> Option<&SingleBeltLane> {
+        self.belts.get(&coord).map(|belt| {
+            if is_left {
+                &belt.left_lane
+            } else {
+                &belt.right_lane
+            }
+        })
+    }
+
+    /// Number of items on both lanes of the belt at `coord`, 0 without a belt
+    #[allow(dead_code)]
+    fn belt_item_count(&self, coord: Coordinate) -> usize {
+        self.belts.get(&coord).map_or(0, |belt| {
+            belt.left_lane.item_count() + belt.right_lane.item_count()
+        })
+    }
+
+    /// Whether an item fits right at `position` on a lane of the belt at `coord`
+    #[allow(dead_code)]
+    fn item_fits(&self, coord: Coordinate, is_left: bool, position: u32) -> bool {
+        self.lane(coord, is_left)
+            .is_some_and(|lane| lane.fits_at(position))
+    }
+
+    /// Average compression of both lanes of the belt at `coord`
+    #[allow(dead_code)]
+    fn belt_compression(&self, coord: Coordinate) -> Option<f64> {
+        let belt = self.belts.get(&coord)?;
+        Some(f64::midpoint(
+            belt.left_lane.compression(),
+            belt.right_lane.compression(),
+        ))
+    }
+
+    /// Tick all belts, fluids, machines, drills, trains, robots, inserters and circuits
+    /// in the world
     fn tick(&mut self) {
-        // Collect all transfers first
-        let mut all_transfers: Vec<(Coordinate, Item, u32)> = Vec::new();
-
-        // Process all lanes and collect transfers
-        for belt in self.belts.values_mut() {
-            let left_transfers = belt.left_lane.tick_and_get_transfers();
-            for (item, pos) in left_transfers {
-                if let Some(next_coord) = belt.left_lane.next_lane_coord {
-                    all_transfers.push((next_coord, item, pos));
+        self.ticks += 1;
+        self.tick_belts();
+        self.exchange_machine_fluids();
+        self.tick_boilers();
+        self.fluids.tick();
+        let satisfaction = self.tick_power();
+        for (coordinate, machine) in &mut self.machines {
+            let demand = machine.power_demand();
+            let factor = match &mut machine.burner {
+                Some(burner) => burner.burn(demand),
+                None if machine.energy_usage > 0.0 => {
+                    satisfaction.get(coordinate).copied().unwrap_or(0.0)
+                }
+                // Machines without an energy usage run without power
+                None => 1.0,
+            };
+            machine.tick_powered(factor);
+        }
+        let powered = |coordinate| satisfaction.get(&coordinate).copied().unwrap_or(0.0);
+        self.tick_drills(powered);
+        self.tick_trains();
+        self.logistics.tick(powered);
+        self.tick_inserters(powered);
+        self.tick_circuits();
+    }
+
+    /// Collects the power demand of every machine, drill, inserter and roboport and
+    /// balances the electric networks. Returns the satisfaction of every consumer.
+    fn tick_power(&mut self) -> HashMap<Coordinate, f64> {
+        let machines = self
+            .machines
+            .iter()
+            .filter(|(_, machine)| machine.energy_usage > 0.0 && machine.burner.is_none())
+            .map(|(coordinate, machine)| power::Demand {
+                coordinate: *coordinate,
+                power: machine.power_demand(),
+            });
+        let inserters = self
+            .inserters
+            .iter()
+            .filter(|(_, inserter)| inserter.burner.is_none())
+            .map(|(coordinate, inserter)| power::Demand {
+                coordinate: *coordinate,
+                power: inserter.power_demand(),
+            });
+        let drills = self
+            .drills
+            .iter()
+            .filter(|(_, drill)| drill.burner.is_none())
+            .map(|(coordinate, drill)| power::Demand {
+                coordinate: *coordinate,
+                power: drill.power_demand(),
+            });
+        let roboports = self
+            .logistics
+            .roboports
+            .iter()
+            .map(|(coordinate, roboport)| power::Demand {
+                coordinate: *coordinate,
+                power: roboport.power_demand(),
+            });
+        let mut demands: Vec<power::Demand> = machines
+            .chain(drills)
+            .chain(inserters)
+            .chain(roboports)
+            .collect();
+        demands.sort_by_key(|demand| (demand.coordinate.y, demand.coordinate.x));
+        self.power.tick(&demands, &mut self.fluids)
+    }
+
+    #[allow(dead_code)]
+    fn add_fuel(&mut self, item: Item, fuel_value: f64) {
+        self.fuels.insert(item, fuel_value);
+    }
+
+    /// Whether [`Self::insert_into`] would take the item now
+    fn can_insert_into(&self, to: Coordinate, item: Item, quality: quality::Quality) -> bool {
+        if self
+            .machines
+            .get(&to)
+            .is_some_and(|machine| machine.accepts_item(item, quality))
+        {
+            return true;
+        }
+        if !self.fuels.contains_key(&item) {
+            return false;
+        }
+        let burner = match (self.machines.get(&to), self.drills.get(&to)) {
+            (Some(machine), _) => machine.burner.as_ref(),
+            (None, Some(drill)) => drill.burner.as_ref(),
+            (None, None) => self.boilers.get(&to).map(|boiler| &boiler.burner),
+        };
+        burner.is_some_and(|burner| burner.accepts_fuel(item))
+    }
+
+    /// Puts one item into the entity at `to`: ingredients into a machine, fuel of any
+    /// quality into the burner of a machine, drill or boiler. Returns whether it was
+    /// accepted.
+    fn insert_into(&mut self, to: Coordinate, item: Item, quality: quality::Quality) -> bool {
+        if let Some(machine) = self.machines.get_mut(&to)
+            && machine.accepts_item(item, quality)
+        {
+            machine.insert_item(item, quality, 1);
+            return true;
+        }
+        let Some(&fuel_value) = self.fuels.get(&item) else {
+            return false;
+        };
+        let burner = if let Some(machine) = self.machines.get_mut(&to) {
+            machine.burner.as_mut()
+        } else if let Some(drill) = self.drills.get_mut(&to) {
+            drill.burner.as_mut()
+        } else {
+            self.boilers.get_mut(&to).map(|boiler| &mut boiler.burner)
+        };
+        burner.is_some_and(|burner| burner.insert_fuel(item, 1, fuel_value) == 1)
+    }
+
+    /// Moves fluid between the ports of every machine and the pipes next to them.
+    /// Input ports fill up with the fluid their recipe ingredient asks for,
+    /// output ports empty into the pipe on their side.
+    fn exchange_machine_fluids(&mut self) {
+        for (coordinate, machine) in &mut self.machines {
+            let ingredients: Vec<fluid::Fluid> = machine
+                .recipe
+                .iter()
+                .flat_map(|recipe| recipe.fluid_ingredients.iter().map(|(fluid, _)| *fluid))
+                .collect();
+            for (port, fluid) in machine.fluid_inputs.iter_mut().zip(ingredients) {
+                if !port.fluidbox.accepts(fluid) {
+                    continue;
                 }
+                let neighbor = coordinate.neighbor(port.side);
+                let taken = self.fluids.take(neighbor, fluid, port.fluidbox.free());
+                port.fluidbox.insert(fluid, taken);
+            }
+            for port in &mut machine.fluid_outputs {
+                let Some(fluid) = port.fluidbox.fluid else {
+                    continue;
+                };
+                let neighbor = coordinate.neighbor(port.side);
+                let added = self.fluids.put(neighbor, fluid, port.fluidbox.amount);
+                port.fluidbox.remove(added);
             }
+        }
+    }
+
+    fn tick_belts(&mut self) {
+        let tracing = self.events.is_active();
 
-            let right_transfers = belt.right_lane.tick_and_get_transfers();
-            for (item, pos) in right_transfers {
-                if let Some(next_coord) = belt.right_lane.next_lane_coord {
-                    all_transfers.push((next_coord, item, pos));
+        // Collect all transfers first:
+        // (source, source lane, target, item, position, quality, stack height)
+        let mut all_transfers: Vec<(
+            Coordinate,
+            bool,
+            Coordinate,
+            Item,
+            u32,
+            quality::Quality,
+            u8,
+        )> = Vec::new();
+
+        // Process all lanes and collect transfers in coordinate order, so transfers
+        // competing for the same target lane are applied the same way every run
+        let mut coordinates: Vec<Coordinate> = self.belts.keys().copied().collect();
+        coordinates.sort_by_key(|c| (c.y, c.x));
+        for coordinate in coordinates {
+            let Some(belt) = self.belts.get_mut(&coordinate) else {
+                continue;
+            };
+            for is_left in [true, false] {
+                let lane = if is_left {
+                    &mut belt.left_lane
+                } else {
+                    &mut belt.right_lane
+                };
+                let before = tracing.then(|| lane.items.clone());
+                let transfers = lane.tick_and_get_transfers();
+                if let Some(before) = before {
+                    self.events
+                        .emit_lane_tick(self.ticks, belt.coordinate, is_left, &before, lane);
+                }
+                for (item, pos, quality, height) in transfers {
+                    if let Some(next_coord) = lane.next_lane_coord {
+                        all_transfers.push((
+                            belt.coordinate,
+                            is_left,
+                            next_coord,
+                            item,
+                            pos,
+                            quality,
+                            height,
+                        ));
+                    }
                 }
             }
         }
 
         // Apply all transfers
-        for (target_coord, item, position) in all_transfers {
-            // Try to find the target belt and accept the item
-            // For now, we'll assume we're transferring to the left lane of the target
-            // A more complete implementation would track which lane to transfer to
-            if let Some(target_belt) = self.belts.get_mut(&target_coord) {
-                // Try left lane first, then right lane if that fails
-                if !target_belt.left_lane.accept_item(item, position) {
-                    target_belt.right_lane.accept_item(item, position);
-                }
+        for (source, source_is_left, target_coord, item, position, quality, height) in all_transfers
+        {
+            let accepted_by =
+                self.accept_transfer(source, target_coord, (item, quality, height), position);
+
+            if tracing {
+                let kind = accepted_by.map_or(
+                    events::EventKind::Dropped {
+                        item,
+                        height,
+                        source,
+                        target: target_coord,
+                    },
+                    |(target, target_is_left, position)| events::EventKind::Transferred {
+                        item,
+                        height,
+                        source,
+                        source_is_left,
+                        target,
+                        target_is_left,
+                        position,
+                    },
+                );
+                self.events.emit(self.ticks, kind);
+            }
+        }
+    }
+
+    /// Puts a stack that left the lane at `source` onto the belt at `target`,
+    /// `position` positions in, trying the left lane first. Movement left over past
+    /// the end of a lane carries on into the lanes after it, so fast belts move items
+    /// over several belts in one tick; if no lane further on has room, the stack stops
+    /// at the end of the one it reached. Returns the belt, lane and position that took it.
+    fn accept_transfer(
+        &mut self,
+        source: Coordinate,
+        target: Coordinate,
+        stack: (Item, quality::Quality, u8),
+        position: u32,
+    ) -> Option<(Coordinate, bool, u32)> {
+        self.accept_carried(source, target, stack, position, &mut HashSet::new())
+    }
+
+    /// [`Self::accept_transfer`], carrying on only into belts not in `passed`, so a
+    /// stack goes round a loop of belts at most once a tick
+    fn accept_carried(
+        &mut self,
+        source: Coordinate,
+        target: Coordinate,
+        (item, quality, height): (Item, quality::Quality, u8),
+        position: u32,
+        passed: &mut HashSet<Coordinate>,
+    ) -> Option<(Coordinate, bool, u32)> {
+        passed.insert(target);
+        let belt = self.belts.get(&target)?;
+        // Both lanes of a straight belt lead into the same lane, which is tried once.
+        // Lanes are never empty, so the carried position gets smaller every step.
+        let mut carries: Vec<(Coordinate, u32)> = [&belt.left_lane, &belt.right_lane]
+            .into_iter()
+            .filter_map(|lane| {
+                let next = lane.next_lane_coord?;
+                Some((next, position.checked_sub(lane.length)?))
+            })
+            .filter(|(next, _)| !passed.contains(next))
+            .collect();
+        carries.dedup();
+        for (next, carried) in carries {
+            let stack = (item, quality, height);
+            if let Some(accepted) = self.accept_carried(target, next, stack, carried, passed) {
+                return Some(accepted);
+            }
+        }
+        for is_left in [true, false] {
+            let belt = self.belts.get_mut(&target)?;
+            let lane = if is_left {
+                &mut belt.left_lane
+            } else {
+                &mut belt.right_lane
+            };
+            if lane.accept_stack(item, quality, height, position) {
+                return Some((target, is_left, position));
+            }
+            if self.events.is_active() {
+                self.events.emit(
+                    self.ticks,
+                    events::EventKind::TransferRejected {
+                        item,
+                        source,
+                        target,
+                        target_is_left: is_left,
+                        position,
+                    },
+                );
             }
         }
+        None
     }
 }
 
@@ -321,7 +995,7 @@ fn main() {
 
     // Create belt 1 (start) with some items
     let mut belt1 = SingleBelt::new(coord1, BeltType::Regular, Some(coord2), Some(coord2));
-    belt1.left_lane.items = [
+    belt1.left_lane.items = slots::Slots::from([
         Some((
             NonZeroUsize::new(1).expect("Failed to create NonZeroUsize"),
             20,
@@ -333,18 +1007,80 @@ fn main() {
         None,
         None,
         None,
-    ];
+    ]);
     world.add_belt(belt1);
 
+    // Optional outputs, e.g. `simulator --svg world.svg --png world.png --apng replay.png`.
+    // Modded belt tiers are loaded with `--belt-tiers tiers.txt`, before `--tui`.
+    let mut outputs: Vec<(String, String)> = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--tui" {
+            tui::run(world);
+            return;
+        }
+        if !matches!(flag.as_str(), "--svg" | "--png" | "--apng" | "--belt-tiers") {
+            eprintln!("Unknown argument: {flag}");
+            continue;
+        }
+        let Some(path) = args.next() else {
+            eprintln!("Missing path after {flag}");
+            break;
+        };
+        if flag == "--belt-tiers" {
+            let tiers = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|data| belt_tiers::BeltTiers::parse(&data));
+            match tiers {
+                Ok(tiers) => world.belt_tiers = tiers,
+                Err(err) => eprintln!("Failed to load belt tiers from {path}: {err}"),
+            }
+            continue;
+        }
+        outputs.push((flag, path));
+    }
+    let mut recorder = outputs
+        .iter()
+        .any(|(flag, _)| flag == "--apng")
+        .then(|| replay::Recorder::for_world(&world).with_frames_per_second(4));
+
     println!("World initialized with {} belts", world.belts.len());
     println!("Initial state:");
+    print!(
+        "{}",
+        ascii::render_world(&world, ascii::AsciiOptions::default())
+    );
     print_world_state(&world);
+    if let Some(recorder) = &mut recorder {
+        recorder.capture(&world);
+    }
 
     // Simulate a few ticks
     for tick in 0..10 {
         world.tick();
         println!("\nTick {} completed:", tick + 1);
+        print!(
+            "{}",
+            ascii::render_world(&world, ascii::AsciiOptions::default())
+        );
         print_world_state(&world);
+        if let Some(recorder) = &mut recorder {
+            recorder.capture(&world);
+        }
+    }
+
+    for (flag, path) in outputs {
+        let contents = match flag.as_str() {
+            "--svg" => snapshot::render_svg(&world).into_bytes(),
+            "--png" => snapshot::render_png(&world),
+            _ => recorder
+                .as_ref()
+                .and_then(replay::Recorder::to_apng)
+                .unwrap_or_default(),
+        };
+        if let Err(err) = std::fs::write(&path, contents) {
+            eprintln!("Failed to write {path}: {err}");
+        }
     }
 }
 
@@ -0,0 +1,282 @@
+//! Resource patches and the mining drills that deplete them.
+//!
+//! A drill mines the resource tiles in a square around it, one unit per mined item,
+//! and outputs onto the tile it faces: onto the near lane of a belt or into a
+//! machine that takes the item as an ingredient. Drills stop while their output is
+//! blocked and for good once every tile in their area is depleted.
+
+use super::{
+    Coordinate, Direction, Item, TICKS_PER_SECOND, World,
+    burner::Burner,
+    inserter::{DROP_POSITION, drops_on_left_lane},
+    modules::{Effect, Module},
+    quality::Quality,
+};
+
+/// A tile of ore, stone, coal and so on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resource {
+    pub item: Item,
+    pub amount: u32,
+    /// Seconds to mine one unit at mining speed 1
+    pub mining_time: f64,
+}
+
+#[allow(dead_code)]
+impl Resource {
+    pub const fn new(item: Item, amount: u32) -> Self {
+        Self {
+            item,
+            amount,
+            mining_time: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum DrillKind {
+    Burner,
+    Electric,
+    Big,
+}
+
+impl DrillKind {
+    pub const fn mining_speed(self) -> f64 {
+        match self {
+            Self::Burner => 0.25,
+            Self::Electric => 0.5,
+            Self::Big => 2.5,
+        }
+    }
+
+    /// Tiles mined in every direction around the drill
+    pub const fn mining_radius(self) -> i32 {
+        match self {
+            Self::Burner => 0,
+            Self::Electric => 2,
+            Self::Big => 6,
+        }
+    }
+
+    /// Power drawn while mining, in watts, from fuel for burner drills
+    pub const fn energy_usage(self) -> f64 {
+        match self {
+            Self::Burner => 150_000.0,
+            Self::Electric => 90_000.0,
+            Self::Big => 300_000.0,
+        }
+    }
+
+    pub const fn module_slots(self) -> usize {
+        match self {
+            Self::Burner => 0,
+            Self::Electric => 3,
+            Self::Big => 4,
+        }
+    }
+
+    /// Resource units used up per mined item
+    pub const fn resource_drain(self) -> f64 {
+        match self {
+            Self::Burner | Self::Electric => 1.0,
+            Self::Big => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MiningDrill {
+    pub kind: DrillKind,
+    /// Side the drill outputs to
+    pub direction: Direction,
+    /// Bonus items per mined item, e.g. 0.1 for +10% from mining productivity research
+    pub productivity: f64,
+    /// Progress towards the next item, from 0 to 1
+    pub progress: f64,
+    /// Progress towards the next bonus item, from 0 to 1
+    pub bonus_progress: f64,
+    /// Resource units mined but not yet taken from a tile
+    pub drained: f64,
+    /// Mined items waiting for room on the output
+    pub output: Vec<Item>,
+    /// No resources are left in the mining area
+    pub depleted: bool,
+    /// Number of items output so far
+    pub mined: u64,
+    /// Fuel of burner drills
+    pub burner: Option<Burner>,
+    pub modules: Vec<Module>,
+    /// Total effect of modules and beacons, kept up to date by the world
+    pub effect: Effect,
+    /// Quality of the drill itself, which scales its mining speed. Mined items are
+    /// always of normal quality.
+    pub quality: Quality,
+}
+
+#[allow(dead_code)]
+impl MiningDrill {
+    pub const fn new(kind: DrillKind, direction: Direction) -> Self {
+        Self {
+            kind,
+            direction,
+            productivity: 0.0,
+            progress: 0.0,
+            bonus_progress: 0.0,
+            drained: 0.0,
+            output: Vec::new(),
+            depleted: false,
+            mined: 0,
+            burner: match kind {
+                DrillKind::Burner => Some(Burner::new()),
+                _ => None,
+            },
+            modules: Vec::new(),
+            effect: Effect::NONE,
+            quality: Quality::Normal,
+        }
+    }
+
+    pub const fn with_quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Adds a module if a slot is free
+    pub fn insert_module(&mut self, module: Module) -> bool {
+        if self.modules.len() >= self.kind.module_slots() {
+            return false;
+        }
+        self.modules.push(module);
+        true
+    }
+
+    pub const fn with_productivity(mut self, productivity: f64) -> Self {
+        self.productivity = productivity;
+        self
+    }
+
+    /// Power the drill asks for this tick. Depleted drills draw nothing.
+    pub const fn power_demand(&self) -> f64 {
+        if self.depleted {
+            0.0
+        } else {
+            self.kind.energy_usage() * self.effect.consumption_multiplier()
+        }
+    }
+}
+
+impl World {
+    #[allow(dead_code)]
+    pub fn add_resource(&mut self, coordinate: Coordinate, resource: Resource) {
+        self.resources.insert(coordinate, resource);
+    }
+
+    #[allow(dead_code)]
+    pub fn add_drill(&mut self, coordinate: Coordinate, drill: MiningDrill) {
+        self.drills.insert(coordinate, drill);
+        self.update_effects();
+    }
+
+    /// Tiles with resources left around `coordinate`, in a fixed order
+    fn resources_around(&self, coordinate: Coordinate, radius: i32) -> Vec<Coordinate> {
+        let mut tiles: Vec<Coordinate> = self
+            .resources
+            .iter()
+            .filter(|(tile, resource)| {
+                resource.amount > 0
+                    && (tile.x - coordinate.x).abs() <= radius
+                    && (tile.y - coordinate.y).abs() <= radius
+            })
+            .map(|(tile, _)| *tile)
+            .collect();
+        tiles.sort_by_key(|c| (c.y, c.x));
+        tiles
+    }
+
+    /// Puts an item onto the tile in front of a drill, returning whether there was room
+    fn output_mined(&mut self, to: Coordinate, direction: Direction, item: Item) -> bool {
+        if let Some(belt) = self.belts.get_mut(&to) {
+            // Drills drop onto the lane closest to them
+            let lane = if drops_on_left_lane(belt.direction(), direction.opposite()) {
+                &mut belt.left_lane
+            } else {
+                &mut belt.right_lane
+            };
+            return lane.accept_item(item, DROP_POSITION);
+        }
+        self.insert_into(to, item, Quality::Normal)
+    }
+
+    /// Advances every drill by one tick, scaled by the power satisfaction returned by
+    /// `satisfaction` for electric drills or by their fuel for burner drills
+    pub fn tick_drills(&mut self, satisfaction: impl Fn(Coordinate) -> f64) {
+        let mut coordinates: Vec<Coordinate> = self.drills.keys().copied().collect();
+        coordinates.sort_by_key(|c| (c.y, c.x));
+        for coordinate in coordinates {
+            let Some(mut drill) = self.drills.get(&coordinate).cloned() else {
+                continue;
+            };
+            self.tick_drill(coordinate, &mut drill, &satisfaction);
+            self.drills.insert(coordinate, drill);
+        }
+    }
+
+    fn tick_drill(
+        &mut self,
+        coordinate: Coordinate,
+        drill: &mut MiningDrill,
+        satisfaction: impl Fn(Coordinate) -> f64,
+    ) {
+        let target = coordinate.neighbor(drill.direction);
+        while let Some(&item) = drill.output.first() {
+            if !self.output_mined(target, drill.direction, item) {
+                return;
+            }
+            drill.output.remove(0);
+            drill.mined += 1;
+        }
+
+        let tiles = self.resources_around(coordinate, drill.kind.mining_radius());
+        let Some(&tile) = tiles.first() else {
+            drill.depleted = true;
+            return;
+        };
+        let demand = drill.power_demand();
+        let factor = drill
+            .burner
+            .as_mut()
+            .map_or_else(|| satisfaction(coordinate), |burner| burner.burn(demand));
+        let resource = self.resources[&tile];
+        let speed = drill.kind.mining_speed()
+            * drill.quality.multiplier()
+            * drill.effect.speed_multiplier();
+        drill.progress += speed * factor / resource.mining_time / TICKS_PER_SECOND;
+        if drill.progress + 1e-9 < 1.0 {
+            return;
+        }
+        drill.progress -= 1.0;
+        drill.output.push(resource.item);
+        drill.bonus_progress += drill.productivity + drill.effect.productivity;
+        if drill.bonus_progress + 1e-9 >= 1.0 {
+            drill.bonus_progress -= 1.0;
+            drill.output.push(resource.item);
+        }
+
+        // Drains are at most one unit per item, so one tile loses at most one unit
+        drill.drained += drill.kind.resource_drain();
+        if drill.drained + 1e-9 < 1.0 {
+            return;
+        }
+        drill.drained -= 1.0;
+        if let Some(resource) = self.resources.get_mut(&tile) {
+            resource.amount -= 1;
+            if resource.amount == 0 {
+                self.resources.remove(&tile);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,202 @@
+use super::*;
+use crate::{
+    BeltType, SingleBelt,
+    crafting::{CraftingMachine, Recipe},
+    power::{GeneratorKind, PoleKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn ore_patch(world: &mut World, amount: u32) {
+    for x in -1..=1 {
+        for y in -1..=1 {
+            world.add_resource(Coordinate::new(x, y), Resource::new(id(1), amount));
+        }
+    }
+}
+
+fn total_ore(world: &World) -> u32 {
+    world
+        .resources
+        .values()
+        .map(|resource| resource.amount)
+        .sum()
+}
+
+#[test]
+fn test_electric_drill_outputs_onto_near_lane() {
+    let mut world = World::new();
+    ore_patch(&mut world, 100);
+    world.add_drill(
+        Coordinate::new(0, 0),
+        MiningDrill::new(DrillKind::Electric, Direction::North),
+    );
+    let next = Some(Coordinate::new(1, -1));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(0, -1),
+        BeltType::Regular,
+        next,
+        next,
+    ));
+    world.power.add_pole(Coordinate::new(1, 0), PoleKind::Small);
+    for x in 0..2 {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, 2), GeneratorKind::SolarPanel);
+    }
+
+    // Mining speed 0.5 mines an item every two seconds; it is output the tick after
+    for _ in 0..120 {
+        world.tick();
+    }
+    assert_eq!(world.drills[&Coordinate::new(0, 0)].mined, 0);
+    world.tick();
+    assert_eq!(world.drills[&Coordinate::new(0, 0)].mined, 1);
+    // The belt faces east, so the drill south of it fills the right lane
+    let belt = &world.belts[&Coordinate::new(0, -1)];
+    assert_eq!(belt.right_lane.items[0], Some((id(1), DROP_POSITION)));
+    assert!(belt.left_lane.items.iter().all(Option::is_none));
+    assert_eq!(total_ore(&world), 899);
+}
+
+#[test]
+fn test_unpowered_electric_drill_does_not_mine() {
+    let mut world = World::new();
+    ore_patch(&mut world, 100);
+    world.add_drill(
+        Coordinate::new(0, 0),
+        MiningDrill::new(DrillKind::Electric, Direction::North),
+    );
+    for _ in 0..600 {
+        world.tick();
+    }
+    assert_eq!(total_ore(&world), 900);
+}
+
+/// A drill at (0, 0) outputting into an assembler that takes up to 2000 ore
+fn drill_into_machine(drill: MiningDrill) -> World {
+    let mut world = World::new();
+    world.add_drill(Coordinate::new(0, 0), drill);
+    world.add_machine(
+        Coordinate::new(1, 0),
+        CraftingMachine::new(1.0).with_recipe(
+            Recipe::new("ore-sink", 1000.0)
+                .item_ingredient(id(1), 1000)
+                .item_product(id(2), 1),
+        ),
+    );
+    world
+}
+
+/// A burner drill with enough coal for the whole test
+fn fueled_burner_drill(direction: Direction) -> MiningDrill {
+    let mut drill = MiningDrill::new(DrillKind::Burner, direction);
+    if let Some(burner) = &mut drill.burner {
+        burner.insert_fuel(id(9), 10, 4_000_000.0);
+    }
+    drill
+}
+
+fn delivered(world: &World) -> u32 {
+    world.machines[&Coordinate::new(1, 0)]
+        .item_inputs
+        .get(&id(1))
+        .copied()
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_burner_drill_depletes_its_tile() {
+    let mut world = drill_into_machine(fueled_burner_drill(Direction::East));
+    world.add_resource(Coordinate::new(0, 0), Resource::new(id(1), 3));
+    // A neighbouring tile is outside of the burner drill's area
+    world.add_resource(Coordinate::new(0, 1), Resource::new(id(1), 50));
+
+    // Mining speed 0.25 takes four seconds per item
+    for _ in 0..=3 * 240 {
+        world.tick();
+    }
+    assert_eq!(delivered(&world), 3);
+    assert!(!world.resources.contains_key(&Coordinate::new(0, 0)));
+    world.tick();
+    let drill = &world.drills[&Coordinate::new(0, 0)];
+    assert!(drill.depleted);
+    assert!(drill.power_demand().abs() < 1e-9);
+    assert_eq!(world.resources[&Coordinate::new(0, 1)].amount, 50);
+}
+
+#[test]
+fn test_drill_carries_progress_into_the_next_item() {
+    let mut world = drill_into_machine(fueled_burner_drill(Direction::East));
+    let mut resource = Resource::new(id(1), 100);
+    resource.mining_time = 0.505;
+    world.add_resource(Coordinate::new(0, 0), resource);
+
+    // 121.2 ticks per item: five items take 606 ticks, not five times 122
+    for _ in 0..610 {
+        world.tick();
+    }
+    assert_eq!(delivered(&world), 5);
+}
+
+#[test]
+fn test_big_drill_drains_half_and_productivity_adds_items() {
+    let drill = MiningDrill::new(DrillKind::Big, Direction::East).with_productivity(0.5);
+    let mut world = drill_into_machine(drill);
+    world.add_resource(Coordinate::new(3, 3), Resource::new(id(1), 2));
+    world.power.add_pole(Coordinate::new(0, 1), PoleKind::Small);
+    for x in 0..5 {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, 2), GeneratorKind::SolarPanel);
+    }
+
+    for _ in 0..600 {
+        world.tick();
+    }
+    // Two units of ore are four mined items, plus two bonus items
+    assert!(world.resources.is_empty());
+    assert_eq!(delivered(&world), 6);
+    assert_eq!(world.drills[&Coordinate::new(0, 0)].mined, 6);
+}
+
+#[test]
+fn test_blocked_drill_stops_mining() {
+    let mut world = drill_into_machine(fueled_burner_drill(Direction::West));
+    world.add_resource(Coordinate::new(0, 0), Resource::new(id(1), 100));
+    for _ in 0..240 * 5 {
+        world.tick();
+    }
+    // Nothing to the west takes the ore, so one item waits in the drill
+    let drill = &world.drills[&Coordinate::new(0, 0)];
+    assert_eq!(drill.output, vec![id(1)]);
+    assert_eq!(drill.mined, 0);
+    assert_eq!(world.resources[&Coordinate::new(0, 0)].amount, 99);
+}
+
+#[test]
+fn test_burner_drill_needs_fuel() {
+    let mut world = drill_into_machine(MiningDrill::new(DrillKind::Burner, Direction::East));
+    world.add_resource(Coordinate::new(0, 0), Resource::new(id(1), 100));
+    for _ in 0..600 {
+        world.tick();
+    }
+    assert_eq!(delivered(&world), 0);
+
+    // 4 MJ of coal last 1600 ticks at 150 kW
+    world.add_fuel(id(9), 4_000_000.0);
+    assert!(world.insert_into(Coordinate::new(0, 0), id(9), Quality::Normal));
+    for _ in 0..1600 {
+        world.tick();
+    }
+    assert_eq!(delivered(&world), 6);
+    for _ in 0..600 {
+        world.tick();
+    }
+    assert_eq!(delivered(&world), 6);
+}
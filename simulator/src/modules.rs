@@ -0,0 +1,251 @@
+//! Modules, beacons and the effects they have on machines and drills.
+//!
+//! Every machine and drill carries the total [`Effect`] of its own modules and of
+//! all beacons reaching it. Totals are recomputed by [`World::update_effects`],
+//! which runs whenever a machine, drill or beacon is placed.
+
+use std::ops::Add;
+
+use super::{Coordinate, World, quality::Quality};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum ModuleKind {
+    Speed,
+    Productivity,
+    Efficiency,
+    Quality,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Module {
+    pub kind: ModuleKind,
+    /// 1 to 3
+    pub tier: u8,
+    pub quality: Quality,
+}
+
+/// Bonuses as fractions, e.g. 0.5 for +50% speed. Quality is the chance to craft
+/// a product one quality level higher.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Effect {
+    pub speed: f64,
+    pub productivity: f64,
+    pub consumption: f64,
+    pub quality: f64,
+}
+
+impl Add for Effect {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            speed: self.speed + other.speed,
+            productivity: self.productivity + other.productivity,
+            consumption: self.consumption + other.consumption,
+            quality: self.quality + other.quality,
+        }
+    }
+}
+
+impl Effect {
+    pub const NONE: Self = Self {
+        speed: 0.0,
+        productivity: 0.0,
+        consumption: 0.0,
+        quality: 0.0,
+    };
+
+    fn scaled(self, factor: f64) -> Self {
+        Self {
+            speed: self.speed * factor,
+            productivity: self.productivity * factor,
+            consumption: self.consumption * factor,
+            quality: self.quality * factor,
+        }
+    }
+
+    /// Applies the game's limits: speed and consumption never drop below -80%,
+    /// productivity and quality never below zero
+    pub const fn clamped(self) -> Self {
+        Self {
+            speed: self.speed.max(-0.8),
+            productivity: self.productivity.max(0.0),
+            consumption: self.consumption.max(-0.8),
+            quality: self.quality.max(0.0),
+        }
+    }
+
+    pub const fn speed_multiplier(&self) -> f64 {
+        1.0 + self.speed
+    }
+
+    pub const fn consumption_multiplier(&self) -> f64 {
+        1.0 + self.consumption
+    }
+}
+
+#[allow(dead_code)]
+impl Module {
+    pub const fn new(kind: ModuleKind, tier: u8) -> Self {
+        Self {
+            kind,
+            tier,
+            quality: Quality::Normal,
+        }
+    }
+
+    pub const fn with_quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Effect of the module with the values of Factorio 2.0. Quality scales the
+    /// bonuses but not the penalties.
+    pub fn effect(self) -> Effect {
+        let bonus = self.quality.multiplier();
+        let scale = |value: f64, good: bool| if good { value * bonus } else { value };
+        let effect = self.normal_effect();
+        Effect {
+            speed: scale(effect.speed, effect.speed > 0.0),
+            productivity: scale(effect.productivity, effect.productivity > 0.0),
+            consumption: scale(effect.consumption, effect.consumption < 0.0),
+            quality: scale(effect.quality, effect.quality > 0.0),
+        }
+    }
+
+    const fn normal_effect(self) -> Effect {
+        let tier = match self.tier {
+            0 | 1 => 0,
+            2 => 1,
+            _ => 2,
+        };
+        match self.kind {
+            ModuleKind::Speed => Effect {
+                speed: [0.2, 0.3, 0.5][tier],
+                consumption: [0.5, 0.6, 0.7][tier],
+                quality: [-0.01, -0.015, -0.025][tier],
+                ..Effect::NONE
+            },
+            ModuleKind::Productivity => Effect {
+                speed: [-0.05, -0.1, -0.15][tier],
+                productivity: [0.04, 0.06, 0.1][tier],
+                consumption: [0.4, 0.6, 0.8][tier],
+                ..Effect::NONE
+            },
+            ModuleKind::Efficiency => Effect {
+                consumption: [-0.3, -0.4, -0.5][tier],
+                ..Effect::NONE
+            },
+            ModuleKind::Quality => Effect {
+                speed: -0.05,
+                quality: [0.01, 0.02, 0.025][tier],
+                ..Effect::NONE
+            },
+        }
+    }
+}
+
+/// Total effect of a set of modules
+pub fn modules_effect(modules: &[Module]) -> Effect {
+    modules
+        .iter()
+        .fold(Effect::NONE, |total, module| total + module.effect())
+}
+
+/// Spreads the effect of its modules to every machine and drill around it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Beacon {
+    pub modules: Vec<Module>,
+}
+
+#[allow(dead_code)]
+impl Beacon {
+    pub const MODULE_SLOTS: usize = 2;
+    /// Tiles covered in every direction around the beacon
+    pub const SUPPLY_RADIUS: i32 = 4;
+    pub const DISTRIBUTION_EFFICIENCY: f64 = 1.5;
+
+    pub const fn new() -> Self {
+        Self {
+            modules: Vec::new(),
+        }
+    }
+
+    /// Adds a module if a slot is free. Beacons do not take productivity modules.
+    pub fn insert_module(&mut self, module: Module) -> bool {
+        if self.modules.len() >= Self::MODULE_SLOTS || module.kind == ModuleKind::Productivity {
+            return false;
+        }
+        self.modules.push(module);
+        true
+    }
+
+    pub const fn covers(beacon: Coordinate, coordinate: Coordinate) -> bool {
+        (beacon.x - coordinate.x).abs() <= Self::SUPPLY_RADIUS
+            && (beacon.y - coordinate.y).abs() <= Self::SUPPLY_RADIUS
+    }
+}
+
+/// Effect of `count` beacons with the given summed module effects on one entity.
+/// Each beacon transmits its modules at the distribution efficiency, and the total
+/// is divided by the square root of the number of beacons.
+#[allow(clippy::cast_precision_loss)]
+pub fn beacons_effect(modules: Effect, count: usize) -> Effect {
+    if count == 0 {
+        return Effect::NONE;
+    }
+    modules.scaled(Beacon::DISTRIBUTION_EFFICIENCY / (count as f64).sqrt())
+}
+
+impl World {
+    #[allow(dead_code)]
+    pub fn add_beacon(&mut self, coordinate: Coordinate, beacon: Beacon) {
+        self.beacons.insert(coordinate, beacon);
+        self.update_effects();
+    }
+
+    /// Summed module effects and number of the beacons reaching `coordinate`
+    fn beacons_reaching(&self, coordinate: Coordinate) -> (Effect, usize) {
+        self.beacons
+            .iter()
+            .filter(|(beacon, _)| Beacon::covers(**beacon, coordinate))
+            .fold((Effect::NONE, 0), |(total, count), (_, beacon)| {
+                (total + modules_effect(&beacon.modules), count + 1)
+            })
+    }
+
+    /// Recomputes the total effect of every machine and drill from its own modules
+    /// and the beacons around it
+    pub fn update_effects(&mut self) {
+        let machines: Vec<(Coordinate, Effect)> = self
+            .machines
+            .keys()
+            .map(|coordinate| {
+                let (modules, count) = self.beacons_reaching(*coordinate);
+                (*coordinate, beacons_effect(modules, count))
+            })
+            .collect();
+        for (coordinate, beacons) in machines {
+            if let Some(machine) = self.machines.get_mut(&coordinate) {
+                machine.effect = (modules_effect(&machine.modules) + beacons).clamped();
+            }
+        }
+        let drills: Vec<(Coordinate, Effect)> = self
+            .drills
+            .keys()
+            .map(|coordinate| {
+                let (modules, count) = self.beacons_reaching(*coordinate);
+                (*coordinate, beacons_effect(modules, count))
+            })
+            .collect();
+        for (coordinate, beacons) in drills {
+            if let Some(drill) = self.drills.get_mut(&coordinate) {
+                drill.effect = (modules_effect(&drill.modules) + beacons).clamped();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
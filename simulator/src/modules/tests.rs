@@ -0,0 +1,157 @@
+use super::*;
+use crate::{
+    Direction,
+    crafting::{CraftingMachine, Recipe},
+    mining::{DrillKind, MiningDrill, Resource},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+fn speed_beacon() -> Beacon {
+    let mut beacon = Beacon::new();
+    assert!(beacon.insert_module(Module::new(ModuleKind::Speed, 3)));
+    assert!(beacon.insert_module(Module::new(ModuleKind::Speed, 3)));
+    beacon
+}
+
+/// An assembler at (0, 0) with four module slots and a recipe that takes one second
+fn assembler() -> CraftingMachine {
+    CraftingMachine::new(1.0)
+        .with_recipe(Recipe::new("gear", 1.0).item_product(id(2), 1))
+        .with_module_slots(4)
+        .with_energy_usage(100_000.0)
+}
+
+#[test]
+fn test_module_effects_stack_and_clamp() {
+    let modules = [
+        Module::new(ModuleKind::Productivity, 3),
+        Module::new(ModuleKind::Productivity, 3),
+    ];
+    let effect = modules_effect(&modules);
+    assert!(close(effect.productivity, 0.2));
+    assert!(close(effect.speed, -0.3));
+    assert!(close(effect.consumption, 1.6));
+
+    let efficiency = [Module::new(ModuleKind::Efficiency, 3); 4];
+    let effect = modules_effect(&efficiency).clamped();
+    assert!(close(effect.consumption, -0.8));
+
+    // Speed modules lower quality, but never below zero
+    let effect = (modules_effect(&[Module::new(ModuleKind::Speed, 1)])).clamped();
+    assert!(close(effect.quality, 0.0));
+}
+
+#[test]
+fn test_beacons_transmit_with_diminishing_returns() {
+    let modules = modules_effect(&speed_beacon().modules);
+    assert!(close(beacons_effect(modules, 1).speed, 1.5));
+    // Four beacons are only twice as strong as one
+    assert!(close(
+        beacons_effect(modules + modules + modules + modules, 4).speed,
+        3.0
+    ));
+
+    let mut beacon = Beacon::new();
+    assert!(!beacon.insert_module(Module::new(ModuleKind::Productivity, 1)));
+    let mut full = speed_beacon();
+    assert!(!full.insert_module(Module::new(ModuleKind::Speed, 1)));
+}
+
+#[test]
+fn test_placing_entities_updates_effects() {
+    let mut world = World::new();
+    let mut machine = assembler();
+    assert!(machine.insert_module(Module::new(ModuleKind::Efficiency, 1)));
+    world.add_machine(Coordinate::new(0, 0), machine);
+    assert!(close(
+        world.machines[&Coordinate::new(0, 0)].effect.consumption,
+        -0.3
+    ));
+
+    world.add_beacon(Coordinate::new(4, -4), speed_beacon());
+    // Out of reach
+    world.add_beacon(Coordinate::new(5, 0), speed_beacon());
+    let effect = world.machines[&Coordinate::new(0, 0)].effect;
+    assert!(close(effect.speed, 1.5));
+    assert!(close(effect.consumption, 2.1 - 0.3));
+    assert!(close(effect.quality, 0.0));
+
+    // Entities placed later see beacons that are already there
+    world.add_drill(
+        Coordinate::new(5, 3),
+        MiningDrill::new(DrillKind::Electric, Direction::North),
+    );
+    assert!(close(
+        world.drills[&Coordinate::new(5, 3)].effect.speed,
+        1.5
+    ));
+}
+
+#[test]
+fn test_beaconed_assembler_outpaces_raw_one() {
+    let mut world = World::new();
+    world.add_machine(Coordinate::new(0, 0), assembler());
+    let mut productive = assembler();
+    for _ in 0..4 {
+        assert!(productive.insert_module(Module::new(ModuleKind::Productivity, 3)));
+    }
+    assert!(!productive.insert_module(Module::new(ModuleKind::Speed, 3)));
+    world.add_machine(Coordinate::new(20, 0), productive);
+    for x in [18, 22] {
+        world.add_beacon(Coordinate::new(x, 0), speed_beacon());
+    }
+
+    // The machines have no power, so run them directly for a minute
+    let mut products = Vec::new();
+    for x in [0, 20] {
+        let machine = world
+            .machines
+            .get_mut(&Coordinate::new(x, 0))
+            .expect("machine exists");
+        let mut made = 0;
+        for _ in 0..3600 {
+            machine.tick();
+            made += machine.take_output(id(2), Quality::Normal, 100);
+        }
+        products.push(made);
+    }
+    assert_eq!(products[0], 60);
+
+    // -60% from the modules and +2 * 1.5 / sqrt(2) * 100% from the beacons
+    let beaconed = &world.machines[&Coordinate::new(20, 0)];
+    let speed = 0.4 + 3.0 / 2.0_f64.sqrt();
+    assert!(close(beaconed.effect.speed_multiplier(), speed));
+    // At speed 2.52 a craft takes 23.8 ticks, and every craft adds 40% of a bonus craft
+    assert_eq!(beaconed.crafts, 151);
+    assert_eq!(products[1], 151 + 60);
+    assert!(close(
+        beaconed.power_demand(),
+        100_000.0 * beaconed.effect.consumption_multiplier()
+    ));
+}
+
+#[test]
+fn test_speed_modules_speed_up_drills() {
+    let mut burner = MiningDrill::new(DrillKind::Burner, Direction::East);
+    assert!(!burner.insert_module(Module::new(ModuleKind::Speed, 1)));
+
+    let mut drill = MiningDrill::new(DrillKind::Electric, Direction::East);
+    for _ in 0..3 {
+        assert!(drill.insert_module(Module::new(ModuleKind::Speed, 3)));
+    }
+    assert!(!drill.insert_module(Module::new(ModuleKind::Speed, 3)));
+    let mut world = World::new();
+    world.add_resource(Coordinate::new(0, 0), Resource::new(id(1), 1000));
+    world.add_drill(Coordinate::new(0, 0), drill);
+    let drill = &world.drills[&Coordinate::new(0, 0)];
+    assert!(close(drill.effect.speed, 1.5));
+    assert!(close(drill.power_demand(), 90_000.0 * 3.1));
+}
@@ -0,0 +1,107 @@
+//! Minimal PNG encoder for RGBA images.
+//!
+//! Image data is stored with uncompressed deflate blocks, which keeps the
+//! encoder tiny and dependency free at the cost of larger files. Snapshots
+//! are mostly flat colours and compress well when re-encoded by other tools.
+
+/// PNG file signature
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Largest payload of a single stored deflate block
+const MAX_STORED_BLOCK: usize = 65_535;
+
+/// Encodes an RGBA8 image as a complete PNG file.
+/// `rgba` must contain exactly `width * height * 4` bytes.
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, *b"IHDR", &header(width, height));
+    write_chunk(&mut out, *b"IDAT", &zlib_stored(&scanlines(width, rgba)));
+    write_chunk(&mut out, *b"IEND", &[]);
+    out
+}
+
+/// IHDR payload for an 8 bit RGBA, non interlaced image
+pub fn header(width: u32, height: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity(13);
+    data.extend_from_slice(&width.to_be_bytes());
+    data.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, colour type 6 (RGBA), default compression, filter and interlace
+    data.extend_from_slice(&[8, 6, 0, 0, 0]);
+    data
+}
+
+/// Prefixes every row of the image with filter type 0 (none)
+pub fn scanlines(width: u32, rgba: &[u8]) -> Vec<u8> {
+    let stride = width as usize * 4;
+    if stride == 0 {
+        return Vec::new();
+    }
+    let mut raw = Vec::with_capacity(rgba.len() + rgba.len() / stride);
+    for row in rgba.chunks(stride) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    raw
+}
+
+/// Appends a chunk with its length and CRC to `out`
+pub fn write_chunk(out: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    let len = u32::try_from(data.len()).expect("PNG chunk larger than 4 GiB");
+    out.extend_from_slice(&len.to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(&kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream made of stored (uncompressed) deflate blocks
+pub fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    // CMF/FLG: deflate with a 32K window, no preset dictionary, lowest compression level
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        // An empty stream still needs one final block
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let is_final = u8::from(chunks.peek().is_none());
+        // Chunks never exceed MAX_STORED_BLOCK so the length always fits
+        let len = u16::try_from(chunk.len()).unwrap_or(u16::MAX);
+        out.push(is_final);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65_521;
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % MOD;
+        b = (b + a) % MOD;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,48 @@
+use super::*;
+
+#[test]
+fn test_crc32_known_value() {
+    // CRC of the IEND chunk type is fixed by the PNG specification
+    assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+}
+
+#[test]
+fn test_adler32_known_value() {
+    assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    assert_eq!(adler32(&[]), 1);
+}
+
+#[test]
+fn test_encode_structure() {
+    let png = encode(2, 1, &[255, 0, 0, 255, 0, 255, 0, 255]);
+    assert_eq!(png[..8], SIGNATURE);
+    // IHDR is always the first chunk and 13 bytes long
+    assert_eq!(png[8..12], 13_u32.to_be_bytes());
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(png[16..20], 2_u32.to_be_bytes());
+    assert_eq!(png[20..24], 1_u32.to_be_bytes());
+    assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+}
+
+#[test]
+fn test_zlib_stored_splits_large_input() {
+    let data = vec![7_u8; MAX_STORED_BLOCK + 10];
+    let stream = zlib_stored(&data);
+    // Header, two block headers, payload and checksum
+    assert_eq!(stream.len(), 2 + 5 * 2 + data.len() + 4);
+    assert_eq!(stream[2], 0, "first block must not be final");
+    assert_eq!(
+        stream[2 + 5 + MAX_STORED_BLOCK],
+        1,
+        "last block must be final"
+    );
+}
+
+#[test]
+fn test_zlib_stored_empty_input() {
+    assert_eq!(
+        zlib_stored(&[]),
+        vec![0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+    );
+}
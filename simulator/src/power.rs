@@ -0,0 +1,375 @@
+//! Electric networks: power poles, generators, accumulators and consumers.
+//!
+//! Poles within wire reach of each other form a network. A consumer is part of the
+//! network of the first pole whose supply area covers its tile. Every tick each
+//! network balances demand against what its generators can produce, charging
+//! accumulators from any surplus and draining them when generators fall short.
+//! When even that is not enough, the network browns out: every consumer gets the
+//! same fraction of the power it asked for and works that much slower.
+
+use std::collections::HashMap;
+
+use super::{
+    Coordinate, Direction, TICKS_PER_SECOND,
+    fluid::{Fluid, FluidSystem},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum PoleKind {
+    Small,
+    Medium,
+    Big,
+    Substation,
+}
+
+impl PoleKind {
+    /// Tiles covered in every direction around the pole
+    pub const fn supply_radius(self) -> i32 {
+        match self {
+            Self::Small => 2,
+            Self::Medium => 3,
+            Self::Big => 1,
+            Self::Substation => 8,
+        }
+    }
+
+    /// Maximum length of a copper wire to another pole, in tiles
+    pub const fn wire_reach(self) -> f64 {
+        match self {
+            Self::Small => 7.5,
+            Self::Medium => 9.0,
+            Self::Big => 30.0,
+            Self::Substation => 18.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum GeneratorKind {
+    /// Burns steam taken from the pipe on `input_side`
+    SteamEngine { steam: Fluid, input_side: Direction },
+    /// Like a steam engine but needs hotter steam and produces far more
+    SteamTurbine { steam: Fluid, input_side: Direction },
+    /// Produces its peak power scaled by the daylight of the system
+    SolarPanel,
+}
+
+impl GeneratorKind {
+    /// Peak output in watts
+    pub const fn max_power(self) -> f64 {
+        match self {
+            Self::SteamEngine { .. } => 900_000.0,
+            Self::SteamTurbine { .. } => 5_820_000.0,
+            Self::SolarPanel => 60_000.0,
+        }
+    }
+
+    /// Energy in joules released per unit of steam, at the steam temperature each
+    /// generator is designed for (165°C for engines, 500°C for turbines)
+    const fn energy_per_steam(self) -> f64 {
+        match self {
+            Self::SteamEngine { .. } => 30_000.0,
+            Self::SteamTurbine { .. } => 97_000.0,
+            Self::SolarPanel => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Accumulator {
+    /// Stored energy in joules
+    pub charge: f64,
+}
+
+impl Accumulator {
+    pub const CAPACITY: f64 = 5_000_000.0;
+    /// Maximum charge and discharge rate in watts
+    pub const MAX_POWER: f64 = 300_000.0;
+}
+
+/// Demand of a single consumer for one tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Demand {
+    pub coordinate: Coordinate,
+    /// Watts the consumer would draw at full speed
+    pub power: f64,
+}
+
+/// What happened in one network during the last tick, in watts
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetworkStats {
+    pub demand: f64,
+    /// Power delivered to consumers, from generators and accumulators
+    pub consumption: f64,
+    /// Power produced by generators, including what went into accumulators
+    pub production: f64,
+    /// Most the generators could have produced
+    pub production_capacity: f64,
+    /// Fraction of the demand that was met, between 0 and 1
+    pub satisfaction: f64,
+    /// Energy stored in all accumulators of the network, in joules
+    pub accumulator_charge: f64,
+}
+
+/// All electric entities of a world
+#[derive(Default)]
+pub struct ElectricSystem {
+    pub poles: HashMap<Coordinate, PoleKind>,
+    pub generators: HashMap<Coordinate, GeneratorKind>,
+    pub accumulators: HashMap<Coordinate, Accumulator>,
+    /// Multiplier for solar panels, 1 at noon and 0 at night
+    pub daylight: f64,
+    /// Network index of every pole, rebuilt when poles change
+    network_of: HashMap<Coordinate, usize>,
+    network_count: usize,
+    dirty: bool,
+    /// Statistics of the last tick, one entry per network
+    pub stats: Vec<NetworkStats>,
+}
+
+fn distance(a: Coordinate, b: Coordinate) -> f64 {
+    f64::from(a.x - b.x).hypot(f64::from(a.y - b.y))
+}
+
+impl ElectricSystem {
+    pub fn new() -> Self {
+        Self {
+            daylight: 1.0,
+            ..Self::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn add_pole(&mut self, coordinate: Coordinate, kind: PoleKind) {
+        self.poles.insert(coordinate, kind);
+        self.dirty = true;
+    }
+
+    #[allow(dead_code)]
+    pub fn remove_pole(&mut self, coordinate: Coordinate) {
+        self.poles.remove(&coordinate);
+        self.dirty = true;
+    }
+
+    fn rebuild_networks(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let mut poles: Vec<(Coordinate, PoleKind)> =
+            self.poles.iter().map(|(c, kind)| (*c, *kind)).collect();
+        poles.sort_by_key(|(c, _)| (c.y, c.x));
+
+        self.network_of.clear();
+        self.network_count = 0;
+        for &(start, _) in &poles {
+            if self.network_of.contains_key(&start) {
+                continue;
+            }
+            let network = self.network_count;
+            self.network_count += 1;
+            self.network_of.insert(start, network);
+            let mut queue = vec![start];
+            while let Some(current) = queue.pop() {
+                let reach = self.poles[&current].wire_reach();
+                for &(other, other_kind) in &poles {
+                    // A wire has to fit within the reach of both poles
+                    if !self.network_of.contains_key(&other)
+                        && distance(current, other) <= reach.min(other_kind.wire_reach())
+                    {
+                        self.network_of.insert(other, network);
+                        queue.push(other);
+                    }
+                }
+            }
+        }
+        self.dirty = false;
+    }
+
+    /// Network powering the entity at `coordinate`, if any pole covers it
+    pub fn network_at(&mut self, coordinate: Coordinate) -> Option<usize> {
+        self.rebuild_networks();
+        let mut covering: Vec<(&Coordinate, &PoleKind)> = self
+            .poles
+            .iter()
+            .filter(|(pole, kind)| {
+                let radius = kind.supply_radius();
+                (pole.x - coordinate.x).abs() <= radius && (pole.y - coordinate.y).abs() <= radius
+            })
+            .collect();
+        covering.sort_by_key(|(pole, _)| (pole.y, pole.x));
+        covering
+            .first()
+            .and_then(|(pole, _)| self.network_of.get(pole).copied())
+    }
+
+    /// Splits the demand of one network between its generators and accumulators
+    fn balance(&mut self, stats: &mut NetworkStats, accumulators: &[Coordinate]) {
+        let from_generators = stats.demand.min(stats.production_capacity);
+        let shortfall = stats.demand - from_generators;
+        let surplus = stats.production_capacity - from_generators;
+
+        // Accumulators share the load evenly, limited by their rate and charge. What
+        // an empty, full or rate-limited accumulator cannot take goes to the others.
+        let mut limits: Vec<(f64, Coordinate)> = accumulators
+            .iter()
+            .filter_map(|coordinate| {
+                let accumulator = self.accumulators.get(coordinate)?;
+                let room = if shortfall > 0.0 {
+                    accumulator.charge
+                } else {
+                    Accumulator::CAPACITY - accumulator.charge
+                };
+                Some((
+                    Accumulator::MAX_POWER.min(room * TICKS_PER_SECOND),
+                    *coordinate,
+                ))
+            })
+            .collect();
+        // The most limited go first, so the rest see what they left over
+        limits.sort_by(|a, b| {
+            a.0.total_cmp(&b.0)
+                .then((a.1.y, a.1.x).cmp(&(b.1.y, b.1.x)))
+        });
+        let mut remaining = if shortfall > 0.0 { shortfall } else { surplus };
+        let mut from_accumulators = 0.0;
+        let mut into_accumulators = 0.0;
+        for (index, (limit, coordinate)) in limits.iter().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let share = remaining / (limits.len() - index) as f64;
+            let power = share.min(*limit).max(0.0);
+            remaining -= power;
+            let Some(accumulator) = self.accumulators.get_mut(coordinate) else {
+                continue;
+            };
+            if shortfall > 0.0 {
+                accumulator.charge -= power / TICKS_PER_SECOND;
+                from_accumulators += power;
+            } else {
+                accumulator.charge += power / TICKS_PER_SECOND;
+                into_accumulators += power;
+            }
+        }
+        stats.accumulator_charge += accumulators
+            .iter()
+            .filter_map(|coordinate| self.accumulators.get(coordinate))
+            .map(|accumulator| accumulator.charge)
+            .sum::<f64>();
+
+        stats.consumption = from_generators + from_accumulators;
+        stats.production = from_generators + into_accumulators;
+        stats.satisfaction = if stats.demand > 0.0 {
+            (stats.consumption / stats.demand).min(1.0)
+        } else {
+            1.0
+        };
+    }
+
+    /// Balances all networks for one tick. Steam for engines and turbines is taken
+    /// from `fluids`. Returns the satisfaction of every consumer by coordinate;
+    /// consumers outside of any network get nothing.
+    pub fn tick(
+        &mut self,
+        demands: &[Demand],
+        fluids: &mut FluidSystem,
+    ) -> HashMap<Coordinate, f64> {
+        self.rebuild_networks();
+        let networks = self.network_count;
+        let mut stats = vec![NetworkStats::default(); networks];
+        let mut satisfaction = HashMap::new();
+
+        let mut consumers: Vec<Vec<Coordinate>> = vec![Vec::new(); networks];
+        for demand in demands {
+            match self.network_at(demand.coordinate) {
+                Some(network) => {
+                    stats[network].demand += demand.power;
+                    consumers[network].push(demand.coordinate);
+                }
+                None => {
+                    satisfaction.insert(demand.coordinate, 0.0);
+                }
+            }
+        }
+
+        // What every generator could produce this tick, limited by steam and daylight
+        let mut generators: Vec<(Coordinate, GeneratorKind)> = self
+            .generators
+            .iter()
+            .map(|(c, kind)| (*c, *kind))
+            .collect();
+        generators.sort_by_key(|(c, _)| (c.y, c.x));
+        let mut capacities: Vec<(Coordinate, GeneratorKind, usize, f64)> = Vec::new();
+        // Steam not yet claimed by a generator, by where it is stored. Generators
+        // sharing a segment claim from it in turn so no steam is counted twice.
+        let mut steam_left: HashMap<(Coordinate, Fluid), f64> = HashMap::new();
+        for (coordinate, kind) in generators {
+            let Some(network) = self.network_at(coordinate) else {
+                continue;
+            };
+            let capacity = match kind {
+                GeneratorKind::SolarPanel => kind.max_power() * self.daylight.clamp(0.0, 1.0),
+                GeneratorKind::SteamEngine { steam, input_side }
+                | GeneratorKind::SteamTurbine { steam, input_side } => {
+                    let input = coordinate.neighbor(input_side);
+                    let key = (fluids.storage_key(input), steam);
+                    let available =
+                        *steam_left
+                            .entry(key)
+                            .or_insert_with(|| match fluids.contents(input) {
+                                Some((Some(fluid), amount)) if fluid == steam => amount,
+                                _ => 0.0,
+                            });
+                    let capacity = kind
+                        .max_power()
+                        .min(available * kind.energy_per_steam() * TICKS_PER_SECOND);
+                    steam_left.insert(
+                        key,
+                        available - capacity / TICKS_PER_SECOND / kind.energy_per_steam(),
+                    );
+                    capacity
+                }
+            };
+            stats[network].production_capacity += capacity;
+            capacities.push((coordinate, kind, network, capacity));
+        }
+
+        let mut accumulators: Vec<Coordinate> = self.accumulators.keys().copied().collect();
+        accumulators.sort_by_key(|c| (c.y, c.x));
+        let mut accumulators_of: Vec<Vec<Coordinate>> = vec![Vec::new(); networks];
+        for coordinate in accumulators {
+            if let Some(network) = self.network_at(coordinate) {
+                accumulators_of[network].push(coordinate);
+            }
+        }
+
+        for (network, stats) in stats.iter_mut().enumerate() {
+            self.balance(stats, &accumulators_of[network]);
+            for coordinate in &consumers[network] {
+                satisfaction.insert(*coordinate, stats.satisfaction);
+            }
+        }
+
+        // Generators share the production in proportion to what they could produce,
+        // and steam generators burn steam for their part
+        for (coordinate, kind, network, capacity) in capacities {
+            let stats = &stats[network];
+            if stats.production_capacity <= 0.0 {
+                continue;
+            }
+            let produced = stats.production * capacity / stats.production_capacity;
+            if let GeneratorKind::SteamEngine { steam, input_side }
+            | GeneratorKind::SteamTurbine { steam, input_side } = kind
+            {
+                let steam_used = produced / TICKS_PER_SECOND / kind.energy_per_steam();
+                fluids.take(coordinate.neighbor(input_side), steam, steam_used);
+            }
+        }
+
+        self.stats = stats;
+        satisfaction
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,229 @@
+use super::*;
+use crate::{
+    World,
+    crafting::{CraftingMachine, Recipe},
+    fluid::{FluidEntity, FluidEntityKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn demand(x: i32, y: i32, power: f64) -> Demand {
+    Demand {
+        coordinate: Coordinate::new(x, y),
+        power,
+    }
+}
+
+fn solar_field(system: &mut ElectricSystem, panels: i32) {
+    system.add_pole(Coordinate::new(0, 0), PoleKind::Substation);
+    for x in 0..panels {
+        system
+            .generators
+            .insert(Coordinate::new(x - 4, -4), GeneratorKind::SolarPanel);
+    }
+}
+
+#[test]
+fn test_poles_connect_within_wire_reach() {
+    let mut system = ElectricSystem::new();
+    system.add_pole(Coordinate::new(0, 0), PoleKind::Small);
+    system.add_pole(Coordinate::new(7, 0), PoleKind::Small);
+    system.add_pole(Coordinate::new(15, 0), PoleKind::Small);
+    // A big pole reaches far, but the wire to a small pole is limited by the small one
+    system.add_pole(Coordinate::new(15, 20), PoleKind::Big);
+
+    let first = system.network_at(Coordinate::new(1, 1));
+    assert!(first.is_some());
+    assert_eq!(system.network_at(Coordinate::new(8, 0)), first);
+    assert_ne!(system.network_at(Coordinate::new(15, 0)), first);
+    assert_ne!(
+        system.network_at(Coordinate::new(15, 20)),
+        system.network_at(Coordinate::new(15, 0))
+    );
+    // Outside of every supply area
+    assert_eq!(system.network_at(Coordinate::new(3, 0)), None);
+
+    system.add_pole(Coordinate::new(11, 0), PoleKind::Small);
+    assert_eq!(system.network_at(Coordinate::new(15, 0)), first);
+}
+
+#[test]
+fn test_enough_supply_satisfies_everyone() {
+    let mut system = ElectricSystem::new();
+    solar_field(&mut system, 4);
+    let mut fluids = FluidSystem::default();
+
+    let satisfaction = system.tick(
+        &[demand(1, 1, 100_000.0), demand(20, 20, 1_000.0)],
+        &mut fluids,
+    );
+    assert!((satisfaction[&Coordinate::new(1, 1)] - 1.0).abs() < 1e-9);
+    // Not covered by any pole
+    assert!(satisfaction[&Coordinate::new(20, 20)].abs() < 1e-9);
+    let stats = system.stats[0];
+    assert!((stats.demand - 100_000.0).abs() < 1e-9);
+    assert!((stats.production_capacity - 240_000.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_brownout_scales_satisfaction() {
+    let mut system = ElectricSystem::new();
+    solar_field(&mut system, 4);
+    system.daylight = 0.5;
+    let mut fluids = FluidSystem::default();
+
+    let satisfaction = system.tick(
+        &[demand(1, 1, 150_000.0), demand(2, 2, 90_000.0)],
+        &mut fluids,
+    );
+    // 120 kW for 240 kW of demand
+    assert!((satisfaction[&Coordinate::new(1, 1)] - 0.5).abs() < 1e-9);
+    assert!((satisfaction[&Coordinate::new(2, 2)] - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_accumulators_buffer_surplus_for_the_night() {
+    let mut system = ElectricSystem::new();
+    solar_field(&mut system, 10);
+    system
+        .accumulators
+        .insert(Coordinate::new(3, 3), Accumulator { charge: 0.0 });
+    let mut fluids = FluidSystem::default();
+    let demands = [demand(1, 1, 300_000.0)];
+
+    // 600 kW of panels, 300 kW go to the consumer and 300 kW into the accumulator
+    for _ in 0..60 {
+        system.tick(&demands, &mut fluids);
+    }
+    let charge = system.accumulators[&Coordinate::new(3, 3)].charge;
+    assert!((charge - 300_000.0).abs() < 1e-6);
+
+    system.daylight = 0.0;
+    let satisfaction = system.tick(&demands, &mut fluids);
+    assert!((satisfaction[&Coordinate::new(1, 1)] - 1.0).abs() < 1e-9);
+    let charge = system.accumulators[&Coordinate::new(3, 3)].charge;
+    assert!((charge - 295_000.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_accumulators_cover_for_an_empty_one() {
+    let mut system = ElectricSystem::new();
+    solar_field(&mut system, 0);
+    system
+        .accumulators
+        .insert(Coordinate::new(3, 3), Accumulator { charge: 0.0 });
+    system.accumulators.insert(
+        Coordinate::new(4, 3),
+        Accumulator {
+            charge: 1_000_000.0,
+        },
+    );
+    let mut fluids = FluidSystem::default();
+
+    // The empty accumulator cannot give its half, so the other one gives all 200 kW
+    let satisfaction = system.tick(&[demand(1, 1, 200_000.0)], &mut fluids);
+    assert!((satisfaction[&Coordinate::new(1, 1)] - 1.0).abs() < 1e-9);
+    let charge = system.accumulators[&Coordinate::new(4, 3)].charge;
+    assert!((charge - (1_000_000.0 - 200_000.0 / 60.0)).abs() < 1e-6);
+}
+
+#[test]
+fn test_steam_engines_share_the_steam_of_a_segment() {
+    let steam = id(1);
+    let mut system = ElectricSystem::new();
+    system.add_pole(Coordinate::new(2, 0), PoleKind::Substation);
+    let mut fluids = FluidSystem::default();
+    let mut tank = FluidEntity::new(FluidEntityKind::StorageTank);
+    tank.fluidbox.insert(steam, 0.5);
+    fluids.add(Coordinate::new(-1, 0), tank);
+    fluids.add(
+        Coordinate::new(-1, 1),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+    for y in 0..2 {
+        system.generators.insert(
+            Coordinate::new(0, y),
+            GeneratorKind::SteamEngine {
+                steam,
+                input_side: Direction::West,
+            },
+        );
+    }
+
+    // 0.5 steam a tick is 900 kW, for both engines together
+    system.tick(&[demand(1, 1, 1_800_000.0)], &mut fluids);
+    let stats = system.stats[0];
+    assert!((stats.production_capacity - 900_000.0).abs() < 1e-6);
+    assert!((stats.satisfaction - 0.5).abs() < 1e-9);
+    let (_, left) = fluids
+        .contents(Coordinate::new(-1, 0))
+        .expect("tank exists");
+    assert!(left.abs() < 1e-9);
+}
+
+#[test]
+fn test_steam_engine_burns_steam_for_what_it_produces() {
+    let steam = id(1);
+    let mut world = World::new();
+    let mut tank = FluidEntity::new(FluidEntityKind::StorageTank);
+    tank.fluidbox.insert(steam, 1_000.0);
+    world.add_fluid_entity(Coordinate::new(-1, 0), tank);
+    world.power.generators.insert(
+        Coordinate::new(0, 0),
+        GeneratorKind::SteamEngine {
+            steam,
+            input_side: Direction::West,
+        },
+    );
+    world
+        .power
+        .add_pole(Coordinate::new(1, 1), PoleKind::Medium);
+    // 450 kW of assemblers, half of what the engine can produce
+    world.add_machine(
+        Coordinate::new(2, 2),
+        CraftingMachine::new(1.0)
+            .with_recipe(Recipe::new("nothing", 1.0))
+            .with_energy_usage(450_000.0),
+    );
+
+    for _ in 0..60 {
+        world.tick();
+    }
+    let stats = world.power.stats[0];
+    assert!((stats.satisfaction - 1.0).abs() < 1e-9);
+    assert!((stats.production - 450_000.0).abs() < 1e-6);
+    // 450 kW at 30 kJ per unit is 15 steam per second
+    let (_, left) = world
+        .fluids
+        .contents(Coordinate::new(-1, 0))
+        .expect("tank exists");
+    assert!((left - 985.0).abs() < 1e-6);
+    assert_eq!(world.machines[&Coordinate::new(2, 2)].crafts, 1);
+}
+
+#[test]
+fn test_brownout_slows_crafting() {
+    let mut world = World::new();
+    solar_field(&mut world.power, 1);
+    // 60 kW of panels for a 120 kW machine
+    world.add_machine(
+        Coordinate::new(1, 1),
+        CraftingMachine::new(1.0)
+            .with_recipe(Recipe::new("nothing", 1.0))
+            .with_energy_usage(120_000.0),
+    );
+    for _ in 0..120 {
+        world.tick();
+    }
+    assert_eq!(world.machines[&Coordinate::new(1, 1)].crafts, 1);
+
+    // Without any pole the machine does not work at all
+    world.power.remove_pole(Coordinate::new(0, 0));
+    for _ in 0..120 {
+        world.tick();
+    }
+    assert_eq!(world.machines[&Coordinate::new(1, 1)].crafts, 1);
+}
@@ -0,0 +1,107 @@
+//! Quality tiers of items and entities.
+//!
+//! Every item on a belt, in a hand or in a machine has a quality. Crafting machines
+//! with a quality effect roll the quality of each craft's products: the effect is
+//! the chance to go up one tier, and every further tier has a 10% chance on top.
+//! Entities of higher quality work faster, 30% per quality level.
+
+/// From worst to best. Ordering follows the tiers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(dead_code)]
+pub enum Quality {
+    #[default]
+    Normal,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+/// Chance to go up each tier after the first one
+const FURTHER_TIER_CHANCE: f64 = 0.1;
+
+#[allow(dead_code)]
+impl Quality {
+    pub const ALL: [Self; 5] = [
+        Self::Normal,
+        Self::Uncommon,
+        Self::Rare,
+        Self::Epic,
+        Self::Legendary,
+    ];
+
+    /// Quality level as used for stat bonuses. Legendary skips a level.
+    pub const fn level(self) -> u32 {
+        match self {
+            Self::Normal => 0,
+            Self::Uncommon => 1,
+            Self::Rare => 2,
+            Self::Epic => 3,
+            Self::Legendary => 5,
+        }
+    }
+
+    /// The tier above, `None` for legendary
+    pub const fn next(self) -> Option<Self> {
+        match self {
+            Self::Normal => Some(Self::Uncommon),
+            Self::Uncommon => Some(Self::Rare),
+            Self::Rare => Some(Self::Epic),
+            Self::Epic => Some(Self::Legendary),
+            Self::Legendary => None,
+        }
+    }
+
+    /// Factor on crafting speed, mining speed, rotation speed and module bonuses
+    pub fn multiplier(self) -> f64 {
+        0.3f64.mul_add(f64::from(self.level()), 1.0)
+    }
+
+    /// Quality of a craft from ingredients of this quality with a quality effect of
+    /// `chance`
+    pub fn roll(self, chance: f64, rng: &mut QualityRng) -> Self {
+        let mut quality = self;
+        let mut chance = chance;
+        while let Some(next) = quality.next() {
+            if rng.next_f64() >= chance {
+                break;
+            }
+            quality = next;
+            chance = FURTHER_TIER_CHANCE;
+        }
+        quality
+    }
+}
+
+/// Deterministic random numbers for quality rolls, so a simulation replays the same
+/// way every time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QualityRng {
+    state: u64,
+}
+
+impl QualityRng {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Uniformly distributed in `[0, 1)` (splitmix64)
+    #[allow(clippy::cast_precision_loss)]
+    pub const fn next_f64(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for QualityRng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,139 @@
+use super::*;
+use crate::{
+    BeltType, Coordinate, SingleBelt, World,
+    crafting::{CraftingMachine, Recipe},
+    modules::{Module, ModuleKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+#[test]
+fn test_multiplier_grows_by_level() {
+    assert!((Quality::Normal.multiplier() - 1.0).abs() < 1e-9);
+    assert!((Quality::Rare.multiplier() - 1.6).abs() < 1e-9);
+    assert!((Quality::Legendary.multiplier() - 2.5).abs() < 1e-9);
+    assert_eq!(Quality::Legendary.next(), None);
+}
+
+#[test]
+fn test_rolls_follow_the_chances() {
+    let mut rng = QualityRng::new(7);
+    assert_eq!(Quality::Rare.roll(0.0, &mut rng), Quality::Rare);
+    assert_eq!(Quality::Epic.roll(1.0, &mut rng), Quality::Legendary);
+
+    let mut counts = [0u32; 5];
+    for _ in 0..100_000 {
+        let quality = Quality::Normal.roll(0.5, &mut rng);
+        counts[Quality::ALL
+            .iter()
+            .position(|q| *q == quality)
+            .expect("tier")] += 1;
+    }
+    // Half go up one tier, and a tenth of those go up another
+    assert!((49_000..51_000).contains(&counts[0]));
+    assert!((44_000..46_000).contains(&counts[1]));
+    assert!((4_000..5_000).contains(&counts[2]));
+    assert!(counts[4] < 100);
+}
+
+#[test]
+fn test_quality_moves_with_items_between_lanes() {
+    let mut world = World::new();
+    let next = Some(Coordinate::new(1, 0));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(0, 0),
+        BeltType::Express,
+        next,
+        next,
+    ));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(1, 0),
+        BeltType::Express,
+        None,
+        None,
+    ));
+    let belt = world.belts.get_mut(&Coordinate::new(0, 0)).expect("belt");
+    assert!(
+        belt.left_lane
+            .accept_item_of_quality(id(1), Quality::Epic, 200)
+    );
+    for _ in 0..10 {
+        world.tick();
+    }
+    let lane = &world.belts[&Coordinate::new(1, 0)].left_lane;
+    let slot = lane.items.iter().position(Option::is_some).expect("item");
+    assert_eq!(lane.quality(slot), Quality::Epic);
+}
+
+#[test]
+fn test_quality_modules_upgrade_products() {
+    let mut machine = CraftingMachine::new(100.0)
+        .with_recipe(Recipe::new("gear", 1.0).item_product(id(2), 1))
+        .with_module_slots(4);
+    for _ in 0..4 {
+        machine.insert_module(Module::new(ModuleKind::Quality, 3).with_quality(Quality::Legendary));
+    }
+    let mut world = World::new();
+    world.add_machine(Coordinate::new(0, 0), machine);
+    // Four legendary quality 3 modules give a 25% chance
+    let chance = world.machines[&Coordinate::new(0, 0)].effect.quality;
+    assert!((chance - 0.25).abs() < 1e-9);
+
+    let mut made = [0u32; 5];
+    for _ in 0..2000 {
+        world.tick();
+        let machine = world
+            .machines
+            .get_mut(&Coordinate::new(0, 0))
+            .expect("machine");
+        for (i, quality) in Quality::ALL.into_iter().enumerate() {
+            made[i] += machine.take_output(id(2), quality, 10);
+        }
+    }
+    let total: u32 = made.iter().sum();
+    assert_eq!(total, 2000);
+    assert!((400..600).contains(&made[1]));
+    assert!((25..80).contains(&made[2]));
+}
+
+#[test]
+fn test_machines_only_take_ingredients_of_their_quality() {
+    let mut world = World::new();
+    world.add_machine(
+        Coordinate::new(0, 0),
+        CraftingMachine::new(1.0)
+            .with_recipe_quality(Quality::Rare)
+            .with_recipe(
+                Recipe::new("gear", 1.0)
+                    .item_ingredient(id(1), 2)
+                    .item_product(id(2), 1),
+            ),
+    );
+    let coordinate = Coordinate::new(0, 0);
+    assert!(!world.insert_into(coordinate, id(1), Quality::Normal));
+    assert!(world.insert_into(coordinate, id(1), Quality::Rare));
+    assert!(world.insert_into(coordinate, id(1), Quality::Rare));
+    for _ in 0..=60 {
+        world.tick();
+    }
+    let machine = &world.machines[&coordinate];
+    assert_eq!(machine.item_outputs.get(&(id(2), Quality::Rare)), Some(&1));
+}
+
+#[test]
+fn test_entity_quality_scales_speed() {
+    let recipe = Recipe::new("gear", 1.0).item_product(id(2), 1);
+    let mut normal = CraftingMachine::new(1.0).with_recipe(recipe.clone());
+    let mut legendary = CraftingMachine::new(1.0)
+        .with_recipe(recipe)
+        .with_quality(Quality::Legendary);
+    for _ in 0..120 {
+        normal.tick();
+        legendary.tick();
+    }
+    assert_eq!(normal.crafts, 2);
+    assert_eq!(legendary.crafts, 5);
+}
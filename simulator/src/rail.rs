@@ -0,0 +1,1189 @@
+//! Trains: rails, train stops, locomotives and wagons.
+//!
+//! Rails are laid out on the tile grid. Every rail piece connects two sides of its
+//! tile: a straight rail two opposite sides, a curved rail two adjacent ones, so a
+//! curve turns by 90° within a single tile. Several pieces on one tile form a
+//! junction. A train is a chain of vehicles, each [`VEHICLE_LENGTH`] tiles long,
+//! occupying the rail pieces behind its front. Trains only drive forwards.
+//!
+//! Every tick a train with a destination, given by its schedule (see
+//! [`super::schedule`]), follows the shortest route to it,
+//! accelerating while its locomotives have fuel and braking in time to stop at the
+//! far edge of the train stop's tile, or behind another train in its way. A stopped
+//! train can be loaded and unloaded by inserters and, for fluid wagons, pumps.
+//!
+//! Signals stand on the side of a tile a train leaves through and divide the rails
+//! into blocks; rails crossing on one tile always share a block. Once a train comes
+//! within braking distance of a signal it reserves the block behind it, which it only
+//! gets if no other train stands in or has reserved that block. A chain signal also
+//! needs every block up to and including the one behind the next rail signal, and
+//! reserves all of them at once. Reservations are released as the rear of the train
+//! leaves a block. A rail with a signal for one direction only is one-way.
+//!
+//! Routes avoid blocks with other trains in them and stations they do not stop at,
+//! by adding a penalty to the length of the route.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+};
+
+use super::{
+    Coordinate, Direction, Item, World,
+    burner::Burner,
+    checksum::Checksum,
+    circuit::{Connector, Signals},
+    fluid::{FluidBox, FluidEntityKind},
+    quality::Quality,
+    schedule::{Interrupt, ScheduleRecord, WaitCondition},
+};
+
+/// Length of every vehicle including the gap to the next one, in tiles
+pub const VEHICLE_LENGTH: f64 = 7.0;
+/// Length of a quarter circle through a tile, from one side's middle to the next
+pub const CURVED_RAIL_LENGTH: f64 = std::f64::consts::FRAC_PI_4;
+/// 40 slots of 50 items
+pub const CARGO_WAGON_CAPACITY: u32 = 2_000;
+pub const FLUID_WAGON_CAPACITY: f64 = 50_000.0;
+/// Top speed in tiles per tick, about 259 km/h
+pub const MAX_SPEED: f64 = 1.2;
+/// Power a locomotive burns while accelerating or holding its speed, in watts
+pub const LOCOMOTIVE_POWER: f64 = 600_000.0;
+/// Pulling force of a locomotive, in weight units times tiles per tick squared
+const LOCOMOTIVE_FORCE: f64 = 10.0;
+
+/// Extra route length for entering a block another train stands in
+const OCCUPIED_BLOCK_PENALTY: f64 = 1_000.0;
+/// Extra route length for driving through a train stop
+const TRAIN_STOP_PENALTY: f64 = 2_000.0;
+
+/// Distances below this count as arrived
+const EPSILON: f64 = 1e-9;
+
+/// A rail piece connecting two sides of its tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rail {
+    pub ends: [Direction; 2],
+}
+
+#[allow(dead_code)]
+impl Rail {
+    /// A straight rail along `direction`
+    pub const fn straight(direction: Direction) -> Self {
+        Self {
+            ends: [direction, direction.opposite()],
+        }
+    }
+
+    /// A curved rail between two adjacent sides
+    pub const fn curved(from: Direction, to: Direction) -> Self {
+        Self { ends: [from, to] }
+    }
+
+    pub fn is_curved(self) -> bool {
+        self.ends[0] != self.ends[1].opposite()
+    }
+
+    pub fn length(self) -> f64 {
+        if self.is_curved() {
+            CURVED_RAIL_LENGTH
+        } else {
+            1.0
+        }
+    }
+
+    /// Side a train entering through `entry` leaves through, if it can enter there
+    pub fn exit(self, entry: Direction) -> Option<Direction> {
+        if self.ends[0] == entry {
+            Some(self.ends[1])
+        } else if self.ends[1] == entry {
+            Some(self.ends[0])
+        } else {
+            None
+        }
+    }
+}
+
+/// One rail piece driven through towards `exit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrackPiece {
+    pub coordinate: Coordinate,
+    /// Index of the rail among the rails of its tile
+    pub rail: usize,
+    pub exit: Direction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum SignalKind {
+    /// Lets a train into the next block if no other train holds it
+    Rail,
+    /// Lets a train in only if the way up to the block behind the next rail signal
+    /// is free as well
+    Chain,
+}
+
+/// Trains waiting for blocks held by each other, none of which can move again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadlock {
+    pub trains: Vec<usize>,
+    /// Blocks the trains are waiting for
+    pub blocks: Vec<usize>,
+}
+
+impl std::fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |numbers: &[usize]| {
+            numbers
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(
+            f,
+            "trains {} wait for blocks {} held by each other",
+            list(&self.trains),
+            list(&self.blocks)
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrainStop {
+    pub name: String,
+    /// Trains driving in this direction stop here
+    pub direction: Direction,
+    /// Number of trains that stopped here so far
+    pub arrivals: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vehicle {
+    Locomotive {
+        burner: Burner,
+    },
+    CargoWagon {
+        items: BTreeMap<(Item, Quality), u32>,
+    },
+    FluidWagon {
+        fluidbox: FluidBox,
+    },
+}
+
+#[allow(dead_code)]
+impl Vehicle {
+    pub const fn locomotive() -> Self {
+        Self::Locomotive {
+            burner: Burner::new(),
+        }
+    }
+
+    pub const fn cargo_wagon() -> Self {
+        Self::CargoWagon {
+            items: BTreeMap::new(),
+        }
+    }
+
+    pub const fn fluid_wagon() -> Self {
+        Self::FluidWagon {
+            fluidbox: FluidBox::new(FLUID_WAGON_CAPACITY),
+        }
+    }
+
+    pub const fn weight(&self) -> f64 {
+        match self {
+            Self::Locomotive { .. } => 2_000.0,
+            Self::CargoWagon { .. } | Self::FluidWagon { .. } => 1_000.0,
+        }
+    }
+
+    pub const fn braking_force(&self) -> f64 {
+        match self {
+            Self::Locomotive { .. } => 10.0,
+            Self::CargoWagon { .. } | Self::FluidWagon { .. } => 3.0,
+        }
+    }
+
+    /// Number of items in a cargo wagon
+    pub fn item_count(&self) -> u32 {
+        match self {
+            Self::CargoWagon { items } => items.values().sum(),
+            _ => 0,
+        }
+    }
+
+    /// Puts one item into a cargo wagon, or into the fuel slot of a locomotive if it
+    /// is fuel worth `fuel_value` joules. Returns whether it was accepted.
+    pub fn insert_item(&mut self, item: Item, quality: Quality, fuel_value: Option<f64>) -> bool {
+        match self {
+            Self::CargoWagon { items } => {
+                if items.values().sum::<u32>() >= CARGO_WAGON_CAPACITY {
+                    return false;
+                }
+                *items.entry((item, quality)).or_default() += 1;
+                true
+            }
+            Self::Locomotive { burner } => {
+                fuel_value.is_some_and(|fuel_value| burner.insert_fuel(item, 1, fuel_value) == 1)
+            }
+            Self::FluidWagon { .. } => false,
+        }
+    }
+
+    /// Whether a wagon cannot take any more. Locomotives are never full.
+    pub fn is_full(&self) -> bool {
+        match self {
+            Self::CargoWagon { .. } => self.item_count() >= CARGO_WAGON_CAPACITY,
+            Self::FluidWagon { fluidbox } => fluidbox.free() <= EPSILON,
+            Self::Locomotive { .. } => false,
+        }
+    }
+
+    /// Whether a wagon carries nothing. Locomotives are always empty.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::CargoWagon { items } => items.is_empty(),
+            Self::FluidWagon { fluidbox } => fluidbox.amount <= EPSILON,
+            Self::Locomotive { .. } => true,
+        }
+    }
+
+    /// Kinds of items in a cargo wagon, the lowest item id first
+    pub fn item_kinds(&self) -> Vec<(Item, Quality)> {
+        match self {
+            Self::CargoWagon { items } => items.keys().copied().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether [`Self::insert_item`] would take the item now
+    pub fn accepts_item(&self, item: Item, fuel_value: Option<f64>) -> bool {
+        match self {
+            Self::CargoWagon { .. } => !self.is_full(),
+            Self::Locomotive { burner } => fuel_value.is_some() && burner.accepts_fuel(item),
+            Self::FluidWagon { .. } => false,
+        }
+    }
+
+    /// Takes one of `item` in `quality` out of a cargo wagon. Returns whether there was one.
+    pub fn take(&mut self, item: Item, quality: Quality) -> bool {
+        let Self::CargoWagon { items } = self else {
+            return false;
+        };
+        let Some(count) = items.get_mut(&(item, quality)) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            items.remove(&(item, quality));
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrainState {
+    /// No schedule to follow
+    Idle,
+    /// On the way to the current schedule record's station
+    Driving,
+    /// No station of the current record can be reached
+    NoPath,
+    /// Stopped at the current record's station since the given tick
+    WaitingAtStation { since: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Train {
+    /// Front to back
+    pub vehicles: Vec<Vehicle>,
+    pub schedule: Vec<ScheduleRecord>,
+    /// Index of the current schedule record
+    pub current: usize,
+    pub interrupts: Vec<Interrupt>,
+    /// Records of the interrupt the train is following, before the schedule
+    pub temporary: VecDeque<ScheduleRecord>,
+    /// Tick the cargo last changed at a station
+    pub last_activity: u64,
+    /// Hash of the cargo as of `last_activity`
+    pub cargo_hash: u64,
+    pub state: TrainState,
+    /// Tiles per tick
+    pub speed: f64,
+    /// Rail pieces the train stands on, front first
+    pub occupied: VecDeque<TrackPiece>,
+    /// How far the front has driven into the first occupied piece
+    pub front_progress: f64,
+    /// Pieces still to drive through to reach `destination`
+    pub route: VecDeque<TrackPiece>,
+    pub destination: Option<TrackPiece>,
+    /// Number of stations reached so far
+    pub trips: u64,
+    /// Blocks reserved at signals, and whether the train has entered them yet
+    pub reserved: Vec<(usize, bool)>,
+    /// Blocks the train is stopped in front of because other trains hold them
+    pub waiting_for: Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl Train {
+    /// A train of `vehicles`, front to back. Every train has at least one vehicle, so
+    /// its weight is never zero.
+    ///
+    /// # Panics
+    ///
+    /// If `vehicles` is empty.
+    pub const fn new(vehicles: Vec<Vehicle>) -> Self {
+        assert!(!vehicles.is_empty(), "a train needs at least one vehicle");
+        Self {
+            vehicles,
+            schedule: Vec::new(),
+            current: 0,
+            interrupts: Vec::new(),
+            temporary: VecDeque::new(),
+            last_activity: 0,
+            cargo_hash: 0,
+            state: TrainState::Idle,
+            speed: 0.0,
+            occupied: VecDeque::new(),
+            front_progress: 0.0,
+            route: VecDeque::new(),
+            destination: None,
+            trips: 0,
+            reserved: Vec::new(),
+            waiting_for: Vec::new(),
+        }
+    }
+
+    /// Adds `station` to the end of the schedule, waiting there for `wait_ticks`
+    pub fn stop(self, station: &str, wait_ticks: u64) -> Self {
+        self.record(ScheduleRecord::new(station).and(WaitCondition::Time(wait_ticks)))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn length(&self) -> f64 {
+        self.vehicles.len() as f64 * VEHICLE_LENGTH
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.vehicles.iter().map(Vehicle::weight).sum()
+    }
+
+    /// Speed change per tick at full power
+    pub fn acceleration(&self) -> f64 {
+        let locomotives = self
+            .vehicles
+            .iter()
+            .filter(|vehicle| matches!(vehicle, Vehicle::Locomotive { .. }))
+            .count();
+        #[allow(clippy::cast_precision_loss)]
+        let force = locomotives as f64 * LOCOMOTIVE_FORCE;
+        force / self.weight()
+    }
+
+    /// Speed change per tick while braking
+    pub fn deceleration(&self) -> f64 {
+        self.vehicles
+            .iter()
+            .map(Vehicle::braking_force)
+            .sum::<f64>()
+            / self.weight()
+    }
+
+    /// Every occupied piece with the stretch of the train on it, as distances from
+    /// the front
+    pub fn spans(&self, rails: &HashMap<Coordinate, Vec<Rail>>) -> Vec<(TrackPiece, f64, f64)> {
+        let mut spans = Vec::new();
+        let mut start = 0.0;
+        for (index, piece) in self.occupied.iter().enumerate() {
+            let on_piece = if index == 0 {
+                self.front_progress
+            } else {
+                piece_length(rails, *piece)
+            };
+            let end = (start + on_piece).min(self.length());
+            spans.push((*piece, start, end));
+            start = end;
+        }
+        spans
+    }
+
+    /// Hash of what the wagons carry, to notice loading and unloading
+    fn cargo_hash(&self) -> u64 {
+        let mut hasher = Checksum::default();
+        for vehicle in &self.vehicles {
+            match vehicle {
+                Vehicle::CargoWagon { items } => items.hash(&mut hasher),
+                Vehicle::FluidWagon { fluidbox } => fluidbox.amount.to_bits().hash(&mut hasher),
+                Vehicle::Locomotive { .. } => {}
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Restarts the inactivity count if the cargo changed
+    fn note_activity(&mut self, now: u64) {
+        let hash = self.cargo_hash();
+        if hash != self.cargo_hash {
+            self.cargo_hash = hash;
+            self.last_activity = now;
+        }
+    }
+
+    /// Burns fuel in every locomotive for one tick of pulling and returns the
+    /// fraction of full power they could supply
+    fn burn_fuel(&mut self) -> f64 {
+        let mut supplied = 0.0;
+        let mut locomotives = 0.0;
+        for vehicle in &mut self.vehicles {
+            if let Vehicle::Locomotive { burner } = vehicle {
+                supplied += burner.burn(LOCOMOTIVE_POWER);
+                locomotives += 1.0;
+            }
+        }
+        if locomotives > 0.0 {
+            supplied / locomotives
+        } else {
+            0.0
+        }
+    }
+}
+
+fn piece_length(rails: &HashMap<Coordinate, Vec<Rail>>, piece: TrackPiece) -> f64 {
+    rails
+        .get(&piece.coordinate)
+        .and_then(|tile| tile.get(piece.rail))
+        .map_or(1.0, |rail| rail.length())
+}
+
+/// Pieces a train can drive into after leaving `piece`
+fn next_pieces(
+    rails: &HashMap<Coordinate, Vec<Rail>>,
+    piece: TrackPiece,
+) -> impl Iterator<Item = TrackPiece> + '_ {
+    let coordinate = piece.coordinate.neighbor(piece.exit);
+    let entry = piece.exit.opposite();
+    rails
+        .get(&coordinate)
+        .into_iter()
+        .flat_map(|tile| tile.iter().enumerate())
+        .filter_map(move |(rail, kind)| {
+            kind.exit(entry).map(|exit| TrackPiece {
+                coordinate,
+                rail,
+                exit,
+            })
+        })
+}
+
+/// The piece a train came from before entering `piece`, the first one if several
+/// rails lead there
+fn previous_piece(rails: &HashMap<Coordinate, Vec<Rail>>, piece: TrackPiece) -> Option<TrackPiece> {
+    let entry = rails
+        .get(&piece.coordinate)?
+        .get(piece.rail)?
+        .exit(piece.exit)?;
+    let coordinate = piece.coordinate.neighbor(entry);
+    let exit = entry.opposite();
+    rails
+        .get(&coordinate)?
+        .iter()
+        .position(|rail| rail.ends.contains(&exit))
+        .map(|rail| TrackPiece {
+            coordinate,
+            rail,
+            exit,
+        })
+}
+
+/// Cheapest route from `start` to a piece for which `is_goal` holds, excluding
+/// `start` itself. `cost` gives the cost of driving from one piece into the next, or
+/// `None` if a train may not.
+fn find_route(
+    rails: &HashMap<Coordinate, Vec<Rail>>,
+    start: TrackPiece,
+    cost: impl Fn(TrackPiece, TrackPiece) -> Option<f64>,
+    is_goal: impl Fn(TrackPiece) -> bool,
+) -> Option<(TrackPiece, VecDeque<TrackPiece>)> {
+    let mut nodes = vec![start];
+    let mut index_of = HashMap::from([(start, 0)]);
+    let mut distance = vec![0.0];
+    let mut previous: Vec<Option<usize>> = vec![None];
+    let mut done = HashSet::new();
+    // Distances are never negative, so their bit patterns sort like the values.
+    // Ties go to the piece discovered first, which keeps routes deterministic.
+    let mut queue = BinaryHeap::from([Reverse((0.0f64.to_bits(), 0))]);
+    while let Some(Reverse((_, index))) = queue.pop() {
+        if !done.insert(index) {
+            continue;
+        }
+        let piece = nodes[index];
+        if is_goal(piece) {
+            let mut route = VecDeque::new();
+            let mut current = index;
+            while let Some(before) = previous[current] {
+                route.push_front(nodes[current]);
+                current = before;
+            }
+            return Some((piece, route));
+        }
+        for next in next_pieces(rails, piece) {
+            let Some(step) = cost(piece, next) else {
+                continue;
+            };
+            let through = distance[index] + step;
+            let next_index = *index_of.entry(next).or_insert_with(|| {
+                nodes.push(next);
+                distance.push(f64::INFINITY);
+                previous.push(None);
+                nodes.len() - 1
+            });
+            if through < distance[next_index] {
+                distance[next_index] = through;
+                previous[next_index] = Some(index);
+                queue.push(Reverse((through.to_bits(), next_index)));
+            }
+        }
+    }
+    None
+}
+
+/// Root of `index` in a union-find forest
+fn find_root(parent: &mut [usize], mut index: usize) -> usize {
+    while parent[index] != index {
+        parent[index] = parent[parent[index]];
+        index = parent[index];
+    }
+    index
+}
+
+/// What a train found on the rails ahead of it
+#[derive(Debug, Default)]
+struct LookAhead {
+    /// Distance it may drive
+    free: f64,
+    /// Blocks to reserve at the signals it is about to pass
+    reserve: Vec<usize>,
+    waiting_for: Vec<usize>,
+}
+
+/// All rails, signals, train stops and trains of a world
+#[derive(Debug, Default)]
+pub struct RailSystem {
+    pub rails: HashMap<Coordinate, Vec<Rail>>,
+    /// Signals by the tile and the side of it they stand on
+    pub signals: HashMap<(Coordinate, Direction), SignalKind>,
+    pub stops: HashMap<Coordinate, TrainStop>,
+    pub trains: Vec<Train>,
+    /// Block of every rail, by tile and index, rebuilt whenever rails or signals change
+    blocks: HashMap<(Coordinate, usize), usize>,
+    block_count: usize,
+    dirty: bool,
+}
+
+#[allow(dead_code)]
+impl RailSystem {
+    pub fn add_rail(&mut self, coordinate: Coordinate, rail: Rail) {
+        self.rails.entry(coordinate).or_default().push(rail);
+        self.dirty = true;
+    }
+
+    /// Places a signal for trains leaving `coordinate` towards `direction`
+    pub fn add_signal(&mut self, coordinate: Coordinate, direction: Direction, kind: SignalKind) {
+        self.signals.insert((coordinate, direction), kind);
+        self.dirty = true;
+    }
+
+    pub const fn block_count(&self) -> usize {
+        self.block_count
+    }
+
+    pub fn block_of(&self, piece: TrackPiece) -> Option<usize> {
+        self.blocks.get(&(piece.coordinate, piece.rail)).copied()
+    }
+
+    /// Groups rails into blocks: rails on the same tile and rails meeting at a tile
+    /// side without a signal belong together. Blocks are numbered in coordinate order.
+    fn rebuild_blocks(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        let mut pieces: Vec<(Coordinate, usize)> = self
+            .rails
+            .iter()
+            .flat_map(|(coordinate, tile)| (0..tile.len()).map(|rail| (*coordinate, rail)))
+            .collect();
+        pieces.sort_by_key(|(c, rail)| (c.y, c.x, *rail));
+        let index_of: HashMap<(Coordinate, usize), usize> = pieces
+            .iter()
+            .enumerate()
+            .map(|(index, piece)| (*piece, index))
+            .collect();
+        let mut parent: Vec<usize> = (0..pieces.len()).collect();
+        let mut union = |a: usize, b: usize| {
+            let (a, b) = (find_root(&mut parent, a), find_root(&mut parent, b));
+            parent[a.max(b)] = a.min(b);
+        };
+        for (index, &(coordinate, rail)) in pieces.iter().enumerate() {
+            union(index_of[&(coordinate, 0)], index);
+            for side in self.rails[&coordinate][rail].ends {
+                let neighbor = coordinate.neighbor(side);
+                if self.signals.contains_key(&(coordinate, side))
+                    || self.signals.contains_key(&(neighbor, side.opposite()))
+                {
+                    continue;
+                }
+                for (other, kind) in self.rails.get(&neighbor).into_iter().flatten().enumerate() {
+                    if kind.ends.contains(&side.opposite()) {
+                        union(index, index_of[&(neighbor, other)]);
+                    }
+                }
+            }
+        }
+        let mut numbers: HashMap<usize, usize> = HashMap::new();
+        self.blocks.clear();
+        for (index, piece) in pieces.iter().enumerate() {
+            let root = find_root(&mut parent, index);
+            let next = numbers.len();
+            let block = *numbers.entry(root).or_insert(next);
+            self.blocks.insert(*piece, block);
+        }
+        self.block_count = numbers.len();
+    }
+
+    /// The signal a train passes when leaving `piece`
+    fn signal_after(&self, piece: TrackPiece) -> Option<SignalKind> {
+        self.signals.get(&(piece.coordinate, piece.exit)).copied()
+    }
+
+    /// Whether leaving `piece` means passing a signal for the other direction only
+    fn is_one_way_against(&self, piece: TrackPiece) -> bool {
+        self.signal_after(piece).is_none()
+            && self
+                .signals
+                .contains_key(&(piece.coordinate.neighbor(piece.exit), piece.exit.opposite()))
+    }
+
+    /// Trains other than `except` standing in or holding a reservation for `block`
+    fn holders(&self, block: usize, except: usize) -> Vec<usize> {
+        self.trains
+            .iter()
+            .enumerate()
+            .filter(|(index, train)| {
+                *index != except
+                    && (train
+                        .reserved
+                        .iter()
+                        .any(|(reserved, _)| *reserved == block)
+                        || train
+                            .occupied
+                            .iter()
+                            .any(|piece| self.block_of(*piece) == Some(block)))
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    pub fn add_stop(&mut self, coordinate: Coordinate, name: &str, direction: Direction) {
+        self.stops.insert(
+            coordinate,
+            TrainStop {
+                name: name.to_string(),
+                direction,
+                arrivals: 0,
+            },
+        );
+    }
+
+    /// Places `train` with its front at the far edge of `front`, facing `heading`,
+    /// and its vehicles on the rails behind. Returns the train's index, or `None` if
+    /// there is no rail leaving `front` towards `heading` or not enough track behind.
+    pub fn add_train(
+        &mut self,
+        front: Coordinate,
+        heading: Direction,
+        mut train: Train,
+    ) -> Option<usize> {
+        let rail = self
+            .rails
+            .get(&front)?
+            .iter()
+            .position(|rail| rail.ends.contains(&heading))?;
+        let mut piece = TrackPiece {
+            coordinate: front,
+            rail,
+            exit: heading,
+        };
+        train.front_progress = piece_length(&self.rails, piece);
+        train.occupied = VecDeque::from([piece]);
+        let mut covered = train.front_progress;
+        loop {
+            if covered >= train.length() - EPSILON {
+                break;
+            }
+            piece = previous_piece(&self.rails, piece)?;
+            covered += piece_length(&self.rails, piece);
+            train.occupied.push_back(piece);
+        }
+        self.trains.push(train);
+        Some(self.trains.len() - 1)
+    }
+
+    /// Train and vehicle index of the vehicle standing on `coordinate` of a train
+    /// waiting at a station
+    fn vehicle_index_at(&self, coordinate: Coordinate) -> Option<(usize, usize)> {
+        for (train_index, train) in self.trains.iter().enumerate() {
+            if !matches!(train.state, TrainState::WaitingAtStation { .. }) {
+                continue;
+            }
+            let span = train
+                .spans(&self.rails)
+                .into_iter()
+                .find(|(piece, start, end)| piece.coordinate == coordinate && end > start);
+            if let Some((_, start, end)) = span {
+                // The vehicle covering the middle of the tile's stretch
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let index = (f64::midpoint(start, end) / VEHICLE_LENGTH) as usize;
+                return (index < train.vehicles.len()).then_some((train_index, index));
+            }
+        }
+        None
+    }
+
+    /// The vehicle standing on `coordinate` of a train waiting at a station
+    pub fn vehicle_at(&self, coordinate: Coordinate) -> Option<&Vehicle> {
+        let (train, index) = self.vehicle_index_at(coordinate)?;
+        self.trains[train].vehicles.get(index)
+    }
+
+    /// The vehicle standing on `coordinate` of a train waiting at a station, which
+    /// inserters and pumps may load and unload
+    pub fn vehicle_at_mut(&mut self, coordinate: Coordinate) -> Option<&mut Vehicle> {
+        let (train, index) = self.vehicle_index_at(coordinate)?;
+        self.trains[train].vehicles.get_mut(index)
+    }
+
+    /// Pieces occupied by every train except the one at `except`
+    fn occupied_by_others(&self, except: usize) -> HashSet<(Coordinate, usize)> {
+        self.trains
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != except)
+            .flat_map(|(_, train)| train.occupied.iter())
+            .map(|piece| (piece.coordinate, piece.rail))
+            .collect()
+    }
+
+    /// Cost of driving from `from` into `to` for a train that is not in `occupied`
+    fn step_cost(
+        &self,
+        from: TrackPiece,
+        to: TrackPiece,
+        occupied: &HashSet<usize>,
+    ) -> Option<f64> {
+        if self.is_one_way_against(from) {
+            return None;
+        }
+        let mut cost = piece_length(&self.rails, to);
+        if self
+            .stops
+            .get(&to.coordinate)
+            .is_some_and(|stop| stop.direction == to.exit)
+        {
+            cost += TRAIN_STOP_PENALTY;
+        }
+        let block = self.block_of(to);
+        if block != self.block_of(from) && block.is_some_and(|block| occupied.contains(&block)) {
+            cost += OCCUPIED_BLOCK_PENALTY;
+        }
+        Some(cost)
+    }
+
+    /// Finds the route to the station of the train's current schedule record
+    fn plan_route(&mut self, index: usize) {
+        let train = &self.trains[index];
+        let Some(&front) = train.occupied.front() else {
+            return;
+        };
+        let Some(record) = train.current_record() else {
+            return;
+        };
+        let station = &record.station;
+        let is_goal = |piece: TrackPiece| {
+            self.stops
+                .get(&piece.coordinate)
+                .is_some_and(|stop| stop.name == *station && stop.direction == piece.exit)
+        };
+        let occupied: HashSet<usize> = self
+            .occupied_by_others(index)
+            .into_iter()
+            .filter_map(|piece| self.blocks.get(&piece).copied())
+            .collect();
+        // A train standing at its next station is there already
+        let found = if is_goal(front) {
+            Some((front, VecDeque::new()))
+        } else {
+            find_route(
+                &self.rails,
+                front,
+                |from, to| self.step_cost(from, to, &occupied),
+                is_goal,
+            )
+        };
+        let train = &mut self.trains[index];
+        match found {
+            Some((destination, route)) => {
+                train.destination = Some(destination);
+                train.route = route;
+                train.state = TrainState::Driving;
+                // Reservations for a previous route are of no use any more
+                train.reserved.retain(|(_, entered)| *entered);
+            }
+            None => train.state = TrainState::NoPath,
+        }
+    }
+
+    /// Blocks a train needs to pass the signal in front of `route[start]`
+    fn signal_path(&self, train: &Train, start: usize, kind: SignalKind) -> Vec<usize> {
+        let mut blocks: Vec<usize> = self.block_of(train.route[start]).into_iter().collect();
+        if kind == SignalKind::Rail {
+            return blocks;
+        }
+        let pieces = train.route.iter().skip(start);
+        for (previous, next) in pieces.clone().zip(pieces.skip(1)) {
+            let Some(signal) = self.signal_after(*previous) else {
+                continue;
+            };
+            blocks.extend(self.block_of(*next));
+            if signal == SignalKind::Rail {
+                break;
+            }
+        }
+        blocks
+    }
+
+    /// How far the train at `index` may drive, reserving blocks at the signals within
+    /// its braking distance. It stops in front of a signal whose blocks are held by
+    /// other trains and in front of rails other trains stand on.
+    fn look_ahead(&self, index: usize) -> LookAhead {
+        let train = &self.trains[index];
+        let mut ahead = LookAhead::default();
+        let Some(&front) = train.occupied.front() else {
+            return ahead;
+        };
+        ahead.free = piece_length(&self.rails, front) - train.front_progress;
+        if train.state != TrainState::Driving {
+            return ahead;
+        }
+        // With a margin for the distance driven before braking starts
+        let braking = 2.0f64.mul_add(
+            MAX_SPEED,
+            train.speed.powi(2) / (2.0 * train.deceleration()),
+        );
+        let blocked = self.occupied_by_others(index);
+        let mut previous = front;
+        for (position, next) in train.route.iter().enumerate() {
+            if blocked.contains(&(next.coordinate, next.rail)) {
+                ahead.waiting_for = self.block_of(*next).into_iter().collect();
+                break;
+            }
+            if let Some(kind) = self.signal_after(previous)
+                && let Some(block) = self.block_of(*next)
+                && !train
+                    .reserved
+                    .iter()
+                    .any(|(reserved, _)| *reserved == block)
+                && !ahead.reserve.contains(&block)
+            {
+                if ahead.free > braking {
+                    break;
+                }
+                let wanted = self.signal_path(train, position, kind);
+                let held: Vec<usize> = wanted
+                    .iter()
+                    .copied()
+                    .filter(|block| !self.holders(*block, index).is_empty())
+                    .collect();
+                if !held.is_empty() {
+                    ahead.waiting_for = held;
+                    break;
+                }
+                ahead.reserve.extend(wanted);
+            }
+            ahead.free += piece_length(&self.rails, *next);
+            previous = *next;
+        }
+        ahead
+    }
+
+    /// Moves every train by one tick. `signals_at` gives the circuit signals at a
+    /// tile, for the conditions of trains standing there.
+    pub fn tick(&mut self, now: u64, signals_at: impl Fn(Coordinate) -> Signals) {
+        self.rebuild_blocks();
+        for index in 0..self.trains.len() {
+            self.tick_train(index, now, &signals_at);
+        }
+    }
+
+    fn tick_train(&mut self, index: usize, now: u64, signals_at: &impl Fn(Coordinate) -> Signals) {
+        let train = &mut self.trains[index];
+        let signals = train
+            .occupied
+            .front()
+            .map(|piece| signals_at(piece.coordinate))
+            .unwrap_or_default();
+        match train.state {
+            TrainState::WaitingAtStation { .. } => {
+                train.note_activity(now);
+                let done = train.current_record().is_none_or(|record| {
+                    crate::schedule::conditions_hold(&record.conditions, train, now, &signals)
+                });
+                if !done {
+                    return;
+                }
+                train.next_record();
+                train.check_interrupts(now, &signals);
+                train.destination = None;
+                train.state = if train.current_record().is_some() {
+                    TrainState::Driving
+                } else {
+                    TrainState::Idle
+                };
+            }
+            TrainState::Idle | TrainState::NoPath => {
+                if train.check_interrupts(now, &signals) {
+                    train.destination = None;
+                    train.state = TrainState::Driving;
+                } else if train.state == TrainState::Idle {
+                    return;
+                }
+            }
+            TrainState::Driving => {}
+        }
+
+        if train.destination.is_none() {
+            self.plan_route(index);
+        }
+
+        let ahead = self.look_ahead(index);
+        let train = &mut self.trains[index];
+        for block in ahead.reserve {
+            if !train
+                .reserved
+                .iter()
+                .any(|(reserved, _)| *reserved == block)
+            {
+                train.reserved.push((block, false));
+            }
+        }
+        train.waiting_for = ahead.waiting_for;
+        drive(&self.rails, train, ahead.free);
+
+        let inside: HashSet<usize> = train
+            .occupied
+            .iter()
+            .filter_map(|piece| self.blocks.get(&(piece.coordinate, piece.rail)).copied())
+            .collect();
+        for (block, entered) in &mut train.reserved {
+            *entered |= inside.contains(block);
+        }
+        train
+            .reserved
+            .retain(|(block, entered)| !*entered || inside.contains(block));
+
+        if train.state == TrainState::Driving
+            && train.route.is_empty()
+            && train.speed == 0.0
+            && train.occupied.front() == train.destination.as_ref()
+            && let Some(front) = train.destination
+            && train.front_progress >= piece_length(&self.rails, front) - EPSILON
+        {
+            train.state = TrainState::WaitingAtStation { since: now };
+            train.trips += 1;
+            train.last_activity = now;
+            train.cargo_hash = train.cargo_hash();
+            if let Some(stop) = self.stops.get_mut(&front.coordinate) {
+                stop.arrivals += 1;
+            }
+        }
+    }
+
+    /// Groups of trains that wait for blocks held by each other in a cycle
+    pub fn deadlocks(&self) -> Vec<Deadlock> {
+        let waits: Vec<Vec<usize>> = self
+            .trains
+            .iter()
+            .enumerate()
+            .map(|(index, train)| {
+                if train.speed > 0.0 {
+                    return Vec::new();
+                }
+                let mut holders: Vec<usize> = train
+                    .waiting_for
+                    .iter()
+                    .flat_map(|block| self.holders(*block, index))
+                    .collect();
+                holders.sort_unstable();
+                holders.dedup();
+                holders
+            })
+            .collect();
+        // Trains reachable from each train by following who waits for whom
+        let reachable: Vec<HashSet<usize>> = (0..waits.len())
+            .map(|start| {
+                let mut seen = HashSet::new();
+                let mut queue: Vec<usize> = waits[start].clone();
+                while let Some(index) = queue.pop() {
+                    if seen.insert(index) {
+                        queue.extend(&waits[index]);
+                    }
+                }
+                seen
+            })
+            .collect();
+        let mut reported = HashSet::new();
+        let mut deadlocks = Vec::new();
+        for start in 0..waits.len() {
+            if reported.contains(&start) || !reachable[start].contains(&start) {
+                continue;
+            }
+            let trains: Vec<usize> = (0..waits.len())
+                .filter(|other| {
+                    reachable[start].contains(other) && reachable[*other].contains(&start)
+                })
+                .collect();
+            reported.extend(trains.iter().copied());
+            let mut blocks: Vec<usize> = trains
+                .iter()
+                .flat_map(|index| self.trains[*index].waiting_for.iter().copied())
+                .collect();
+            blocks.sort_unstable();
+            blocks.dedup();
+            deadlocks.push(Deadlock { trains, blocks });
+        }
+        deadlocks
+    }
+}
+
+/// Accelerates or brakes `train` and moves it along its route by at most `free`
+fn drive(rails: &HashMap<Coordinate, Vec<Rail>>, train: &mut Train, free: f64) {
+    // Fastest speed that still allows stopping within the free distance
+    let limit = MAX_SPEED.min((2.0 * train.deceleration() * free).sqrt());
+    if train.speed < limit {
+        let power = train.burn_fuel();
+        train.speed = train.acceleration().mul_add(power, train.speed).min(limit);
+    } else {
+        train.speed = limit;
+    }
+    let moved = train.speed.min(free);
+    if moved >= free - EPSILON {
+        train.speed = 0.0;
+    }
+
+    train.front_progress += moved;
+    while let Some(&next) = train.route.front() {
+        let length = piece_length(rails, train.occupied[0]);
+        // A front right at the edge stays on its piece
+        if train.front_progress <= length + EPSILON {
+            break;
+        }
+        train.front_progress = (train.front_progress - length).max(0.0);
+        train.route.pop_front();
+        train.occupied.push_front(next);
+    }
+    // Drop pieces the rear has left
+    let length = train.length();
+    while train.occupied.len() > 1 {
+        let behind_last: f64 = train.front_progress
+            + train
+                .occupied
+                .iter()
+                .skip(1)
+                .take(train.occupied.len() - 2)
+                .map(|piece| piece_length(rails, *piece))
+                .sum::<f64>();
+        if behind_last < length - EPSILON {
+            break;
+        }
+        train.occupied.pop_back();
+    }
+}
+
+impl World {
+    #[allow(dead_code)]
+    pub fn add_rail(&mut self, coordinate: Coordinate, rail: Rail) {
+        self.rails.add_rail(coordinate, rail);
+    }
+
+    #[allow(dead_code)]
+    pub fn add_rail_signal(
+        &mut self,
+        coordinate: Coordinate,
+        direction: Direction,
+        kind: SignalKind,
+    ) {
+        self.rails.add_signal(coordinate, direction, kind);
+    }
+
+    #[allow(dead_code)]
+    pub fn add_train_stop(&mut self, coordinate: Coordinate, name: &str, direction: Direction) {
+        self.rails.add_stop(coordinate, name, direction);
+    }
+
+    #[allow(dead_code)]
+    pub fn add_train(
+        &mut self,
+        front: Coordinate,
+        heading: Direction,
+        train: Train,
+    ) -> Option<usize> {
+        self.rails.add_train(front, heading, train)
+    }
+
+    /// Moves every train, then lets pumps next to stopped fluid wagons fill or empty
+    /// them: a pump facing the wagon fills it, a pump facing away empties it
+    pub fn tick_trains(&mut self) {
+        let circuits = &self.circuits;
+        self.rails.tick(self.ticks, |coordinate| {
+            circuits.signals(Connector::main(coordinate))
+        });
+
+        let mut pumps: Vec<(Coordinate, Direction, f64)> = self
+            .fluids
+            .entities
+            .iter()
+            .filter_map(|(coordinate, entity)| match entity.kind {
+                FluidEntityKind::Pump { direction } => {
+                    Some((*coordinate, direction, entity.kind.pumping_speed()))
+                }
+                _ => None,
+            })
+            .collect();
+        pumps.sort_by_key(|(c, _, _)| (c.y, c.x));
+        for (coordinate, direction, speed) in pumps {
+            if let Some(Vehicle::FluidWagon { fluidbox }) =
+                self.rails.vehicle_at_mut(coordinate.neighbor(direction))
+                && let Some(input) = self.fluids.pump_port(coordinate, direction.opposite())
+                && let Some((Some(fluid), available)) = self.fluids.contents(input)
+            {
+                let moved = fluidbox.insert(fluid, speed.min(available));
+                self.fluids.take(input, fluid, moved);
+            }
+            if let Some(Vehicle::FluidWagon { fluidbox }) = self
+                .rails
+                .vehicle_at_mut(coordinate.neighbor(direction.opposite()))
+                && let Some(output) = self.fluids.pump_port(coordinate, direction)
+                && let Some(fluid) = fluidbox.fluid
+            {
+                let moved = self.fluids.put(output, fluid, speed.min(fluidbox.amount));
+                fluidbox.remove(moved);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
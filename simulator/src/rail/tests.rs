@@ -0,0 +1,461 @@
+use super::*;
+use crate::{
+    BeltType, SingleBelt,
+    fluid::{FluidEntity, FluidEntityKind},
+    inserter::{Inserter, InserterKind},
+    power::{GeneratorKind, PoleKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+const COAL: usize = 9;
+
+/// A clockwise loop of rails from (0, 0) to (20, 4) with curves in the corners,
+/// station "A" heading east on the top and "B" heading west on the bottom
+fn loop_world() -> World {
+    let mut world = World::new();
+    for x in 1..20 {
+        world.add_rail(Coordinate::new(x, 0), Rail::straight(Direction::East));
+        world.add_rail(Coordinate::new(x, 4), Rail::straight(Direction::East));
+    }
+    for y in 1..4 {
+        world.add_rail(Coordinate::new(0, y), Rail::straight(Direction::North));
+        world.add_rail(Coordinate::new(20, y), Rail::straight(Direction::North));
+    }
+    world.add_rail(
+        Coordinate::new(0, 0),
+        Rail::curved(Direction::East, Direction::South),
+    );
+    world.add_rail(
+        Coordinate::new(20, 0),
+        Rail::curved(Direction::West, Direction::South),
+    );
+    world.add_rail(
+        Coordinate::new(20, 4),
+        Rail::curved(Direction::North, Direction::West),
+    );
+    world.add_rail(
+        Coordinate::new(0, 4),
+        Rail::curved(Direction::North, Direction::East),
+    );
+    world.add_train_stop(Coordinate::new(15, 0), "A", Direction::East);
+    world.add_train_stop(Coordinate::new(5, 4), "B", Direction::West);
+    world.add_fuel(id(COAL), 4_000_000.0);
+    world
+}
+
+fn fueled_locomotive() -> Vehicle {
+    let mut locomotive = Vehicle::locomotive();
+    assert!(locomotive.insert_item(id(COAL), Quality::Normal, Some(4_000_000.0)));
+    locomotive
+}
+
+#[test]
+fn test_rail_geometry() {
+    let straight = Rail::straight(Direction::North);
+    assert!(!straight.is_curved());
+    assert_eq!(straight.exit(Direction::South), Some(Direction::North));
+    assert_eq!(straight.exit(Direction::East), None);
+    let curve = Rail::curved(Direction::East, Direction::South);
+    assert!(curve.is_curved());
+    assert!((curve.length() - CURVED_RAIL_LENGTH).abs() < 1e-12);
+    assert_eq!(curve.exit(Direction::South), Some(Direction::East));
+}
+
+#[test]
+fn test_train_placed_behind_its_front() {
+    let mut world = loop_world();
+    let train = Train::new(vec![Vehicle::locomotive(), Vehicle::cargo_wagon()]);
+    let index = world
+        .add_train(Coordinate::new(15, 0), Direction::East, train)
+        .expect("placed");
+    let train = &world.rails.trains[index];
+    assert_eq!(train.occupied.len(), 14);
+    assert_eq!(
+        train.occupied.back().map(|piece| piece.coordinate),
+        Some(Coordinate::new(2, 0))
+    );
+    // Not enough track behind a short dead end
+    let mut short = World::new();
+    short.add_rail(Coordinate::new(0, 0), Rail::straight(Direction::East));
+    assert_eq!(
+        short.add_train(
+            Coordinate::new(0, 0),
+            Direction::East,
+            Train::new(vec![Vehicle::locomotive()])
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_train_drives_its_schedule() {
+    let mut world = loop_world();
+    let train = Train::new(vec![fueled_locomotive(), Vehicle::cargo_wagon()])
+        .stop("B", 60)
+        .stop("A", 60);
+    world.add_train(Coordinate::new(15, 0), Direction::East, train);
+
+    let mut top_speed: f64 = 0.0;
+    for _ in 0..2_000 {
+        world.tick();
+        top_speed = top_speed.max(world.rails.trains[0].speed);
+        if world.rails.trains[0].trips == 3 {
+            break;
+        }
+    }
+    let train = &world.rails.trains[0];
+    assert_eq!(train.trips, 3);
+    assert_eq!(
+        train.state,
+        TrainState::WaitingAtStation { since: world.ticks }
+    );
+    assert!(top_speed > 0.1 && top_speed <= MAX_SPEED);
+    let stop = |x, y| world.rails.stops[&Coordinate::new(x, y)].arrivals;
+    assert_eq!(stop(5, 4), 2);
+    assert_eq!(stop(15, 0), 1);
+    // Stopped exactly at the far edge of the stop
+    assert_eq!(
+        train.occupied.front().map(|piece| piece.coordinate),
+        Some(Coordinate::new(5, 4))
+    );
+    assert!((train.front_progress - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_train_without_fuel_stays() {
+    let mut world = loop_world();
+    let train = Train::new(vec![Vehicle::locomotive()]).stop("B", 0);
+    world.add_train(Coordinate::new(15, 0), Direction::East, train);
+    for _ in 0..100 {
+        world.tick();
+    }
+    let train = &world.rails.trains[0];
+    assert_eq!(train.state, TrainState::Driving);
+    assert!(train.speed.abs() < 1e-12);
+    assert_eq!(
+        train.occupied.front().map(|piece| piece.coordinate),
+        Some(Coordinate::new(15, 0))
+    );
+}
+
+#[test]
+fn test_no_path_to_unknown_station() {
+    let mut world = loop_world();
+    let train = Train::new(vec![fueled_locomotive()]).stop("C", 0);
+    world.add_train(Coordinate::new(15, 0), Direction::East, train);
+    world.tick();
+    assert_eq!(world.rails.trains[0].state, TrainState::NoPath);
+}
+
+#[test]
+fn test_trains_queue_behind_each_other() {
+    let mut world = loop_world();
+    // The first train has nowhere to go and blocks the way to "A"
+    world.add_train(
+        Coordinate::new(10, 0),
+        Direction::East,
+        Train::new(vec![Vehicle::locomotive()]),
+    );
+    world.add_train(
+        Coordinate::new(8, 4),
+        Direction::West,
+        Train::new(vec![fueled_locomotive()]).stop("A", 0),
+    );
+    for _ in 0..1_000 {
+        world.tick();
+        let first: HashSet<_> = world.rails.trains[0].occupied.iter().collect();
+        assert!(
+            world.rails.trains[1]
+                .occupied
+                .iter()
+                .all(|piece| !first.contains(piece))
+        );
+    }
+    let waiting = &world.rails.trains[1];
+    assert_eq!(waiting.trips, 0);
+    assert!(waiting.speed.abs() < 1e-12);
+    // Right behind the last tile of the first train
+    assert_eq!(
+        waiting.occupied.front().map(|piece| piece.coordinate),
+        Some(Coordinate::new(3, 0))
+    );
+}
+
+#[test]
+fn test_inserters_load_and_unload_stopped_wagons() {
+    let mut world = loop_world();
+    let train = Train::new(vec![fueled_locomotive(), Vehicle::cargo_wagon()]).stop("A", 10_000);
+    world.add_train(Coordinate::new(15, 0), Direction::East, train);
+    // The wagon covers x = 2 to 8
+    let mut belt = SingleBelt::new(Coordinate::new(4, 2), BeltType::Regular, None, None);
+    belt.left_lane.items[0] = Some((id(1), 0));
+    belt.left_lane.items[1] = Some((id(1), 64));
+    world.add_belt(belt);
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(6, 2),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    world.add_inserter(
+        Coordinate::new(4, 1),
+        Inserter::new(InserterKind::Fast, Direction::North),
+    );
+    world.add_inserter(
+        Coordinate::new(6, 1),
+        Inserter::new(InserterKind::Fast, Direction::South),
+    );
+    world.power.add_pole(Coordinate::new(5, 3), PoleKind::Small);
+    for x in 3..8 {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, 5), GeneratorKind::SolarPanel);
+    }
+    for _ in 0..100 {
+        world.tick();
+    }
+    // Everything loaded went straight out again
+    assert_eq!(world.inserters[&Coordinate::new(4, 1)].moved, 2);
+    assert_eq!(world.inserters[&Coordinate::new(6, 1)].moved, 2);
+    assert_eq!(world.rails.trains[0].vehicles[1].item_count(), 0);
+}
+
+#[test]
+fn test_only_trains_at_a_station_can_be_loaded() {
+    let mut world = loop_world();
+    // Out of fuel on its way to "B", standing still on the open track
+    let train = Train::new(vec![Vehicle::locomotive(), Vehicle::cargo_wagon()]).stop("B", 0);
+    world.add_train(Coordinate::new(15, 0), Direction::East, train);
+    world.tick();
+    assert!(world.rails.trains[0].speed.abs() < 1e-12);
+    assert!(world.rails.vehicle_at(Coordinate::new(5, 0)).is_none());
+
+    world.rails.trains[0].state = TrainState::WaitingAtStation { since: 0 };
+    assert!(matches!(
+        world.rails.vehicle_at(Coordinate::new(5, 0)),
+        Some(Vehicle::CargoWagon { .. })
+    ));
+}
+
+#[test]
+#[should_panic(expected = "a train needs at least one vehicle")]
+fn test_train_without_vehicles_is_rejected() {
+    let _ = Train::new(Vec::new());
+}
+
+#[test]
+fn test_pumps_fill_fluid_wagons() {
+    let mut world = loop_world();
+    let train = Train::new(vec![fueled_locomotive(), Vehicle::fluid_wagon()]).stop("A", 10_000);
+    world.add_train(Coordinate::new(15, 0), Direction::East, train);
+    let mut tank = FluidEntity::new(FluidEntityKind::StorageTank);
+    tank.fluidbox.insert(id(3), 1_000.0);
+    world.add_fluid_entity(Coordinate::new(5, 2), tank);
+    world.add_fluid_entity(
+        Coordinate::new(5, 1),
+        FluidEntity::new(FluidEntityKind::Pump {
+            direction: Direction::North,
+        }),
+    );
+    for _ in 0..10 {
+        world.tick();
+    }
+    let Vehicle::FluidWagon { fluidbox } = &world.rails.trains[0].vehicles[1] else {
+        panic!("not a fluid wagon");
+    };
+    assert_eq!(fluidbox.fluid, Some(id(3)));
+    assert!((fluidbox.amount - 200.0).abs() < 1e-6);
+}
+
+/// Two lines crossing at (0, 0) with rail signals on both sides of the crossing
+fn crossing_world() -> World {
+    let mut world = World::new();
+    for i in -12..=20 {
+        world.add_rail(Coordinate::new(i, 0), Rail::straight(Direction::East));
+    }
+    for i in -12..=20 {
+        world.add_rail(Coordinate::new(0, i), Rail::straight(Direction::South));
+    }
+    for (coordinate, direction) in [
+        (Coordinate::new(-1, 0), Direction::East),
+        (Coordinate::new(0, 0), Direction::East),
+        (Coordinate::new(0, -1), Direction::South),
+        (Coordinate::new(0, 0), Direction::South),
+    ] {
+        world.add_rail_signal(coordinate, direction, SignalKind::Rail);
+    }
+    world.add_train_stop(Coordinate::new(15, 0), "East", Direction::East);
+    world.add_train_stop(Coordinate::new(0, 15), "South", Direction::South);
+    world
+}
+
+#[test]
+fn test_trains_take_turns_at_a_crossing() {
+    let mut world = crossing_world();
+    world.add_train(
+        Coordinate::new(-2, 0),
+        Direction::East,
+        Train::new(vec![fueled_locomotive()]).stop("East", 10_000),
+    );
+    world.add_train(
+        Coordinate::new(0, -2),
+        Direction::South,
+        Train::new(vec![fueled_locomotive()]).stop("South", 10_000),
+    );
+    let crossing = TrackPiece {
+        coordinate: Coordinate::new(0, 0),
+        rail: 0,
+        exit: Direction::East,
+    };
+    let mut waited = false;
+    for _ in 0..600 {
+        world.tick();
+        let on_crossing = world
+            .rails
+            .trains
+            .iter()
+            .filter(|train| {
+                train
+                    .occupied
+                    .iter()
+                    .any(|piece| piece.coordinate == crossing.coordinate)
+            })
+            .count();
+        assert!(on_crossing <= 1);
+        waited |= world.rails.trains.iter().any(|train| {
+            train.waiting_for
+                == world
+                    .rails
+                    .block_of(crossing)
+                    .into_iter()
+                    .collect::<Vec<_>>()
+        });
+    }
+    // West, north, the crossing, east and south
+    assert_eq!(world.rails.block_count(), 5);
+    assert!(waited);
+    assert!(world.rails.trains.iter().all(|train| train.trips == 1));
+    // Both left the crossing behind
+    let crossing_block = world.rails.block_of(crossing).expect("block");
+    assert!(world.rails.holders(crossing_block, usize::MAX).is_empty());
+}
+
+/// A straight line with a signal of `kind` in front of x = 10 and a rail signal in
+/// front of x = 20, a train standing behind it and one coming from the west
+fn signal_line(kind: SignalKind) -> World {
+    let mut world = World::new();
+    for x in -10..=40 {
+        world.add_rail(Coordinate::new(x, 0), Rail::straight(Direction::East));
+    }
+    world.add_rail_signal(Coordinate::new(9, 0), Direction::East, kind);
+    world.add_rail_signal(Coordinate::new(19, 0), Direction::East, SignalKind::Rail);
+    world.add_train_stop(Coordinate::new(40, 0), "End", Direction::East);
+    world.add_train(
+        Coordinate::new(30, 0),
+        Direction::East,
+        Train::new(vec![Vehicle::locomotive()]),
+    );
+    world.add_train(
+        Coordinate::new(5, 0),
+        Direction::East,
+        Train::new(vec![fueled_locomotive()]).stop("End", 0),
+    );
+    for _ in 0..600 {
+        world.tick();
+    }
+    world
+}
+
+#[test]
+fn test_rail_signal_lets_train_into_free_block() {
+    let world = signal_line(SignalKind::Rail);
+    let train = &world.rails.trains[1];
+    assert!(train.speed.abs() < 1e-12);
+    assert_eq!(
+        train.occupied.front().map(|piece| piece.coordinate),
+        Some(Coordinate::new(19, 0))
+    );
+    assert_eq!(train.waiting_for, vec![2]);
+}
+
+#[test]
+fn test_chain_signal_waits_for_block_behind_next_signal() {
+    let world = signal_line(SignalKind::Chain);
+    let train = &world.rails.trains[1];
+    assert_eq!(
+        train.occupied.front().map(|piece| piece.coordinate),
+        Some(Coordinate::new(9, 0))
+    );
+    assert_eq!(train.waiting_for, vec![2]);
+    assert!(train.reserved.is_empty());
+}
+
+#[test]
+fn test_signal_on_one_side_makes_rails_one_way() {
+    let mut world = World::new();
+    for x in 0..=30 {
+        world.add_rail(Coordinate::new(x, 0), Rail::straight(Direction::East));
+    }
+    world.add_rail_signal(Coordinate::new(10, 0), Direction::East, SignalKind::Rail);
+    world.add_train_stop(Coordinate::new(2, 0), "West", Direction::West);
+    world.add_train_stop(Coordinate::new(25, 0), "East", Direction::East);
+    world.add_train(
+        Coordinate::new(15, 0),
+        Direction::West,
+        Train::new(vec![fueled_locomotive()]).stop("West", 0),
+    );
+    world.add_train(
+        Coordinate::new(8, 0),
+        Direction::East,
+        Train::new(vec![fueled_locomotive()]).stop("East", 0),
+    );
+    world.tick();
+    assert_eq!(world.rails.trains[0].state, TrainState::NoPath);
+    assert_eq!(world.rails.trains[1].state, TrainState::Driving);
+}
+
+#[test]
+fn test_deadlock_is_reported() {
+    let mut world = loop_world();
+    world.add_rail_signal(Coordinate::new(10, 0), Direction::East, SignalKind::Rail);
+    world.add_rail_signal(Coordinate::new(10, 4), Direction::West, SignalKind::Rail);
+    // Each train stands in the block the other one needs
+    world.add_train(
+        Coordinate::new(10, 0),
+        Direction::East,
+        Train::new(vec![fueled_locomotive()]).stop("A", 0),
+    );
+    world.add_train(
+        Coordinate::new(10, 4),
+        Direction::West,
+        Train::new(vec![fueled_locomotive()]).stop("B", 0),
+    );
+    for _ in 0..100 {
+        world.tick();
+    }
+    assert_eq!(world.rails.block_count(), 2);
+    let deadlocks = world.rails.deadlocks();
+    assert_eq!(
+        deadlocks,
+        vec![Deadlock {
+            trains: vec![0, 1],
+            blocks: vec![0, 1],
+        }]
+    );
+    assert_eq!(
+        deadlocks[0].to_string(),
+        "trains 0, 1 wait for blocks 0, 1 held by each other"
+    );
+}
+
+#[test]
+fn test_no_deadlock_while_trains_queue() {
+    let mut world = signal_line(SignalKind::Rail);
+    world.tick();
+    assert!(world.rails.deadlocks().is_empty());
+}
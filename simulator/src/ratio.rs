@@ -0,0 +1,399 @@
+//! Static production ratios, computed without running the simulation.
+//!
+//! Given a set of recipes, the machines that craft them and target rates, the
+//! calculator finds how many crafts per second each recipe needs. Materials no
+//! recipe makes are raw inputs, and it solves the linear program
+//!
+//! ```text
+//! minimize   raw inputs consumed, weighted by their cost, then machines used
+//! subject to net output of every craftable material >= its target rate
+//! ```
+//!
+//! so recipes with byproducts are balanced against each other and surplus is only
+//! made where using it up would cost more. Craft durations are rounded up to whole
+//! ticks, the same as in the simulator.
+
+use std::{collections::BTreeMap, fmt};
+
+use super::{
+    BeltType, Item, TICKS_PER_SECOND,
+    crafting::Recipe,
+    fluid::Fluid,
+    modules::{Effect, Module, modules_effect},
+};
+
+/// Tolerance of the simplex method
+const EPSILON: f64 = 1e-9;
+
+/// Cost of a machine working for one second, relative to one unit of a raw input,
+/// so machines only decide between plans that use the same inputs
+const MACHINE_COST: f64 = 1e-4;
+
+/// Anything a recipe consumes or produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(dead_code)]
+pub enum Material {
+    Item(Item),
+    Fluid(Fluid),
+}
+
+/// The machine every craft of one recipe runs in
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineSetup {
+    pub crafting_speed: f64,
+    /// Power drawn while crafting in watts
+    pub energy_usage: f64,
+    pub modules: Vec<Module>,
+    /// Effect of beacons on each machine, on top of its own modules
+    pub beacons: Effect,
+}
+
+#[allow(dead_code)]
+impl MachineSetup {
+    pub const fn new(crafting_speed: f64, energy_usage: f64) -> Self {
+        Self {
+            crafting_speed,
+            energy_usage,
+            modules: Vec::new(),
+            beacons: Effect::NONE,
+        }
+    }
+
+    pub fn with_modules(mut self, modules: Vec<Module>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    pub const fn with_beacons(mut self, effect: Effect) -> Self {
+        self.beacons = effect;
+        self
+    }
+
+    fn effect(&self) -> Effect {
+        (modules_effect(&self.modules) + self.beacons).clamped()
+    }
+
+    /// Seconds one craft of `recipe` takes, in whole ticks
+    fn craft_seconds(&self, recipe: &Recipe) -> f64 {
+        let speed = self.crafting_speed * self.effect().speed_multiplier();
+        (recipe.energy / speed)
+            .mul_add(TICKS_PER_SECOND, -EPSILON)
+            .ceil()
+            / TICKS_PER_SECOND
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipePlan {
+    pub name: String,
+    pub crafts_per_second: f64,
+    /// Machines needed, as a fraction: 2.5 means two busy and one half busy machine
+    pub machines: f64,
+    /// Modules in all of the machines, counting whole machines
+    pub modules: usize,
+    /// Power drawn by all of the machines, with idle machines drawing their drain
+    pub power: f64,
+}
+
+/// Rate of a material through the factory and the belt lanes it needs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flow {
+    pub material: Material,
+    /// Items or fluid units per second
+    pub rate: f64,
+    /// Lanes of the chosen belt type, 0 for fluids
+    pub lanes: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    /// Recipes in the order they were added, leaving out those not needed
+    pub recipes: Vec<RecipePlan>,
+    /// Raw materials consumed per second
+    pub inputs: Vec<Flow>,
+    /// Everything produced per second, including the targets
+    pub outputs: Vec<Flow>,
+    /// Production per second beyond the targets and what other recipes consume
+    pub surplus: Vec<Flow>,
+}
+
+#[allow(dead_code)]
+impl Plan {
+    pub fn power(&self) -> f64 {
+        self.recipes.iter().map(|recipe| recipe.power).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RatioError {
+    /// No recipe produces this target
+    NoRecipe(Material),
+    /// The recipes cannot reach the targets, e.g. because they only turn a material
+    /// into less of itself
+    Infeasible,
+}
+
+impl fmt::Display for RatioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoRecipe(material) => write!(f, "no recipe produces {material:?}"),
+            Self::Infeasible => write!(f, "the recipes cannot reach the targets"),
+        }
+    }
+}
+
+/// Recipes with their machines and the rates to reach
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RatioProblem {
+    pub recipes: Vec<(Recipe, MachineSetup)>,
+    pub targets: Vec<(Material, f64)>,
+    /// Cost per unit of raw inputs, 1 unless given
+    pub input_costs: Vec<(Material, f64)>,
+    /// Items per belt slot on stacked belts, 1 unless given
+    pub belt_stack_height: Option<u8>,
+}
+
+#[allow(dead_code)]
+impl RatioProblem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn recipe(mut self, recipe: Recipe, machine: MachineSetup) -> Self {
+        self.recipes.push((recipe, machine));
+        self
+    }
+
+    /// Asks for `rate` of `material` per second
+    pub fn target(mut self, material: Material, rate: f64) -> Self {
+        self.targets.push((material, rate));
+        self
+    }
+
+    /// Sets the cost of one unit of a raw input, e.g. 0 for water that is free to pump
+    pub fn input_cost(mut self, material: Material, cost: f64) -> Self {
+        self.input_costs.push((material, cost));
+        self
+    }
+
+    /// Counts belt lanes for belts stacked `height` items high
+    pub const fn belt_stack_height(mut self, height: u8) -> Self {
+        self.belt_stack_height = Some(height);
+        self
+    }
+
+    /// Whether any recipe makes `material`
+    fn produces(&self, material: Material) -> bool {
+        self.recipes.iter().any(|(recipe, _)| match material {
+            Material::Item(item) => recipe.item_products.iter().any(|(p, _)| *p == item),
+            Material::Fluid(fluid) => recipe.fluid_products.iter().any(|(p, _)| *p == fluid),
+        })
+    }
+
+    fn cost(&self, material: Material) -> f64 {
+        self.input_costs
+            .iter()
+            .find(|(m, _)| *m == material)
+            .map_or(1.0, |(_, cost)| *cost)
+    }
+
+    /// What one craft of recipe `index` with net amounts `net` costs in raw inputs
+    /// and machine time
+    fn craft_cost(&self, index: usize, net: &BTreeMap<Material, f64>) -> f64 {
+        let (recipe, machine) = &self.recipes[index];
+        let inputs: f64 = net
+            .iter()
+            .filter(|(m, _)| !self.produces(**m))
+            .map(|(m, amount)| -amount * self.cost(*m))
+            .sum();
+        MACHINE_COST.mul_add(machine.craft_seconds(recipe), inputs.max(0.0))
+    }
+
+    /// Net amount of every material per craft of recipe `index`, with productivity
+    fn net_per_craft(&self, index: usize) -> BTreeMap<Material, f64> {
+        let (recipe, machine) = &self.recipes[index];
+        let bonus = 1.0 + machine.effect().productivity;
+        let mut net = BTreeMap::new();
+        for (item, count) in &recipe.item_ingredients {
+            *net.entry(Material::Item(*item)).or_default() -= f64::from(*count);
+        }
+        for (fluid, amount) in &recipe.fluid_ingredients {
+            *net.entry(Material::Fluid(*fluid)).or_default() -= amount;
+        }
+        for (item, count) in &recipe.item_products {
+            *net.entry(Material::Item(*item)).or_default() += f64::from(*count) * bonus;
+        }
+        for (fluid, amount) in &recipe.fluid_products {
+            *net.entry(Material::Fluid(*fluid)).or_default() += amount * bonus;
+        }
+        net
+    }
+
+    /// Finds the crafts per second of every recipe that reach all targets with the
+    /// fewest machines, and what that takes in belts of `belt` type, modules and power
+    pub fn solve(&self, belt: BeltType) -> Result<Plan, RatioError> {
+        let nets: Vec<BTreeMap<Material, f64>> = (0..self.recipes.len())
+            .map(|index| self.net_per_craft(index))
+            .collect();
+        let produces = |material: &Material| self.produces(*material);
+        if let Some((material, _)) = self.targets.iter().find(|(m, _)| !produces(m)) {
+            return Err(RatioError::NoRecipe(*material));
+        }
+
+        // Every craftable material gets a constraint, all others are raw inputs
+        let mut materials: Vec<Material> =
+            nets.iter().flat_map(|net| net.keys().copied()).collect();
+        materials.sort_unstable();
+        materials.dedup();
+        let constrained: Vec<Material> = materials.iter().copied().filter(produces).collect();
+        let target = |material: &Material| -> f64 {
+            self.targets
+                .iter()
+                .filter(|(m, _)| m == material)
+                .map(|(_, rate)| rate)
+                .sum()
+        };
+        let matrix: Vec<Vec<f64>> = constrained
+            .iter()
+            .map(|m| {
+                nets.iter()
+                    .map(|net| net.get(m).copied().unwrap_or(0.0))
+                    .collect()
+            })
+            .collect();
+        let targets: Vec<f64> = constrained.iter().map(target).collect();
+        let costs: Vec<f64> = nets
+            .iter()
+            .enumerate()
+            .map(|(index, net)| self.craft_cost(index, net))
+            .collect();
+        let crafts = minimize_covering(&matrix, &targets, &costs).ok_or(RatioError::Infeasible)?;
+
+        let stack_height = self.belt_stack_height.unwrap_or(1);
+        let lanes = |material: Material, rate: f64| match material {
+            Material::Item(_) => {
+                rate / f64::from(belt.item_throughput_per_second_one_lane(stack_height))
+            }
+            Material::Fluid(_) => 0.0,
+        };
+        let flow = |material: Material, rate: f64| Flow {
+            material,
+            rate,
+            lanes: lanes(material, rate),
+        };
+
+        let mut recipes = Vec::new();
+        let mut made: BTreeMap<Material, f64> = BTreeMap::new();
+        let mut consumed: BTreeMap<Material, f64> = BTreeMap::new();
+        for (index, (recipe, machine)) in self.recipes.iter().enumerate() {
+            let rate = crafts[index];
+            if rate <= EPSILON {
+                continue;
+            }
+            for (material, net) in &nets[index] {
+                let total = if *net > 0.0 {
+                    made.entry(*material).or_default()
+                } else {
+                    consumed.entry(*material).or_default()
+                };
+                *total += net.abs() * rate;
+            }
+            let machines = rate * machine.craft_seconds(recipe);
+            let whole = machines.ceil();
+            let full_load = machine.energy_usage * machine.effect().consumption_multiplier();
+            recipes.push(RecipePlan {
+                name: recipe.name.clone(),
+                crafts_per_second: rate,
+                machines,
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                modules: whole as usize * machine.modules.len(),
+                power: machines
+                    .mul_add(full_load, (whole - machines) * machine.energy_usage / 30.0),
+            });
+        }
+
+        let inputs = materials
+            .iter()
+            .filter(|m| !produces(m))
+            .filter_map(|m| consumed.get(m).map(|rate| flow(*m, *rate)))
+            .collect();
+        let outputs = made.iter().map(|(m, rate)| flow(*m, *rate)).collect();
+        let surplus = constrained
+            .iter()
+            .filter_map(|m| {
+                let extra = made.get(m).copied().unwrap_or(0.0)
+                    - consumed.get(m).copied().unwrap_or(0.0)
+                    - target(m);
+                (extra > 1e-6).then(|| flow(*m, extra))
+            })
+            .collect();
+        Ok(Plan {
+            recipes,
+            inputs,
+            outputs,
+            surplus,
+        })
+    }
+}
+
+/// Minimizes `costs·x` subject to `matrix x >= targets` and `x >= 0`, for `costs >= 0`. Returns `None`
+/// if no `x` satisfies the constraints.
+///
+/// Runs the simplex method on the dual, maximize `targets·y` subject to
+/// `matrixᵀ y <= costs` and `y >= 0`, whose slack variables give a feasible start
+/// because no cost is negative. At the optimum, the objective row under the slack variables holds `x`.
+fn minimize_covering(matrix: &[Vec<f64>], targets: &[f64], costs: &[f64]) -> Option<Vec<f64>> {
+    let m = targets.len();
+    let n = costs.len();
+    // One row per primal variable: m dual variables, n slacks and the right-hand side
+    let width = m + n + 1;
+    let mut rows: Vec<Vec<f64>> = (0..n)
+        .map(|j| {
+            let mut row = vec![0.0; width];
+            for (i, constraint) in matrix.iter().enumerate() {
+                row[i] = constraint[j];
+            }
+            row[m + j] = 1.0;
+            row[width - 1] = costs[j];
+            row
+        })
+        .collect();
+    let mut objective = vec![0.0; width];
+    for (i, target) in targets.iter().enumerate() {
+        objective[i] = -target;
+    }
+    let mut basis: Vec<usize> = (m..m + n).collect();
+
+    // Bland's rule: the lowest entering and leaving indices, so the method never cycles
+    while let Some(entering) = (0..width - 1).find(|&col| objective[col] < -EPSILON) {
+        let leaving = (0..n)
+            .filter(|&row| rows[row][entering] > EPSILON)
+            .min_by(|&r1, &r2| {
+                let ratio1 = rows[r1][width - 1] / rows[r1][entering];
+                let ratio2 = rows[r2][width - 1] / rows[r2][entering];
+                ratio1.total_cmp(&ratio2).then(basis[r1].cmp(&basis[r2]))
+            })?;
+        let pivot = rows[leaving][entering];
+        for value in &mut rows[leaving] {
+            *value /= pivot;
+        }
+        let pivot_row = rows[leaving].clone();
+        for (index, row) in rows.iter_mut().enumerate() {
+            let factor = row[entering];
+            if index != leaving && factor != 0.0 {
+                for (value, p) in row.iter_mut().zip(&pivot_row) {
+                    *value -= factor * p;
+                }
+            }
+        }
+        let factor = objective[entering];
+        for (value, p) in objective.iter_mut().zip(&pivot_row) {
+            *value -= factor * p;
+        }
+        basis[leaving] = entering;
+    }
+    Some(objective[m..m + n].iter().map(|x| x.max(0.0)).collect())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,203 @@
+use super::*;
+use crate::{crafting::CraftingMachine, modules::ModuleKind, quality::Quality};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+fn item(n: usize) -> Material {
+    Material::Item(id(n))
+}
+
+fn fluid(n: usize) -> Material {
+    Material::Fluid(id(n))
+}
+
+fn gear() -> Recipe {
+    Recipe::new("gear", 0.5)
+        .item_ingredient(id(1), 2)
+        .item_product(id(2), 1)
+}
+
+fn assembler() -> MachineSetup {
+    MachineSetup::new(0.75, 150_000.0)
+}
+
+#[test]
+fn test_single_recipe_needs_machines_belts_and_power() {
+    let plan = RatioProblem::new()
+        .recipe(gear(), assembler())
+        .target(item(2), 1.0)
+        .solve(BeltType::Regular)
+        .expect("solvable");
+
+    let gears = &plan.recipes[0];
+    assert!(close(gears.crafts_per_second, 1.0));
+    // A craft takes 40 ticks at speed 0.75
+    assert!(close(gears.machines, 2.0 / 3.0));
+    assert!(close(gears.power, 100_000.0 + 5_000.0 / 3.0));
+    assert_eq!(plan.inputs.len(), 1);
+    assert_eq!(plan.inputs[0].material, item(1));
+    assert!(close(plan.inputs[0].rate, 2.0));
+    assert!(close(plan.inputs[0].lanes, 2.0 / 7.5));
+    assert!(plan.surplus.is_empty());
+}
+
+#[test]
+fn test_chained_recipes() {
+    let cable = Recipe::new("cable", 0.5)
+        .item_ingredient(id(3), 1)
+        .item_product(id(4), 2);
+    let circuit = Recipe::new("circuit", 0.5)
+        .item_ingredient(id(1), 1)
+        .item_ingredient(id(4), 3)
+        .item_product(id(5), 1);
+    let plan = RatioProblem::new()
+        .recipe(cable, MachineSetup::new(1.0, 0.0))
+        .recipe(circuit, MachineSetup::new(1.0, 0.0))
+        .target(item(5), 10.0)
+        .solve(BeltType::Express)
+        .expect("solvable");
+
+    assert!(close(plan.recipes[0].machines, 7.5));
+    assert!(close(plan.recipes[1].machines, 5.0));
+    let input = |material| {
+        plan.inputs
+            .iter()
+            .find(|flow| flow.material == material)
+            .map(|flow| flow.rate)
+    };
+    assert!(close(input(item(1)).unwrap_or(0.0), 10.0));
+    assert!(close(input(item(3)).unwrap_or(0.0), 15.0));
+    let cables = plan.outputs.iter().find(|flow| flow.material == item(4));
+    assert!(cables.is_some_and(|flow| close(flow.lanes, 30.0 / 22.5)));
+}
+
+/// Oil processing into heavy oil, light oil and petroleum gas, and optionally the
+/// recipes that crack heavy into light and light into petroleum
+fn oil(cracking: bool) -> RatioProblem {
+    let refinery = MachineSetup::new(1.0, 420_000.0);
+    let plant = MachineSetup::new(1.0, 210_000.0);
+    let mut problem = RatioProblem::new().recipe(
+        Recipe::new("advanced-oil-processing", 5.0)
+            .fluid_ingredient(id(1), 100.0)
+            .fluid_ingredient(id(2), 50.0)
+            .fluid_product(id(3), 25.0)
+            .fluid_product(id(4), 45.0)
+            .fluid_product(id(5), 55.0),
+        refinery,
+    );
+    if cracking {
+        problem = problem
+            .recipe(
+                Recipe::new("heavy-oil-cracking", 2.0)
+                    .fluid_ingredient(id(3), 40.0)
+                    .fluid_ingredient(id(2), 30.0)
+                    .fluid_product(id(4), 30.0),
+                plant.clone(),
+            )
+            .recipe(
+                Recipe::new("light-oil-cracking", 2.0)
+                    .fluid_ingredient(id(4), 30.0)
+                    .fluid_ingredient(id(2), 30.0)
+                    .fluid_product(id(5), 20.0),
+                plant,
+            );
+    }
+    // Water is free, so only crude oil counts
+    problem.input_cost(fluid(2), 0.0).target(fluid(5), 100.0)
+}
+
+#[test]
+fn test_byproducts_are_cracked_away() {
+    let plan = oil(true).solve(BeltType::Regular).expect("solvable");
+    assert_eq!(plan.recipes.len(), 3);
+    assert!(plan.surplus.is_empty());
+    let petroleum = plan.outputs.iter().find(|flow| flow.material == fluid(5));
+    assert!(petroleum.is_some_and(|flow| close(flow.rate, 100.0) && flow.lanes == 0.0));
+
+    // Without cracking the heavy and light oil pile up
+    let plan = oil(false).solve(BeltType::Regular).expect("solvable");
+    assert!(close(plan.recipes[0].crafts_per_second, 100.0 / 55.0));
+    let surplus: Vec<Material> = plan.surplus.iter().map(|flow| flow.material).collect();
+    assert_eq!(surplus, vec![fluid(3), fluid(4)]);
+    assert!(close(plan.surplus[0].rate, 25.0 * 100.0 / 55.0));
+}
+
+#[test]
+fn test_productivity_modules_reduce_crafts() {
+    let machine = assembler().with_modules(vec![Module::new(ModuleKind::Productivity, 3); 4]);
+    let plan = RatioProblem::new()
+        .recipe(gear(), machine)
+        .target(item(2), 7.0)
+        .solve(BeltType::Regular)
+        .expect("solvable");
+    let gears = &plan.recipes[0];
+    assert!(close(gears.crafts_per_second, 5.0));
+    // Speed 0.75 * 0.4 = 0.3 takes 100 ticks per craft
+    assert!(close(gears.machines, 5.0 * 100.0 / 60.0));
+    assert_eq!(gears.modules, 9 * 4);
+    assert!(close(plan.inputs[0].rate, 10.0));
+    assert!(close(plan.power(), gears.power));
+}
+
+#[test]
+fn test_unreachable_targets() {
+    let problem = RatioProblem::new().recipe(gear(), assembler());
+    assert_eq!(
+        problem
+            .clone()
+            .target(item(9), 1.0)
+            .solve(BeltType::Regular),
+        Err(RatioError::NoRecipe(item(9)))
+    );
+
+    let shrink = Recipe::new("shrink", 1.0)
+        .item_ingredient(id(7), 2)
+        .item_product(id(7), 1);
+    let problem = problem.recipe(shrink, assembler()).target(item(7), 1.0);
+    assert_eq!(
+        problem.solve(BeltType::Regular),
+        Err(RatioError::Infeasible)
+    );
+}
+
+#[test]
+fn test_plan_matches_simulated_machine() {
+    let plan = RatioProblem::new()
+        .recipe(gear(), assembler())
+        .target(item(2), 1.5)
+        .solve(BeltType::Regular)
+        .expect("solvable");
+    let per_machine = plan.recipes[0].crafts_per_second / plan.recipes[0].machines;
+
+    let mut machine = CraftingMachine::new(0.75).with_recipe(gear());
+    machine.insert_item(id(1), Quality::Normal, 100);
+    let mut made = 0;
+    for _ in 0..1200 {
+        machine.tick();
+        made += machine.take_output(id(2), Quality::Normal, 10);
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let expected = (per_machine * 20.0).round() as u32;
+    assert_eq!(made, expected);
+}
+
+#[test]
+fn test_stacked_belts_need_fewer_lanes() {
+    let plan = RatioProblem::new()
+        .recipe(gear(), assembler())
+        .target(item(2), 60.0)
+        .belt_stack_height(4)
+        .solve(BeltType::Turbo)
+        .expect("solvable");
+
+    // 120 plates per second on turbo belts carrying 4 items per slot
+    assert!(close(plan.inputs[0].rate, 120.0));
+    assert!(close(plan.inputs[0].lanes, 1.0));
+}
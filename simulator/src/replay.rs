@@ -0,0 +1,135 @@
+//! Animated replays of a simulation, exported as APNG.
+//!
+//! A [`Recorder`] captures one frame per call to [`Recorder::capture`], usually once
+//! per tick. All frames share the region of the world that was visible when the
+//! recorder was created, so the animation does not jump around.
+
+use super::{Coordinate, World, png, snapshot};
+
+/// Ticks per second of the game, used as the default playback speed
+pub const GAME_TICKS_PER_SECOND: u16 = 60;
+
+/// Collects frames of a world over time
+pub struct Recorder {
+    min: Coordinate,
+    max: Coordinate,
+    frames: Vec<Vec<u8>>,
+    width: u32,
+    height: u32,
+    /// Frames shown per second when played back
+    frames_per_second: u16,
+}
+
+impl Recorder {
+    /// Creates a recorder for the region covered by the belts currently in the world.
+    /// An empty world records a single tile at the origin.
+    pub fn for_world(world: &World) -> Self {
+        let origin = Coordinate::new(0, 0);
+        let (min, max) = world.bounds().unwrap_or((origin, origin));
+        Self::for_region(min, max)
+    }
+
+    /// Creates a recorder for the tiles between the `min` and `max` corners (inclusive)
+    pub const fn for_region(min: Coordinate, max: Coordinate) -> Self {
+        Self {
+            min,
+            max,
+            frames: Vec::new(),
+            width: 0,
+            height: 0,
+            frames_per_second: GAME_TICKS_PER_SECOND,
+        }
+    }
+
+    /// Sets the playback speed. With one frame captured per tick, lower values
+    /// give a slow motion replay which is easier to follow.
+    pub fn with_frames_per_second(mut self, frames_per_second: u16) -> Self {
+        self.frames_per_second = frames_per_second.max(1);
+        self
+    }
+
+    /// Captures the current state of the world as the next frame
+    pub fn capture(&mut self, world: &World) {
+        let scene = snapshot::build_scene_in(world, self.min, self.max);
+        self.width = scene.width;
+        self.height = scene.height;
+        self.frames.push(scene.rasterize());
+    }
+
+    /// Number of frames captured so far
+    #[allow(dead_code)]
+    pub const fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Encodes all captured frames as an endlessly looping APNG.
+    /// Viewers without APNG support show the first frame as a still image.
+    /// Returns `None` if no frame has been captured yet.
+    pub fn to_apng(&self) -> Option<Vec<u8>> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let mut out = png::SIGNATURE.to_vec();
+        png::write_chunk(&mut out, *b"IHDR", &png::header(self.width, self.height));
+
+        let frame_count = u32::try_from(self.frames.len()).unwrap_or(u32::MAX);
+        let mut actl = Vec::with_capacity(8);
+        actl.extend_from_slice(&frame_count.to_be_bytes());
+        // Zero plays means loop forever
+        actl.extend_from_slice(&0_u32.to_be_bytes());
+        png::write_chunk(&mut out, *b"acTL", &actl);
+
+        // fcTL and fdAT chunks share one sequence counter
+        let mut sequence = 0_u32;
+        for (index, frame) in self.frames.iter().enumerate() {
+            png::write_chunk(&mut out, *b"fcTL", &self.frame_control(sequence));
+            sequence += 1;
+
+            let data = png::zlib_stored(&png::scanlines(self.width, frame));
+            if index == 0 {
+                // The first frame doubles as the default image
+                png::write_chunk(&mut out, *b"IDAT", &data);
+            } else {
+                let mut fdat = Vec::with_capacity(data.len() + 4);
+                fdat.extend_from_slice(&sequence.to_be_bytes());
+                fdat.extend_from_slice(&data);
+                png::write_chunk(&mut out, *b"fdAT", &fdat);
+                sequence += 1;
+            }
+        }
+
+        png::write_chunk(&mut out, *b"IEND", &[]);
+        Some(out)
+    }
+
+    fn frame_control(&self, sequence: u32) -> Vec<u8> {
+        let mut fctl = Vec::with_capacity(26);
+        fctl.extend_from_slice(&sequence.to_be_bytes());
+        fctl.extend_from_slice(&self.width.to_be_bytes());
+        fctl.extend_from_slice(&self.height.to_be_bytes());
+        // Every frame covers the full image, so both offsets are zero
+        fctl.extend_from_slice(&0_u32.to_be_bytes());
+        fctl.extend_from_slice(&0_u32.to_be_bytes());
+        // Delay of 1 / frames_per_second seconds
+        fctl.extend_from_slice(&1_u16.to_be_bytes());
+        fctl.extend_from_slice(&self.frames_per_second.to_be_bytes());
+        // Dispose op none, blend op source: each frame replaces the previous one
+        fctl.extend_from_slice(&[0, 0]);
+        fctl
+    }
+}
+
+/// Records the world for `ticks` ticks, capturing the initial state and every tick after it
+#[allow(dead_code)]
+pub fn record(world: &mut World, ticks: usize, frames_per_second: u16) -> Recorder {
+    let mut recorder = Recorder::for_world(world).with_frames_per_second(frames_per_second);
+    recorder.capture(world);
+    for _ in 0..ticks {
+        world.tick();
+        recorder.capture(world);
+    }
+    recorder
+}
+
+#[cfg(test)]
+mod tests;
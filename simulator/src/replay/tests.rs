@@ -0,0 +1,113 @@
+use super::*;
+use crate::{BeltType, SingleBelt};
+use std::num::NonZeroUsize;
+
+fn item(id: usize) -> crate::Item {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+/// Returns the chunk types of a PNG file in order
+fn chunk_types(data: &[u8]) -> Vec<String> {
+    let mut types = Vec::new();
+    let mut offset = png::SIGNATURE.len();
+    while offset + 8 <= data.len() {
+        let len =
+            u32::from_be_bytes(data[offset..offset + 4].try_into().expect("4 bytes")) as usize;
+        types.push(String::from_utf8_lossy(&data[offset + 4..offset + 8]).into_owned());
+        offset += 12 + len;
+    }
+    types
+}
+
+fn two_belt_world() -> World {
+    let mut world = World::new();
+    let coord1 = Coordinate::new(0, 0);
+    let coord2 = Coordinate::new(1, 0);
+    world.add_belt(SingleBelt::new(coord2, BeltType::Regular, None, None));
+    let mut belt = SingleBelt::new(coord1, BeltType::Regular, Some(coord2), Some(coord2));
+    belt.left_lane.items[0] = Some((item(1), 240));
+    world.add_belt(belt);
+    world
+}
+
+#[test]
+fn test_empty_recorder_has_no_animation() {
+    let recorder = Recorder::for_world(&World::new());
+    assert_eq!(recorder.frame_count(), 0);
+    assert!(recorder.to_apng().is_none());
+}
+
+#[test]
+fn test_record_captures_initial_state_and_each_tick() {
+    let mut world = two_belt_world();
+    let recorder = record(&mut world, 5, 10);
+    assert_eq!(recorder.frame_count(), 6);
+}
+
+#[test]
+fn test_apng_chunk_layout() {
+    let mut world = two_belt_world();
+    let apng = record(&mut world, 2, 10)
+        .to_apng()
+        .expect("Frames were captured");
+
+    assert!(apng.starts_with(&png::SIGNATURE));
+    assert_eq!(
+        chunk_types(&apng),
+        vec![
+            "IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "fcTL", "fdAT", "IEND"
+        ]
+    );
+}
+
+#[test]
+fn test_apng_sequence_numbers_and_delay() {
+    let mut world = two_belt_world();
+    let apng = record(&mut world, 1, 10)
+        .to_apng()
+        .expect("Frames were captured");
+
+    // Signature, IHDR (25 bytes) and acTL (20 bytes)
+    let actl = png::SIGNATURE.len() + 25;
+    assert_eq!(
+        apng[actl + 8..actl + 12],
+        2_u32.to_be_bytes(),
+        "frame count"
+    );
+    let first_fctl = actl + 20;
+    assert_eq!(&apng[first_fctl + 4..first_fctl + 8], b"fcTL");
+    let payload = &apng[first_fctl + 8..first_fctl + 8 + 26];
+    assert_eq!(payload[0..4], 0_u32.to_be_bytes());
+    assert_eq!(payload[4..8], 128_u32.to_be_bytes());
+    assert_eq!(payload[20..22], 1_u16.to_be_bytes());
+    assert_eq!(payload[22..24], 10_u16.to_be_bytes());
+}
+
+#[test]
+fn test_frames_change_as_items_move() {
+    let mut world = two_belt_world();
+    let mut recorder = Recorder::for_world(&world);
+    recorder.capture(&world);
+    world.tick();
+    recorder.capture(&world);
+    // A belt with nothing on it does not change between ticks
+    recorder.capture(&world);
+
+    assert_ne!(recorder.frames[0], recorder.frames[1]);
+    assert_eq!(recorder.frames[1], recorder.frames[2]);
+}
+
+#[test]
+fn test_region_is_fixed_at_creation() {
+    let mut world = two_belt_world();
+    let mut recorder = Recorder::for_world(&world);
+    // Belts added later outside of the region are not recorded
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(5, 5),
+        BeltType::Fast,
+        None,
+        None,
+    ));
+    recorder.capture(&world);
+    assert_eq!((recorder.width, recorder.height), (128, 64));
+}
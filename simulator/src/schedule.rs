@@ -0,0 +1,255 @@
+//! Train schedules: the stations a train visits, what it waits for there, and the
+//! interrupts that send it somewhere else.
+//!
+//! Wait conditions are combined like in the game: `and` binds tighter than `or`, so
+//! `a and b or c` waits for both `a` and `b`, or for `c` alone. A record without
+//! conditions is left right away. The conditions of the current record are checked
+//! every tick while the train waits at its station.
+//!
+//! Interrupts are checked every tick while a train has nowhere to go or no path, and
+//! whenever it leaves a station. The first interrupt whose conditions hold sends the
+//! train to its targets before it carries on with the schedule. While a train follows
+//! an interrupt, only interrupts that allow it can take over.
+
+use super::{
+    Item,
+    circuit::{Comparator, Condition, Signals},
+    rail::{Train, TrainState, Vehicle},
+};
+
+/// How a condition combines with the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Logic {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum WaitCondition {
+    /// Ticks since arriving at the station
+    Time(u64),
+    /// Ticks since the cargo last changed at the station
+    Inactivity(u64),
+    /// Every wagon is full, and there is at least one
+    Full,
+    /// Every wagon is empty
+    Empty,
+    /// Number of `item` in all cargo wagons compared with `value`
+    ItemCount {
+        item: Item,
+        comparator: Comparator,
+        value: i32,
+    },
+    /// Fuel items of the locomotive with the least fuel compared with `value`
+    Fuel { comparator: Comparator, value: i32 },
+    /// Condition on the circuit network of the train stop the train stands at
+    Circuit(Condition),
+    /// The train has no path to its destination
+    NoPath,
+}
+
+impl WaitCondition {
+    /// Whether the condition holds for `train` at tick `now`, with `signals` on the
+    /// circuit network where it stands
+    pub fn holds(self, train: &Train, now: u64, signals: &Signals) -> bool {
+        let count = |value: u32| i32::try_from(value).unwrap_or(i32::MAX);
+        let wagons = || {
+            train
+                .vehicles
+                .iter()
+                .filter(|vehicle| !matches!(vehicle, Vehicle::Locomotive { .. }))
+        };
+        match self {
+            Self::Time(ticks) => match train.state {
+                TrainState::WaitingAtStation { since } => now - since >= ticks,
+                _ => false,
+            },
+            Self::Inactivity(ticks) => {
+                matches!(train.state, TrainState::WaitingAtStation { .. })
+                    && now - train.last_activity >= ticks
+            }
+            Self::Full => wagons().count() > 0 && wagons().all(Vehicle::is_full),
+            Self::Empty => wagons().all(Vehicle::is_empty),
+            Self::ItemCount {
+                item,
+                comparator,
+                value,
+            } => {
+                let total: u32 = train
+                    .vehicles
+                    .iter()
+                    .map(|vehicle| match vehicle {
+                        Vehicle::CargoWagon { items } => items
+                            .iter()
+                            .filter(|((stored, _), _)| *stored == item)
+                            .map(|(_, count)| count)
+                            .sum(),
+                        _ => 0,
+                    })
+                    .sum();
+                comparator.compare(count(total), value)
+            }
+            Self::Fuel { comparator, value } => {
+                let least = train
+                    .vehicles
+                    .iter()
+                    .filter_map(|vehicle| match vehicle {
+                        Vehicle::Locomotive { burner } => Some(burner.fuel_count()),
+                        _ => None,
+                    })
+                    .min()
+                    .unwrap_or(0);
+                comparator.compare(count(least), value)
+            }
+            Self::Circuit(condition) => condition.holds(signals),
+            Self::NoPath => train.state == TrainState::NoPath,
+        }
+    }
+}
+
+/// Whether `conditions` hold, with `and` binding tighter than `or`. No conditions
+/// always hold.
+pub fn conditions_hold(
+    conditions: &[(Logic, WaitCondition)],
+    train: &Train,
+    now: u64,
+    signals: &Signals,
+) -> bool {
+    if conditions.is_empty() {
+        return true;
+    }
+    let mut any = false;
+    let mut all = true;
+    for (index, (logic, condition)) in conditions.iter().enumerate() {
+        if index > 0 && *logic == Logic::Or {
+            any |= all;
+            all = true;
+        }
+        all &= condition.holds(train, now, signals);
+    }
+    any || all
+}
+
+/// A station on a schedule and when to leave it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleRecord {
+    pub station: String,
+    pub conditions: Vec<(Logic, WaitCondition)>,
+}
+
+#[allow(dead_code)]
+impl ScheduleRecord {
+    pub fn new(station: &str) -> Self {
+        Self {
+            station: station.to_string(),
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Adds a condition that has to hold together with the one before it
+    pub fn and(mut self, condition: WaitCondition) -> Self {
+        self.conditions.push((Logic::And, condition));
+        self
+    }
+
+    /// Adds a condition that is enough on its own, together with the ones joined to
+    /// it by `and`
+    pub fn or(mut self, condition: WaitCondition) -> Self {
+        self.conditions.push((Logic::Or, condition));
+        self
+    }
+}
+
+/// Sends a train to other stations when its conditions hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interrupt {
+    pub name: String,
+    pub conditions: Vec<(Logic, WaitCondition)>,
+    /// Stations to visit, in order, before going back to the schedule
+    pub targets: Vec<ScheduleRecord>,
+    /// Whether it may take over from another interrupt the train is following
+    pub inside_interrupts: bool,
+}
+
+#[allow(dead_code)]
+impl Interrupt {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            conditions: Vec::new(),
+            targets: Vec::new(),
+            inside_interrupts: false,
+        }
+    }
+
+    pub fn and(mut self, condition: WaitCondition) -> Self {
+        self.conditions.push((Logic::And, condition));
+        self
+    }
+
+    pub fn or(mut self, condition: WaitCondition) -> Self {
+        self.conditions.push((Logic::Or, condition));
+        self
+    }
+
+    pub fn target(mut self, record: ScheduleRecord) -> Self {
+        self.targets.push(record);
+        self
+    }
+
+    pub const fn with_inside_interrupts(mut self) -> Self {
+        self.inside_interrupts = true;
+        self
+    }
+}
+
+#[allow(dead_code)]
+impl Train {
+    pub fn record(mut self, record: ScheduleRecord) -> Self {
+        self.schedule.push(record);
+        self.state = TrainState::Driving;
+        self
+    }
+
+    pub fn interrupt(mut self, interrupt: Interrupt) -> Self {
+        self.interrupts.push(interrupt);
+        self
+    }
+
+    /// The record the train is going to or waiting at, from an interrupt first
+    pub fn current_record(&self) -> Option<&ScheduleRecord> {
+        self.temporary
+            .front()
+            .or_else(|| self.schedule.get(self.current))
+    }
+
+    /// Moves on from the current record. The schedule only advances once the records
+    /// of an interrupt are done.
+    pub fn next_record(&mut self) {
+        if self.temporary.pop_front().is_none() && !self.schedule.is_empty() {
+            self.current = (self.current + 1) % self.schedule.len();
+        }
+    }
+
+    /// Starts the first interrupt whose conditions hold, if any, and returns whether
+    /// one did
+    pub fn check_interrupts(&mut self, now: u64, signals: &Signals) -> bool {
+        let inside = !self.temporary.is_empty();
+        let targets = self
+            .interrupts
+            .iter()
+            .filter(|interrupt| !interrupt.targets.is_empty())
+            .filter(|interrupt| !inside || interrupt.inside_interrupts)
+            .find(|interrupt| conditions_hold(&interrupt.conditions, self, now, signals))
+            .map(|interrupt| interrupt.targets.clone());
+        let Some(targets) = targets else {
+            return false;
+        };
+        self.temporary = targets.into();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,220 @@
+use super::*;
+use crate::{
+    BeltType, Coordinate, Direction, SingleBelt, World,
+    circuit::{Combinator, Connector, Operand, Signal, SignalSpec, WireColor},
+    inserter::{Inserter, InserterKind},
+    power::{GeneratorKind, PoleKind},
+    quality::Quality,
+    rail::Rail,
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+/// A clockwise loop of rails from (0, 0) to (20, 4) with station "A" heading east
+/// at (15, 0) and "B" heading west at (5, 4)
+fn loop_world() -> World {
+    let mut world = World::new();
+    for x in 1..20 {
+        world.add_rail(Coordinate::new(x, 0), Rail::straight(Direction::East));
+        world.add_rail(Coordinate::new(x, 4), Rail::straight(Direction::East));
+    }
+    for y in 1..4 {
+        world.add_rail(Coordinate::new(0, y), Rail::straight(Direction::North));
+        world.add_rail(Coordinate::new(20, y), Rail::straight(Direction::North));
+    }
+    for (x, y, from, to) in [
+        (0, 0, Direction::East, Direction::South),
+        (20, 0, Direction::West, Direction::South),
+        (20, 4, Direction::North, Direction::West),
+        (0, 4, Direction::North, Direction::East),
+    ] {
+        world.add_rail(Coordinate::new(x, y), Rail::curved(from, to));
+    }
+    world.add_train_stop(Coordinate::new(15, 0), "A", Direction::East);
+    world.add_train_stop(Coordinate::new(5, 4), "B", Direction::West);
+    world
+}
+
+fn fueled_locomotive() -> Vehicle {
+    let mut locomotive = Vehicle::locomotive();
+    assert!(locomotive.insert_item(id(9), Quality::Normal, Some(4_000_000.0)));
+    locomotive
+}
+
+fn state(world: &World) -> TrainState {
+    world.rails.trains[0].state
+}
+
+#[test]
+fn test_and_binds_tighter_than_or() {
+    use Logic::{And, Or};
+    use WaitCondition::{Empty, Full};
+    let train = Train::new(vec![Vehicle::locomotive(), Vehicle::cargo_wagon()]);
+    let holds = |conditions: &[(Logic, WaitCondition)]| {
+        conditions_hold(conditions, &train, 0, &Signals::new())
+    };
+    assert!(holds(&[]));
+    assert!(!holds(&[(And, Full), (And, Empty)]));
+    assert!(holds(&[(And, Full), (Or, Empty)]));
+    assert!(holds(&[(And, Empty), (Or, Full), (And, Full)]));
+    assert!(!holds(&[(And, Full), (Or, Empty), (And, Full)]));
+}
+
+#[test]
+fn test_train_leaves_after_inactivity() {
+    let mut world = loop_world();
+    world.add_train(
+        Coordinate::new(15, 0),
+        Direction::East,
+        Train::new(vec![fueled_locomotive(), Vehicle::cargo_wagon()])
+            .record(ScheduleRecord::new("A").and(WaitCondition::Inactivity(30)))
+            .stop("B", 0),
+    );
+    for _ in 0..30 {
+        world.tick();
+    }
+    assert_eq!(state(&world), TrainState::WaitingAtStation { since: 1 });
+    world.tick();
+    assert_eq!(state(&world), TrainState::Driving);
+    assert_eq!(world.rails.trains[0].current, 1);
+}
+
+#[test]
+fn test_train_waits_for_circuit_condition() {
+    let mut world = loop_world();
+    let constant = Coordinate::new(15, -2);
+    let output = |value| Combinator::Constant(Signals::from([(Signal::Virtual('A'), value)]));
+    world.circuits.add_combinator(constant, output(1));
+    world.circuits.connect(
+        WireColor::Red,
+        Connector::main(constant),
+        Connector::main(Coordinate::new(15, 0)),
+    );
+    let go = Condition::new(
+        SignalSpec::Signal(Signal::Virtual('A')),
+        Comparator::Greater,
+        Operand::Constant(1),
+    );
+    world.add_train(
+        Coordinate::new(15, 0),
+        Direction::East,
+        Train::new(vec![fueled_locomotive()])
+            .record(ScheduleRecord::new("A").and(WaitCondition::Circuit(go)))
+            .stop("B", 0),
+    );
+    for _ in 0..20 {
+        world.tick();
+    }
+    assert_eq!(state(&world), TrainState::WaitingAtStation { since: 1 });
+    world.circuits.add_combinator(constant, output(2));
+    for _ in 0..3 {
+        world.tick();
+    }
+    assert_eq!(state(&world), TrainState::Driving);
+}
+
+#[test]
+fn test_interrupt_sends_loaded_train_to_unload() {
+    let mut world = loop_world();
+    let mut wagon = Vehicle::cargo_wagon();
+    for _ in 0..2 {
+        assert!(wagon.insert_item(id(1), Quality::Normal, None));
+    }
+    let unload = Interrupt::new("unload")
+        .and(WaitCondition::ItemCount {
+            item: id(1),
+            comparator: Comparator::Greater,
+            value: 0,
+        })
+        .target(ScheduleRecord::new("B").and(WaitCondition::Empty));
+    world.add_train(
+        Coordinate::new(15, 0),
+        Direction::East,
+        Train::new(vec![fueled_locomotive(), wagon])
+            .stop("A", 0)
+            .interrupt(unload),
+    );
+    // At "B" the wagon covers x = 12 to 18
+    world.add_inserter(
+        Coordinate::new(14, 5),
+        Inserter::new(InserterKind::Fast, Direction::South),
+    );
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(14, 6),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    world
+        .power
+        .add_pole(Coordinate::new(14, 7), PoleKind::Small);
+    for x in 12..17 {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, 9), GeneratorKind::SolarPanel);
+    }
+
+    for _ in 0..1_500 {
+        world.tick();
+    }
+    let arrivals = |x, y| world.rails.stops[&Coordinate::new(x, y)].arrivals;
+    assert_eq!(arrivals(5, 4), 1);
+    assert!(arrivals(15, 0) >= 2);
+    assert_eq!(world.inserters[&Coordinate::new(14, 5)].moved, 2);
+    let train = &world.rails.trains[0];
+    assert!(train.vehicles[1].is_empty());
+    assert!(train.temporary.is_empty());
+}
+
+#[test]
+fn test_interrupt_on_no_path() {
+    let mut world = loop_world();
+    let fallback = Interrupt::new("fallback")
+        .and(WaitCondition::NoPath)
+        .target(ScheduleRecord::new("A").and(WaitCondition::Time(10_000)));
+    world.add_train(
+        Coordinate::new(8, 4),
+        Direction::West,
+        Train::new(vec![fueled_locomotive()])
+            .stop("C", 0)
+            .interrupt(fallback),
+    );
+    for _ in 0..600 {
+        world.tick();
+    }
+    assert_eq!(world.rails.stops[&Coordinate::new(15, 0)].arrivals, 1);
+    assert!(matches!(state(&world), TrainState::WaitingAtStation { .. }));
+    assert_eq!(
+        world.rails.trains[0]
+            .current_record()
+            .map(|record| record.station.as_str()),
+        Some("A")
+    );
+}
+
+#[test]
+fn test_interrupts_inside_interrupts_only_when_allowed() {
+    let interrupt = Interrupt::new("any")
+        .and(WaitCondition::Empty)
+        .target(ScheduleRecord::new("X"));
+    let mut train = Train::new(vec![Vehicle::locomotive()])
+        .stop("A", 0)
+        .interrupt(interrupt.clone());
+    let signals = Signals::new();
+    assert!(train.check_interrupts(0, &signals));
+    assert_eq!(train.temporary.len(), 1);
+    assert!(!train.check_interrupts(0, &signals));
+
+    train.interrupts = vec![interrupt.with_inside_interrupts()];
+    assert!(train.check_interrupts(0, &signals));
+    // Back to the schedule once the interrupt's records are done
+    train.next_record();
+    assert_eq!(
+        train.current_record().map(|record| record.station.as_str()),
+        Some("A")
+    );
+}
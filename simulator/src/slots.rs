@@ -0,0 +1,82 @@
+//! Item slots of belt lanes.
+//!
+//! A lane has a fixed number of slots, chosen from its length when it is built. Lanes
+//! with at most [`INLINE_SLOTS`] slots, which covers every straight belt, keep them in
+//! place like a plain array, so ticking them never touches the heap. Longer lanes, such
+//! as those of underground belts or modded belts, keep their slots in a `Vec`. Both
+//! dereference to a slice, so code working on lanes does not care which one it has.
+
+use std::ops::{Deref, DerefMut};
+
+/// Slots of a straight belt lane: the four items of a compressed lane and one more
+pub const INLINE_SLOTS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Slots<T> {
+    /// The first `len` of `slots` are in use
+    Inline {
+        slots: [T; INLINE_SLOTS],
+        len: usize,
+    },
+    Heap(Vec<T>),
+}
+
+impl<T: Copy + Default> Slots<T> {
+    /// `capacity` slots holding the default value
+    pub fn new(capacity: usize) -> Self {
+        if capacity <= INLINE_SLOTS {
+            Self::Inline {
+                slots: [T::default(); INLINE_SLOTS],
+                len: capacity,
+            }
+        } else {
+            Self::Heap(vec![T::default(); capacity])
+        }
+    }
+
+    /// Whether the slots live on the heap
+    #[allow(dead_code)]
+    pub const fn is_heap(&self) -> bool {
+        matches!(self, Self::Heap(_))
+    }
+}
+
+impl<T> Deref for Slots<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Self::Inline { slots, len } => &slots[..*len],
+            Self::Heap(slots) => slots,
+        }
+    }
+}
+
+impl<T> DerefMut for Slots<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self {
+            Self::Inline { slots, len } => &mut slots[..*len],
+            Self::Heap(slots) => slots,
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> From<[T; N]> for Slots<T> {
+    fn from(values: [T; N]) -> Self {
+        let mut slots = Self::new(N);
+        slots.copy_from_slice(&values);
+        slots
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Slots<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,28 @@
+use super::*;
+
+#[test]
+fn test_small_capacities_stay_inline() {
+    let slots: Slots<Option<u32>> = Slots::new(3);
+    assert!(!slots.is_heap());
+    assert_eq!(slots.len(), 3);
+    assert!(slots.iter().all(Option::is_none));
+    assert!(!Slots::<u8>::new(INLINE_SLOTS).is_heap());
+}
+
+#[test]
+fn test_large_capacities_go_to_the_heap() {
+    let mut slots: Slots<Option<u32>> = Slots::new(9);
+    assert!(slots.is_heap());
+    assert_eq!(slots.len(), 9);
+    slots[8] = Some(4);
+    assert_eq!(slots.iter().flatten().collect::<Vec<_>>(), vec![&4]);
+}
+
+#[test]
+fn test_from_array() {
+    let slots = Slots::from([Some(1), None, Some(3)]);
+    assert_eq!(&*slots, &[Some(1), None, Some(3)]);
+    let long = Slots::from([1u8; 7]);
+    assert!(long.is_heap());
+    assert_eq!(long.iter().map(|v| u32::from(*v)).sum::<u32>(), 7);
+}
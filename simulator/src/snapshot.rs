@@ -0,0 +1,562 @@
+//! Image snapshots of a [`World`] for visual diffs of layouts.
+//!
+//! A world is first turned into a [`Scene`] of simple shapes, which is then
+//! either written out as SVG or rasterized into a PNG. Both outputs come from the
+//! same scene, so they always agree. Items are placed at sub-tile accuracy: a lane
+//! position of 0 is the edge the belt is entered from, the last position of the lane
+//! (255 on a straight belt) the edge it leaves through. Every other entity is drawn
+//! as a box in the colour of its kind, labelled with what it is, with an arrow for
+//! the entities that face somewhere.
+
+use std::fmt::Write;
+
+use super::{
+    BeltType, Coordinate, Direction, Item, SingleBelt, World, circuit::Combinator,
+    fluid::FluidEntityKind, png, power::GeneratorKind,
+};
+
+/// Size in pixels of one tile
+pub const TILE_SIZE: f32 = 64.0;
+
+/// Number of discrete positions along a straight lane
+const LANE_POSITIONS: f32 = 256.0;
+
+/// Distance of a lane from the centre line of its belt, as a fraction of a tile
+const LANE_OFFSET: f32 = 0.25;
+
+/// Items need 64 positions of spacing, so a radius of a bit less than
+/// 32 positions keeps neighbouring items on a compressed lane from touching
+const ITEM_RADIUS: f32 = TILE_SIZE * 28.0 / LANE_POSITIONS;
+
+const BACKGROUND: Rgb = Rgb(0x2b, 0x2b, 0x2b);
+const LANE_DIVIDER: Rgb = Rgb(0x1a, 0x1a, 0x1a);
+const ARROW: Rgb = Rgb(0x40, 0x40, 0x40);
+
+/// Gap between the box of an entity and the edge of its tile
+const ENTITY_INSET: f32 = TILE_SIZE / 8.0;
+
+const MACHINE: Rgb = Rgb(0x6c, 0x7a, 0x89);
+const INSERTER: Rgb = Rgb(0xa5, 0x8f, 0x4e);
+const DRILL: Rgb = Rgb(0x7d, 0x5a, 0x3c);
+const CHEST: Rgb = Rgb(0x9c, 0x6b, 0x30);
+const PIPE: Rgb = Rgb(0x4a, 0x6f, 0x8a);
+const POLE: Rgb = Rgb(0x8b, 0x5e, 0x3b);
+const POWER: Rgb = Rgb(0x5b, 0x6e, 0x3a);
+const LOGISTICS: Rgb = Rgb(0x6e, 0x4a, 0x7e);
+const CIRCUIT: Rgb = Rgb(0x3a, 0x7a, 0x6e);
+const RAIL: Rgb = Rgb(0x70, 0x70, 0x70);
+
+/// Colours cycled through by item id
+const ITEM_PALETTE: [Rgb; 8] = [
+    Rgb(0xf0, 0xf0, 0xf0),
+    Rgb(0xb8, 0x73, 0x33),
+    Rgb(0x9a, 0xa4, 0xb0),
+    Rgb(0x8e, 0x44, 0xad),
+    Rgb(0x27, 0xae, 0x60),
+    Rgb(0xe6, 0x7e, 0x22),
+    Rgb(0x16, 0xa0, 0x85),
+    Rgb(0xc0, 0x39, 0x2b),
+];
+
+/// An opaque colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// A shape in pixel coordinates, with y pointing down
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Rgb,
+        /// Shown as a tooltip in SVG viewers
+        label: Option<String>,
+    },
+    Circle {
+        cx: f32,
+        cy: f32,
+        r: f32,
+        color: Rgb,
+        /// Shown as a tooltip in SVG viewers
+        label: Option<String>,
+    },
+    Triangle {
+        points: [(f32, f32); 3],
+        color: Rgb,
+    },
+}
+
+/// Everything needed to draw a world, in painting order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene {
+    pub width: u32,
+    pub height: u32,
+    pub shapes: Vec<Shape>,
+}
+
+const fn belt_color(belt_type: BeltType) -> Rgb {
+    match belt_type {
+        BeltType::Regular => Rgb(0xc9, 0xa2, 0x27),
+        BeltType::Fast => Rgb(0xb0, 0x3a, 0x2e),
+        BeltType::Express => Rgb(0x2e, 0x6d, 0xb0),
+        BeltType::Turbo => Rgb(0x3f, 0x8f, 0x4a),
+        BeltType::Modded { .. } => Rgb(0x8e, 0x4a, 0xa8),
+    }
+}
+
+const fn item_color(item: Item) -> Rgb {
+    ITEM_PALETTE[item.get() % ITEM_PALETTE.len()]
+}
+
+/// Unit vector pointing in `direction`, in screen space
+fn forward(direction: Direction) -> (f32, f32) {
+    let (dx, dy) = direction.offset();
+    // Offsets are always -1, 0 or 1
+    (
+        f32::from(i8::try_from(dx).unwrap_or(0)),
+        f32::from(i8::try_from(dy).unwrap_or(0)),
+    )
+}
+
+/// Direction a belt is drawn in. Belts at the end of a line inherit the direction
+/// of a belt feeding into them, and default to east when nothing does.
+fn facing(world: &World, belt: &SingleBelt) -> Direction {
+    belt.direction()
+        .or_else(|| {
+            let mut feeders: Vec<&SingleBelt> = world
+                .belts
+                .values()
+                .filter(|other| {
+                    other.left_lane.next_lane_coord == Some(belt.coordinate)
+                        || other.right_lane.next_lane_coord == Some(belt.coordinate)
+                })
+                .collect();
+            // HashMap order is arbitrary, keep the output stable
+            feeders.sort_by_key(|other| (other.coordinate.y, other.coordinate.x));
+            feeders.into_iter().find_map(SingleBelt::direction)
+        })
+        .unwrap_or(Direction::East)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn belt_shapes(world: &World, belt: &SingleBelt, origin: Coordinate, shapes: &mut Vec<Shape>) {
+    let x = (belt.coordinate.x - origin.x) as f32 * TILE_SIZE;
+    let y = (belt.coordinate.y - origin.y) as f32 * TILE_SIZE;
+    let (cx, cy) = (x + TILE_SIZE / 2.0, y + TILE_SIZE / 2.0);
+    let (fx, fy) = forward(facing(world, belt));
+    // Left of the direction of travel, with y pointing down
+    let (lx, ly) = (fy, -fx);
+
+    shapes.push(Shape::Rect {
+        x,
+        y,
+        width: TILE_SIZE,
+        height: TILE_SIZE,
+        color: belt_color(belt.left_lane.belt_type),
+        label: None,
+    });
+
+    // Thin divider along the centre line, between the two lanes
+    let (half_long, half_thin) = (TILE_SIZE / 2.0, 1.0);
+    let (w, h) = if fx == 0.0 {
+        (half_thin * 2.0, half_long * 2.0)
+    } else {
+        (half_long * 2.0, half_thin * 2.0)
+    };
+    shapes.push(Shape::Rect {
+        x: cx - w / 2.0,
+        y: cy - h / 2.0,
+        width: w,
+        height: h,
+        color: LANE_DIVIDER,
+        label: None,
+    });
+
+    // Point at `along` in the direction of travel and `across` to the left of the centre
+    let at = |along: f32, across: f32| {
+        (
+            lx.mul_add(across, fx.mul_add(along, cx)),
+            ly.mul_add(across, fy.mul_add(along, cy)),
+        )
+    };
+
+    let tip = TILE_SIZE * 0.2;
+    let base = TILE_SIZE * 0.1;
+    shapes.push(Shape::Triangle {
+        points: [at(tip, 0.0), at(-base, base), at(-base, -base)],
+        color: ARROW,
+    });
+
+    for (lane, side) in [(&belt.left_lane, 1.0), (&belt.right_lane, -1.0)] {
+        let offset = side * LANE_OFFSET * TILE_SIZE;
+        let mut items: Vec<(Item, u32)> = lane.items.iter().flatten().copied().collect();
+        items.sort_by_key(|&(_, pos)| pos);
+        for (item, pos) in items {
+            let along = (pos as f32 / lane.length as f32 - 0.5) * TILE_SIZE;
+            let (cx, cy) = at(along, offset);
+            shapes.push(Shape::Circle {
+                cx,
+                cy,
+                r: ITEM_RADIUS,
+                color: item_color(item),
+                label: Some(format!("item {} at {pos}", item.get())),
+            });
+        }
+    }
+}
+
+/// An entity other than a belt: its tile, colour, label and the side it faces
+type Entity = (Coordinate, Rgb, String, Option<Direction>);
+
+/// Every entity other than belts, in coordinate order
+fn entities(world: &World) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = Vec::new();
+    for (coordinate, machine) in &world.machines {
+        let label = machine.recipe.as_ref().map_or_else(
+            || "machine without a recipe".to_string(),
+            |recipe| format!("machine crafting {}", recipe.name),
+        );
+        entities.push((*coordinate, MACHINE, label, None));
+    }
+    for (coordinate, inserter) in &world.inserters {
+        let label = format!("{:?} inserter", inserter.kind);
+        entities.push((*coordinate, INSERTER, label, Some(inserter.direction)));
+    }
+    for (coordinate, drill) in &world.drills {
+        let label = format!("{:?} mining drill", drill.kind);
+        entities.push((*coordinate, DRILL, label, Some(drill.direction)));
+    }
+    for (coordinate, entity) in &world.fluids.entities {
+        let (label, direction) = match entity.kind {
+            FluidEntityKind::Pipe => ("pipe", None),
+            FluidEntityKind::UndergroundPipe { direction } => ("underground pipe", Some(direction)),
+            FluidEntityKind::StorageTank => ("storage tank", None),
+            FluidEntityKind::Pump { direction } => ("pump", Some(direction)),
+            FluidEntityKind::OffshorePump { direction, .. } => ("offshore pump", Some(direction)),
+        };
+        entities.push((*coordinate, PIPE, label.to_string(), direction));
+    }
+    for (coordinate, kind) in &world.power.poles {
+        entities.push((*coordinate, POLE, format!("{kind:?} electric pole"), None));
+    }
+    for (coordinate, kind) in &world.power.generators {
+        let label = match kind {
+            GeneratorKind::SteamEngine { .. } => "steam engine",
+            GeneratorKind::SteamTurbine { .. } => "steam turbine",
+            GeneratorKind::SolarPanel => "solar panel",
+        };
+        entities.push((*coordinate, POWER, label.to_string(), None));
+    }
+    for coordinate in world.power.accumulators.keys() {
+        entities.push((*coordinate, POWER, "accumulator".to_string(), None));
+    }
+    for coordinate in world.boilers.keys() {
+        entities.push((*coordinate, POWER, "boiler".to_string(), None));
+    }
+    for coordinate in world.beacons.keys() {
+        entities.push((*coordinate, MACHINE, "beacon".to_string(), None));
+    }
+    for (coordinate, chest) in &world.logistics.chests {
+        entities.push((*coordinate, CHEST, format!("{:?} chest", chest.kind), None));
+    }
+    for coordinate in world.logistics.roboports.keys() {
+        entities.push((*coordinate, LOGISTICS, "roboport".to_string(), None));
+    }
+    for (coordinate, combinator) in &world.circuits.combinators {
+        let label = match combinator {
+            Combinator::Constant(_) => "constant combinator",
+            Combinator::Arithmetic { .. } => "arithmetic combinator",
+            Combinator::Decider { .. } => "decider combinator",
+            Combinator::Selector(_) => "selector combinator",
+        };
+        entities.push((*coordinate, CIRCUIT, label.to_string(), None));
+    }
+    for (coordinate, stop) in &world.rails.stops {
+        let label = format!("train stop {}", stop.name);
+        entities.push((*coordinate, RAIL, label, Some(stop.direction)));
+    }
+    // HashMap order is arbitrary, keep the output stable
+    entities.sort_by_key(|(c, ..)| (c.y, c.x));
+    entities
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn entity_shapes(
+    (coordinate, color, label, direction): Entity,
+    origin: Coordinate,
+    shapes: &mut Vec<Shape>,
+) {
+    let x = (coordinate.x - origin.x) as f32 * TILE_SIZE;
+    let y = (coordinate.y - origin.y) as f32 * TILE_SIZE;
+    shapes.push(Shape::Rect {
+        x: x + ENTITY_INSET,
+        y: y + ENTITY_INSET,
+        width: 2.0f32.mul_add(-ENTITY_INSET, TILE_SIZE),
+        height: 2.0f32.mul_add(-ENTITY_INSET, TILE_SIZE),
+        color,
+        label: Some(label),
+    });
+    if let Some(direction) = direction {
+        let (cx, cy) = (x + TILE_SIZE / 2.0, y + TILE_SIZE / 2.0);
+        let (fx, fy) = forward(direction);
+        let at = |along: f32, across: f32| {
+            (
+                fy.mul_add(across, fx.mul_add(along, cx)),
+                (-fx).mul_add(across, fy.mul_add(along, cy)),
+            )
+        };
+        let tip = TILE_SIZE * 0.25;
+        let base = TILE_SIZE * 0.15;
+        shapes.push(Shape::Triangle {
+            points: [at(tip, 0.0), at(-base, base), at(-base, -base)],
+            color: ARROW,
+        });
+    }
+}
+
+/// Smallest and largest corner of the bounding box of all belts and other entities
+fn scene_bounds(world: &World) -> Option<(Coordinate, Coordinate)> {
+    let entities = entities(world);
+    let corners = entities.iter().map(|(c, ..)| (*c, *c));
+    world
+        .bounds()
+        .into_iter()
+        .chain(corners)
+        .reduce(|(min, max), (low, high)| {
+            (
+                Coordinate::new(min.x.min(low.x), min.y.min(low.y)),
+                Coordinate::new(max.x.max(high.x), max.y.max(high.y)),
+            )
+        })
+}
+
+/// Builds the scene for the bounding box of all belts and other entities in the
+/// world. An empty world is a single empty tile.
+pub fn build_scene(world: &World) -> Scene {
+    let origin = Coordinate::new(0, 0);
+    let (min, max) = scene_bounds(world).unwrap_or((origin, origin));
+    build_scene_in(world, min, max)
+}
+
+/// Builds the scene for the tiles between the `min` and `max` corners (inclusive),
+/// at least one tile in size. Entities outside of that region are left out.
+pub fn build_scene_in(world: &World, min: Coordinate, max: Coordinate) -> Scene {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let tile = TILE_SIZE as u32;
+    let width = (max.x - min.x + 1).max(1).unsigned_abs() * tile;
+    let height = (max.y - min.y + 1).max(1).unsigned_abs() * tile;
+    let inside = |c: Coordinate| (min.x..=max.x).contains(&c.x) && (min.y..=max.y).contains(&c.y);
+
+    let mut belts: Vec<&SingleBelt> = world
+        .belts
+        .values()
+        .filter(|belt| inside(belt.coordinate))
+        .collect();
+    belts.sort_by_key(|belt| (belt.coordinate.y, belt.coordinate.x));
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut shapes = vec![Shape::Rect {
+        x: 0.0,
+        y: 0.0,
+        width: width as f32,
+        height: height as f32,
+        color: BACKGROUND,
+        label: None,
+    }];
+    for belt in belts {
+        belt_shapes(world, belt, min, &mut shapes);
+    }
+    for entity in entities(world) {
+        if inside(entity.0) {
+            entity_shapes(entity, min, &mut shapes);
+        }
+    }
+
+    Scene {
+        width,
+        height,
+        shapes,
+    }
+}
+
+/// Escapes the characters that have a meaning in XML text
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Scene {
+    /// Serializes the scene as a standalone SVG document
+    pub fn to_svg(&self) -> String {
+        let mut out = String::new();
+        // Writing into a String cannot fail
+        let _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = self.width,
+            h = self.height
+        );
+        for shape in &self.shapes {
+            let _ = match shape {
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                    label,
+                } => match label {
+                    Some(label) => writeln!(
+                        out,
+                        r#"<rect x="{x}" y="{y}" width="{width}" height="{height}" fill="{}"><title>{}</title></rect>"#,
+                        color.hex(),
+                        escape(label)
+                    ),
+                    None => writeln!(
+                        out,
+                        r#"<rect x="{x}" y="{y}" width="{width}" height="{height}" fill="{}"/>"#,
+                        color.hex()
+                    ),
+                },
+                Shape::Circle {
+                    cx,
+                    cy,
+                    r,
+                    color,
+                    label,
+                } => match label {
+                    Some(label) => writeln!(
+                        out,
+                        r#"<circle cx="{cx}" cy="{cy}" r="{r}" fill="{}"><title>{}</title></circle>"#,
+                        color.hex(),
+                        escape(label)
+                    ),
+                    None => writeln!(
+                        out,
+                        r#"<circle cx="{cx}" cy="{cy}" r="{r}" fill="{}"/>"#,
+                        color.hex()
+                    ),
+                },
+                Shape::Triangle { points, color } => writeln!(
+                    out,
+                    r#"<polygon points="{},{} {},{} {},{}" fill="{}"/>"#,
+                    points[0].0,
+                    points[0].1,
+                    points[1].0,
+                    points[1].1,
+                    points[2].0,
+                    points[2].1,
+                    color.hex()
+                ),
+            };
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+
+    /// Rasterizes the scene into RGBA8 pixels, sampling each pixel at its centre
+    pub fn rasterize(&self) -> Vec<u8> {
+        let mut pixels = vec![0; self.width as usize * self.height as usize * 4];
+        for shape in &self.shapes {
+            let (color, (x0, y0, x1, y1)) = match shape {
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                    ..
+                } => (*color, (*x, *y, x + width, y + height)),
+                Shape::Circle {
+                    cx, cy, r, color, ..
+                } => (*color, (cx - r, cy - r, cx + r, cy + r)),
+                Shape::Triangle { points, color } => {
+                    let xs = points.map(|p| p.0);
+                    let ys = points.map(|p| p.1);
+                    (
+                        *color,
+                        (
+                            xs.iter().copied().fold(f32::INFINITY, f32::min),
+                            ys.iter().copied().fold(f32::INFINITY, f32::min),
+                            xs.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+                            ys.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+                        ),
+                    )
+                }
+            };
+
+            for py in pixel_range(y0, y1, self.height) {
+                for px in pixel_range(x0, x1, self.width) {
+                    #[allow(clippy::cast_precision_loss)]
+                    let (sx, sy) = (px as f32 + 0.5, py as f32 + 0.5);
+                    if shape.contains(sx, sy) {
+                        let idx = (py as usize * self.width as usize + px as usize) * 4;
+                        pixels[idx..idx + 4].copy_from_slice(&[color.0, color.1, color.2, 0xff]);
+                    }
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Encodes the rasterized scene as a PNG file
+    pub fn to_png(&self) -> Vec<u8> {
+        png::encode(self.width, self.height, &self.rasterize())
+    }
+}
+
+/// Pixels whose centre may fall inside `[start, end)`, clamped to the image
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn pixel_range(start: f32, end: f32, limit: u32) -> std::ops::Range<u32> {
+    let first = start.floor().max(0.0) as u32;
+    let last = (end.ceil().max(0.0) as u32).min(limit);
+    first.min(last)..last
+}
+
+impl Shape {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        match self {
+            Self::Rect {
+                x: rx,
+                y: ry,
+                width,
+                height,
+                ..
+            } => x >= *rx && x < rx + width && y >= *ry && y < ry + height,
+            Self::Circle { cx, cy, r, .. } => (x - cx).hypot(y - cy) <= *r,
+            Self::Triangle { points, .. } => {
+                let edge = |(ax, ay): (f32, f32), (bx, by): (f32, f32)| {
+                    (bx - ax).mul_add(y - ay, -((by - ay) * (x - ax)))
+                };
+                let d0 = edge(points[0], points[1]);
+                let d1 = edge(points[1], points[2]);
+                let d2 = edge(points[2], points[0]);
+                let has_negative = d0 < 0.0 || d1 < 0.0 || d2 < 0.0;
+                let has_positive = d0 > 0.0 || d1 > 0.0 || d2 > 0.0;
+                !(has_negative && has_positive)
+            }
+        }
+    }
+}
+
+/// Renders the world as an SVG document
+pub fn render_svg(world: &World) -> String {
+    build_scene(world).to_svg()
+}
+
+/// Renders the world as a PNG image
+pub fn render_png(world: &World) -> Vec<u8> {
+    build_scene(world).to_png()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,216 @@
+use super::*;
+use crate::{
+    SingleBeltLane, World,
+    crafting::{CraftingMachine, Recipe},
+    fluid::{FluidEntity, FluidEntityKind},
+    inserter::{Inserter, InserterKind},
+    logistic::{ChestKind, LogisticChest},
+    mining::{DrillKind, MiningDrill},
+    power::PoleKind,
+};
+use std::num::NonZeroUsize;
+
+fn item(id: usize) -> Item {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+fn circles(scene: &Scene) -> Vec<(f32, f32)> {
+    scene
+        .shapes
+        .iter()
+        .filter_map(|shape| match shape {
+            Shape::Circle { cx, cy, .. } => Some((*cx, *cy)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn pixel(scene: &Scene, pixels: &[u8], x: u32, y: u32) -> [u8; 4] {
+    let idx = (y as usize * scene.width as usize + x as usize) * 4;
+    [
+        pixels[idx],
+        pixels[idx + 1],
+        pixels[idx + 2],
+        pixels[idx + 3],
+    ]
+}
+
+#[test]
+fn test_empty_world_scene() {
+    // One empty tile, so the PNG is still a valid image
+    let scene = build_scene(&World::new());
+    assert_eq!((scene.width, scene.height), (64, 64));
+    assert_eq!(scene.shapes.len(), 1);
+    let png = render_png(&World::new());
+    assert!(png.starts_with(&png::SIGNATURE));
+    assert_eq!(&png[16..24], &[0, 0, 0, 64, 0, 0, 0, 64]);
+}
+
+#[test]
+fn test_world_with_only_a_machine_is_drawn() {
+    let mut world = World::new();
+    world.add_machine(
+        Coordinate::new(3, -2),
+        CraftingMachine::new(1.0).with_recipe(Recipe::new("gear & wheel", 0.5)),
+    );
+    let scene = build_scene(&world);
+    assert_eq!((scene.width, scene.height), (64, 64));
+    let pixels = scene.rasterize();
+    assert_eq!(pixel(&scene, &pixels, 32, 32), [0x6c, 0x7a, 0x89, 0xff]);
+    assert_eq!(pixel(&scene, &pixels, 2, 2), [0x2b, 0x2b, 0x2b, 0xff]);
+    assert!(
+        scene
+            .to_svg()
+            .contains("<title>machine crafting gear &amp; wheel</title>")
+    );
+}
+
+#[test]
+fn test_every_entity_kind_gets_a_labelled_box() {
+    let mut world = World::new();
+    world.add_inserter(
+        Coordinate::new(0, 0),
+        Inserter::new(InserterKind::Fast, Direction::East),
+    );
+    world.add_drill(
+        Coordinate::new(1, 0),
+        MiningDrill::new(DrillKind::Electric, Direction::North),
+    );
+    world.logistics.add_chest(
+        Coordinate::new(2, 0),
+        LogisticChest::new(ChestKind::Storage),
+    );
+    world.add_fluid_entity(
+        Coordinate::new(3, 0),
+        FluidEntity::new(FluidEntityKind::Pipe),
+    );
+    world.power.add_pole(Coordinate::new(4, 0), PoleKind::Small);
+    let labels: Vec<String> = build_scene(&world)
+        .shapes
+        .into_iter()
+        .filter_map(|shape| match shape {
+            Shape::Rect { label, .. } => label,
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            "Fast inserter",
+            "Electric mining drill",
+            "Storage chest",
+            "pipe",
+            "Small electric pole",
+        ]
+    );
+}
+
+#[test]
+fn test_scene_size_covers_all_belts() {
+    let mut world = World::new();
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(-1, 0),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(2, 1),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    let scene = build_scene(&world);
+    assert_eq!((scene.width, scene.height), (256, 128));
+}
+
+#[test]
+fn test_item_positions_sub_tile_accuracy() {
+    let mut world = World::new();
+    let coord1 = Coordinate::new(0, 0);
+    let coord2 = Coordinate::new(1, 0);
+    world.add_belt(SingleBelt::new(coord2, BeltType::Regular, None, None));
+    let mut belt = SingleBelt::new(coord1, BeltType::Regular, Some(coord2), Some(coord2));
+    belt.left_lane.items[0] = Some((item(1), 0));
+    belt.left_lane.items[1] = Some((item(2), 128));
+    belt.right_lane.items[0] = Some((item(3), 192));
+    world.add_belt(belt);
+
+    let scene = build_scene(&world);
+    // Belt faces east: left lane is the northern half, position 0 is the western edge
+    assert_eq!(
+        circles(&scene),
+        vec![(0.0, 16.0), (32.0, 16.0), (48.0, 48.0)]
+    );
+}
+
+#[test]
+fn test_end_of_line_belt_inherits_direction() {
+    let mut world = World::new();
+    let top = Coordinate::new(0, 0);
+    let bottom = Coordinate::new(0, 1);
+    let mut end = SingleBelt::new(top, BeltType::Fast, None, None);
+    end.left_lane.items[0] = Some((item(1), 0));
+    world.add_belt(end);
+    world.add_belt(SingleBelt::new(
+        bottom,
+        BeltType::Fast,
+        Some(top),
+        Some(top),
+    ));
+
+    let scene = build_scene(&world);
+    // Facing north: left lane is the western half and position 0 is the southern edge
+    assert_eq!(circles(&scene), vec![(16.0, 64.0)]);
+}
+
+#[test]
+fn test_svg_contains_shapes() {
+    let mut world = World::new();
+    let mut belt = SingleBelt::new(Coordinate::new(0, 0), BeltType::Express, None, None);
+    belt.right_lane = SingleBeltLane::new(BeltType::Express, None);
+    belt.right_lane.items[0] = Some((item(5), 64));
+    world.add_belt(belt);
+
+    let svg = render_svg(&world);
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert!(svg.contains(r#"width="64" height="64""#));
+    assert!(svg.contains(r##"fill="#2e6db0""##));
+    assert!(svg.contains("<title>item 5 at 64</title>"));
+    assert_eq!(svg.matches("<polygon").count(), 1);
+}
+
+#[test]
+fn test_rasterize_matches_scene() {
+    let mut world = World::new();
+    let mut belt = SingleBelt::new(Coordinate::new(0, 0), BeltType::Turbo, None, None);
+    belt.left_lane.items[0] = Some((item(1), 128));
+    world.add_belt(belt);
+
+    let scene = build_scene(&world);
+    let pixels = scene.rasterize();
+    assert_eq!(pixels.len(), 64 * 64 * 4);
+    // Corner is plain belt, item sits on the left lane in the middle of the tile
+    assert_eq!(pixel(&scene, &pixels, 1, 62), [0x3f, 0x8f, 0x4a, 0xff]);
+    let item = item_color(item(1));
+    assert_eq!(
+        pixel(&scene, &pixels, 32, 16),
+        [item.0, item.1, item.2, 0xff]
+    );
+}
+
+#[test]
+fn test_png_output_is_deterministic() {
+    let mut world = World::new();
+    let coord1 = Coordinate::new(0, 0);
+    let coord2 = Coordinate::new(1, 0);
+    world.add_belt(SingleBelt::new(coord2, BeltType::Regular, None, None));
+    let mut belt = SingleBelt::new(coord1, BeltType::Regular, Some(coord2), Some(coord2));
+    belt.left_lane.items[0] = Some((item(1), 20));
+    world.add_belt(belt);
+
+    let first = render_png(&world);
+    assert_eq!(first, render_png(&world));
+    assert!(first.starts_with(&png::SIGNATURE));
+}
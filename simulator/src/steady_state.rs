@@ -0,0 +1,237 @@
+//! Detection of the periodic state a world settles into.
+//!
+//! Most layouts become periodic after a while: every item on a belt is replaced by
+//! another one at the same position, and every machine and inserter goes through
+//! the same motions again. The world is hashed after every tick, ignoring which
+//! items sit on the lanes and how much has piled up in chests or is left in the
+//! ground, and a period is found once the hashes of a full cycle repeat. Averages
+//! over that one cycle are then exact.
+
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+};
+
+use super::{
+    Coordinate, TICKS_PER_SECOND, World,
+    burner::{Burner, FUEL_SLOT_SIZE},
+    checksum::Checksum,
+    fluid::FluidBox,
+    rail::Vehicle,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SteadyState {
+    /// Tick count of the world when the measured cycle began
+    pub start: u64,
+    /// Length of one cycle in ticks
+    pub period: u64,
+    /// Items accepted per second by each belt, over both lanes
+    pub belt_throughput: HashMap<Coordinate, f64>,
+    /// Crafts per second of each machine
+    pub crafts_per_second: HashMap<Coordinate, f64>,
+    /// Items per second robots bring to each logistic chest
+    pub robot_deliveries: HashMap<Coordinate, f64>,
+}
+
+/// Counters to take differences of over one cycle
+struct Totals {
+    accepted: HashMap<Coordinate, u64>,
+    crafts: HashMap<Coordinate, u64>,
+    delivered: HashMap<Coordinate, u64>,
+}
+
+impl World {
+    /// Hash of everything that moves in cycles: the positions of items on every lane,
+    /// but not which items they are, the progress of machines, drills, inserters,
+    /// trains and robots, fuel burning, fluids and their flows and accumulator charge.
+    /// Stock only counts as far as it stops something: items in machines up to what
+    /// blocks inserters or crafting, and whether fuel slots, wagons and chests are
+    /// empty or full. Totals that only ever grow or shrink, like items in a sink
+    /// chest or ore left in the ground, are left out, or the world would never repeat.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Checksum::default();
+        let mut belts: Vec<_> = self.belts.iter().collect();
+        belts.sort_by_key(|(c, _)| (c.y, c.x));
+        for (coordinate, belt) in belts {
+            (coordinate.x, coordinate.y).hash(&mut hasher);
+            for lane in [&belt.left_lane, &belt.right_lane] {
+                let mut positions: Vec<u32> =
+                    lane.items.iter().flatten().map(|(_, pos)| *pos).collect();
+                positions.sort_unstable();
+                Hash::hash_slice(&positions, &mut hasher);
+            }
+        }
+        let mut machines: Vec<_> = self.machines.iter().collect();
+        machines.sort_by_key(|(c, _)| (c.y, c.x));
+        for (_, machine) in machines {
+            machine.progress.is_some().hash(&mut hasher);
+            hash_phase(machine.progress.unwrap_or(0.0), &mut hasher);
+            Hash::hash_slice(&machine.buffer_levels(), &mut hasher);
+            for port in machine.fluid_inputs.iter().chain(&machine.fluid_outputs) {
+                hash_fluidbox(&port.fluidbox, &mut hasher);
+            }
+            hash_burner(machine.burner.as_ref(), &mut hasher);
+        }
+        let mut inserters: Vec<_> = self.inserters.iter().collect();
+        inserters.sort_by_key(|(c, _)| (c.y, c.x));
+        for (_, inserter) in inserters {
+            hash_phase(inserter.angle, &mut hasher);
+            inserter.held.is_some().hash(&mut hasher);
+            hash_burner(inserter.burner.as_ref(), &mut hasher);
+        }
+        let mut drills: Vec<_> = self.drills.iter().collect();
+        drills.sort_by_key(|(c, _)| (c.y, c.x));
+        for (_, drill) in drills {
+            hash_phase(drill.progress, &mut hasher);
+            drill.output.len().hash(&mut hasher);
+            hash_burner(drill.burner.as_ref(), &mut hasher);
+        }
+        let mut boilers: Vec<_> = self.boilers.iter().collect();
+        boilers.sort_by_key(|(c, _)| (c.y, c.x));
+        for (_, boiler) in boilers {
+            hash_burner(Some(&boiler.burner), &mut hasher);
+        }
+        let mut fluids: Vec<_> = self.fluids.entities.iter().collect();
+        fluids.sort_by_key(|(c, _)| (c.y, c.x));
+        for (_, entity) in fluids {
+            hash_fluidbox(&entity.fluidbox, &mut hasher);
+        }
+        for ((from, to), flow) in self.fluids.flows() {
+            (from.x, from.y, to.x, to.y).hash(&mut hasher);
+            hash_phase(flow, &mut hasher);
+        }
+        let mut accumulators: Vec<_> = self.power.accumulators.iter().collect();
+        accumulators.sort_by_key(|(c, _)| (c.y, c.x));
+        for (_, accumulator) in accumulators {
+            hash_phase(accumulator.charge, &mut hasher);
+        }
+        for train in &self.rails.trains {
+            train.occupied.front().hash(&mut hasher);
+            hash_phase(train.front_progress, &mut hasher);
+            hash_phase(train.speed, &mut hasher);
+            train.current.hash(&mut hasher);
+            for vehicle in &train.vehicles {
+                match vehicle {
+                    Vehicle::Locomotive { burner } => hash_burner(Some(burner), &mut hasher),
+                    Vehicle::FluidWagon { fluidbox } => hash_fluidbox(fluidbox, &mut hasher),
+                    Vehicle::CargoWagon { .. } => {
+                        (vehicle.is_empty(), vehicle.is_full()).hash(&mut hasher);
+                    }
+                }
+            }
+        }
+        for robot in &self.logistics.robots {
+            hash_phase(robot.position.0, &mut hasher);
+            hash_phase(robot.position.1, &mut hasher);
+            robot.cargo.is_some().hash(&mut hasher);
+            robot.state.hash(&mut hasher);
+        }
+        let mut chests: Vec<_> = self.logistics.chests.iter().collect();
+        chests.sort_by_key(|(c, _)| (c.y, c.x));
+        for (_, chest) in chests {
+            chest.has_room().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    fn totals(&self) -> Totals {
+        Totals {
+            accepted: self
+                .belts
+                .iter()
+                .map(|(c, belt)| (*c, belt.left_lane.accepted + belt.right_lane.accepted))
+                .collect(),
+            crafts: self
+                .machines
+                .iter()
+                .map(|(c, machine)| (*c, machine.crafts))
+                .collect(),
+            delivered: self
+                .logistics
+                .chests
+                .iter()
+                .map(|(c, chest)| (*c, chest.delivered))
+                .collect(),
+        }
+    }
+
+    /// Ticks until the world repeats itself, for at most `max_ticks`, and returns the
+    /// period and the averages over one cycle. A candidate period only counts once
+    /// the following cycle repeats it tick by tick, so a single hash collision or a
+    /// state passed once during start-up is not mistaken for a cycle.
+    #[allow(dead_code, clippy::cast_precision_loss)]
+    pub fn run_until_periodic(&mut self, max_ticks: u64) -> Option<SteadyState> {
+        let mut seen: HashMap<u64, u64> = HashMap::new();
+        let mut history: Vec<u64> = Vec::new();
+        let mut candidate: Option<(u64, u64, Totals)> = None;
+        for tick in 0..max_ticks {
+            self.tick();
+            let hash = self.state_hash();
+            history.push(hash);
+            if let Some((start, period, totals)) = &candidate {
+                // Compare with the same point of the previous cycle
+                #[allow(clippy::cast_possible_truncation)]
+                let expected = history[(tick - period) as usize];
+                if expected != hash {
+                    candidate = None;
+                } else if tick + 1 == start + period {
+                    let seconds = *period as f64 / TICKS_PER_SECOND;
+                    let now = self.totals();
+                    let rate = |after: &HashMap<Coordinate, u64>,
+                                before: &HashMap<Coordinate, u64>| {
+                        after
+                            .iter()
+                            .map(|(c, count)| {
+                                let before = before.get(c).copied().unwrap_or(0);
+                                (*c, (count - before) as f64 / seconds)
+                            })
+                            .collect()
+                    };
+                    return Some(SteadyState {
+                        start: self.ticks - period,
+                        period: *period,
+                        belt_throughput: rate(&now.accepted, &totals.accepted),
+                        crafts_per_second: rate(&now.crafts, &totals.crafts),
+                        robot_deliveries: rate(&now.delivered, &totals.delivered),
+                    });
+                }
+            }
+            if candidate.is_none()
+                && let Some(&previous) = seen.get(&hash)
+            {
+                // The cycle to check starts with the next tick
+                candidate = Some((tick + 1, tick - previous, self.totals()));
+            }
+            seen.insert(hash, tick);
+        }
+        None
+    }
+}
+
+/// Hashes `value` rounded to a billionth. Progress carried over from one cycle to the
+/// next picks up a little floating point noise every cycle, which would otherwise
+/// keep the world from ever repeating exactly.
+fn hash_phase(value: f64, hasher: &mut Checksum) {
+    // Adding zero turns -0 into 0
+    ((value * 1e9).round() + 0.0).to_bits().hash(hasher);
+}
+
+fn hash_fluidbox(fluidbox: &FluidBox, hasher: &mut Checksum) {
+    fluidbox.fluid.hash(hasher);
+    hash_phase(fluidbox.amount, hasher);
+}
+
+/// The part of a burner that comes round again: whether it has fuel left to burn or
+/// room for more, and how much of the burning item is left
+fn hash_burner(burner: Option<&Burner>, hasher: &mut Checksum) {
+    burner.is_some().hash(hasher);
+    if let Some(burner) = burner {
+        let count = burner.fuel_count();
+        (count > 0, count < FUEL_SLOT_SIZE).hash(hasher);
+        hash_phase(burner.remaining, hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests;
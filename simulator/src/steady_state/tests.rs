@@ -0,0 +1,178 @@
+use super::*;
+use crate::{
+    BeltType, Direction, SingleBelt,
+    crafting::{CraftingMachine, Recipe},
+    inserter::{Inserter, InserterKind},
+    logistic::{ChestKind, LogisticChest},
+    mining::{DrillKind, MiningDrill, Resource},
+    power::{Accumulator, GeneratorKind, PoleKind},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+/// Four belts in a square loop, each feeding the next
+fn belt_loop(belt_type: BeltType) -> World {
+    let corners = [(0, 0), (1, 0), (1, 1), (0, 1)];
+    let mut world = World::new();
+    for (i, (x, y)) in corners.iter().enumerate() {
+        let (nx, ny) = corners[(i + 1) % corners.len()];
+        let next = Some(Coordinate::new(nx, ny));
+        world.add_belt(SingleBelt::new(
+            Coordinate::new(*x, *y),
+            belt_type,
+            next,
+            next,
+        ));
+    }
+    world
+}
+
+#[test]
+fn test_item_going_round_a_loop() {
+    let mut world = belt_loop(BeltType::Regular);
+    if let Some(belt) = world.belts.get_mut(&Coordinate::new(0, 0)) {
+        belt.left_lane.items[0] = Some((id(1), 0));
+    }
+    // 1024 positions at 8 per tick
+    assert_eq!(world.run_until_periodic(10), None);
+    let steady = world.run_until_periodic(1000).expect("periodic");
+    assert_eq!(steady.period, 128);
+    for throughput in steady.belt_throughput.values() {
+        assert!((throughput - 60.0 / 128.0).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_stored_energy_is_part_of_the_state_but_resources_are_not() {
+    let mut world = belt_loop(BeltType::Regular);
+    let mut other = belt_loop(BeltType::Regular);
+    for (world, charge) in [(&mut world, 0.0), (&mut other, 1000.0)] {
+        world
+            .power
+            .accumulators
+            .insert(Coordinate::new(5, 5), Accumulator { charge });
+        world.add_resource(Coordinate::new(5, 6), Resource::new(id(1), 10));
+    }
+    assert_ne!(world.state_hash(), other.state_hash());
+
+    if let Some(accumulator) = other.power.accumulators.get_mut(&Coordinate::new(5, 5)) {
+        accumulator.charge = 0.0;
+    }
+    assert_eq!(world.state_hash(), other.state_hash());
+    other.add_resource(Coordinate::new(5, 6), Resource::new(id(1), 9));
+    assert_eq!(world.state_hash(), other.state_hash());
+}
+
+#[test]
+fn test_drill_feeding_a_chest_settles() {
+    let mut world = World::new();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            world.add_resource(Coordinate::new(x, y), Resource::new(id(1), 10_000));
+        }
+    }
+    world.add_drill(
+        Coordinate::new(0, 0),
+        MiningDrill::new(DrillKind::Electric, Direction::North),
+    );
+    for x in 0..3 {
+        let next = (x < 2).then(|| Coordinate::new(x + 1, -1));
+        world.add_belt(SingleBelt::new(
+            Coordinate::new(x, -1),
+            BeltType::Regular,
+            next,
+            next,
+        ));
+    }
+    world.add_inserter(
+        Coordinate::new(3, -1),
+        Inserter::new(InserterKind::Fast, Direction::East),
+    );
+    world.logistics.add_chest(
+        Coordinate::new(4, -1),
+        LogisticChest::new(ChestKind::PassiveProvider),
+    );
+    world
+        .power
+        .add_pole(Coordinate::new(2, 0), PoleKind::Medium);
+    for x in 0..4 {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, 2), GeneratorKind::SolarPanel);
+    }
+
+    // The chest keeps filling and the patch keeps shrinking, but the motions repeat
+    let steady = world.run_until_periodic(2000).expect("periodic");
+    assert_eq!(steady.period, 120);
+    for x in 0..3 {
+        assert!((steady.belt_throughput[&Coordinate::new(x, -1)] - 0.5).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_different_items_at_the_same_positions_are_the_same_state() {
+    let mut world = belt_loop(BeltType::Express);
+    let mut other = belt_loop(BeltType::Express);
+    for (world, item) in [(&mut world, 1), (&mut other, 2)] {
+        if let Some(belt) = world.belts.get_mut(&Coordinate::new(1, 0)) {
+            belt.right_lane.items[3] = Some((id(item), 100));
+        }
+    }
+    assert_eq!(world.state_hash(), other.state_hash());
+    world.tick();
+    assert_ne!(world.state_hash(), other.state_hash());
+}
+
+#[test]
+fn test_production_line_settles_into_exact_rates() {
+    let mut world = World::new();
+    world.add_machine(
+        Coordinate::new(0, 0),
+        CraftingMachine::new(1.0).with_recipe(Recipe::new("ore", 1.0).item_product(id(1), 1)),
+    );
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(2, 0),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    world.add_machine(
+        Coordinate::new(4, 0),
+        CraftingMachine::new(1.0).with_recipe(
+            Recipe::new("gear", 1.0)
+                .item_ingredient(id(1), 2)
+                .item_product(id(2), 1),
+        ),
+    );
+    world.add_machine(
+        Coordinate::new(6, 0),
+        CraftingMachine::new(1.0).with_recipe(Recipe::new("sink", 0.1).item_ingredient(id(2), 1)),
+    );
+    for x in [1, 3, 5] {
+        world.add_inserter(
+            Coordinate::new(x, 0),
+            Inserter::new(InserterKind::Fast, Direction::East),
+        );
+    }
+    world
+        .power
+        .add_pole(Coordinate::new(3, 1), PoleKind::Medium);
+    for x in 0..=6 {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, 3), GeneratorKind::SolarPanel);
+    }
+
+    let steady = world.run_until_periodic(10_000).expect("periodic");
+    assert_eq!(steady.period % 60, 0);
+    let rate = |x| steady.crafts_per_second[&Coordinate::new(x, 0)];
+    assert!((rate(0) - 1.0).abs() < 1e-9);
+    assert!((rate(4) - 0.5).abs() < 1e-9);
+    assert!((rate(6) - 0.5).abs() < 1e-9);
+    assert!((steady.belt_throughput[&Coordinate::new(2, 0)] - 1.0).abs() < 1e-9);
+}
@@ -256,6 +256,162 @@ fn test_accept_item_beyond_255() {
     }
 }
 
+#[test]
+fn test_lane_occupancy_queries() {
+    let mut lane = SingleBeltLane::new(BeltType::Regular, None);
+    lane.items[0] = Some((item(1), 100));
+    lane.items[3] = Some((item(2), 10));
+
+    assert_eq!(lane.item_count(), 2);
+    assert_eq!(lane.positions(), vec![10, 100]);
+    assert_eq!(lane.gaps(), vec![0..10, 74..100, 164..256]);
+    assert_eq!(lane.free_space_at(0), 10);
+    assert_eq!(lane.free_space_at(50), 0);
+    assert_eq!(lane.free_space_at(74), 26);
+    assert_eq!(lane.free_space_at(200), 56);
+    assert!(lane.fits_at(164));
+    assert!(!lane.fits_at(163));
+    assert!(!lane.fits_at(36));
+    assert!(lane.fits_at(255));
+    assert!(!lane.fits_at(256));
+    assert!((lane.compression() - 0.5).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_compressed_lane_has_no_room() {
+    let mut lane = SingleBeltLane::new(BeltType::Regular, None);
+    for (slot, position) in [0, 64, 128, 192].into_iter().enumerate() {
+        lane.items[slot] = Some((item(1), position));
+    }
+    assert!(lane.gaps().is_empty());
+    assert!((0..256).all(|position| !lane.fits_at(position)));
+    assert!((lane.compression() - 1.0).abs() < f64::EPSILON);
+    assert_eq!(lane.accept_position(200), None);
+}
+
+#[test]
+fn test_accept_position_matches_accept_item() {
+    let mut lane = SingleBeltLane::new(BeltType::Regular, None);
+    lane.items[0] = Some((item(1), 100));
+    assert_eq!(lane.accept_position(120), Some(164));
+    assert_eq!(lane.accept_position(300), Some(255));
+    assert_eq!(lane.accept_position(200), Some(200));
+    assert!(lane.accept_item(item(2), 120));
+    assert_eq!(get_positions(&lane), vec![100, 164]);
+    assert_eq!(lane.accept_position(200), Some(228));
+}
+
+#[test]
+fn test_world_lane_queries() {
+    let mut world = World::new();
+    let coord = Coordinate::new(0, 0);
+    let mut belt = SingleBelt::new(coord, BeltType::Regular, None, None);
+    belt.left_lane.items[0] = Some((item(1), 0));
+    belt.left_lane.items[1] = Some((item(1), 64));
+    belt.right_lane.items[0] = Some((item(2), 200));
+    world.add_belt(belt);
+
+    assert_eq!(world.belt_item_count(coord), 3);
+    assert_eq!(world.belt_item_count(Coordinate::new(1, 0)), 0);
+    assert!(world.item_fits(coord, true, 128));
+    assert!(!world.item_fits(coord, false, 150));
+    assert!(!world.item_fits(Coordinate::new(1, 0), true, 0));
+    assert_eq!(world.belt_compression(coord), Some(0.375));
+    assert_eq!(world.belt_compression(Coordinate::new(1, 0)), None);
+    assert_eq!(
+        world.lane(coord, false).map(|lane| lane.free_space_at(0)),
+        Some(200)
+    );
+}
+
+#[test]
+fn test_lane_length_sets_capacity() {
+    let straight = SingleBeltLane::new(BeltType::Regular, None);
+    assert_eq!((straight.length, straight.items.len()), (256, 5));
+    assert!(!straight.items.is_heap());
+
+    let short = SingleBeltLane::new(BeltType::Regular, None).with_length(150);
+    assert_eq!((short.length, short.items.len()), (150, 4));
+    assert!(!short.items.is_heap());
+
+    let long = SingleBeltLane::new(BeltType::Regular, None).with_length(1024);
+    assert_eq!(
+        (long.length, long.items.len(), long.qualities.len()),
+        (1024, 17, 17)
+    );
+    assert!(long.items.is_heap());
+
+    let custom = SingleBeltLane::new(BeltType::Regular, None).with_capacity(2);
+    assert!(custom.accept_position(0).is_some());
+}
+
+#[test]
+fn test_long_lane_holds_more_items() {
+    let mut lane =
+        SingleBeltLane::new(BeltType::Regular, Some(Coordinate::new(1, 0))).with_length(512);
+    for position in (0..512).step_by(64) {
+        assert!(lane.accept_item(item(1), position));
+    }
+    assert_eq!(lane.item_count(), 8);
+    assert!((lane.compression() - 1.0).abs() < f64::EPSILON);
+    assert!(lane.gaps().is_empty());
+
+    // The front item leaves only once it passes position 511
+    for _ in 0..7 {
+        assert!(lane.tick_and_get_transfers().is_empty());
+    }
+    let transfers = lane.tick_and_get_transfers();
+    assert_eq!(transfers, vec![(item(1), 0, quality::Quality::Normal, 1)]);
+    assert_eq!(lane.item_count(), 7);
+}
+
+#[test]
+fn test_short_lane_stops_items_at_its_end() {
+    let mut lane = SingleBeltLane::new(BeltType::Regular, None).with_length(100);
+    assert!(lane.accept_item(item(1), 200));
+    assert!(lane.accept_item(item(2), 10));
+    assert_eq!(get_positions(&lane), vec![10, 99]);
+    for _ in 0..20 {
+        lane.tick_and_get_transfers();
+    }
+    assert_eq!(get_positions(&lane), vec![35, 99]);
+    assert_eq!(lane.gaps(), vec![0..35]);
+}
+
+#[test]
+fn test_short_lane_compression() {
+    let mut lane = SingleBeltLane::new(BeltType::Regular, None).with_length(100);
+    assert!(lane.accept_item(item(1), 99));
+    assert!((lane.compression() - 0.5).abs() < f64::EPSILON);
+    assert!(lane.accept_item(item(1), 35));
+    assert!((lane.compression() - 1.0).abs() < f64::EPSILON);
+    assert_eq!(lane.items.len(), 3);
+}
+
+#[test]
+#[should_panic(expected = "a lane needs at least one position")]
+fn test_lane_without_positions_is_rejected() {
+    let _ = SingleBeltLane::new(BeltType::Regular, None).with_length(0);
+}
+
+#[test]
+fn test_items_move_from_short_lanes_onto_straight_ones() {
+    let mut world = World::new();
+    let curve = Coordinate::new(0, 0);
+    let next = Coordinate::new(1, 0);
+    let mut belt = SingleBelt::new(curve, BeltType::Regular, Some(next), Some(next))
+        .with_lane_lengths(128, 384);
+    belt.left_lane.items[0] = Some((item(1), 120));
+    belt.right_lane.items[0] = Some((item(2), 120));
+    world.add_belt(belt);
+    world.add_belt(SingleBelt::new(next, BeltType::Regular, None, None));
+
+    world.tick();
+    assert_eq!(world.belts[&curve].left_lane.item_count(), 0);
+    assert_eq!(get_positions(&world.belts[&next].left_lane), vec![0]);
+    assert_eq!(get_positions(&world.belts[&curve].right_lane), vec![128]);
+}
+
 #[test]
 fn test_world_creation() {
     let world = World::new();
@@ -1543,3 +1699,217 @@ fn test_edge_case_zero_gap_attempt() {
         println!("Resulting positions: {positions:?}");
     }
 }
+
+#[test]
+fn test_disabled_lane_keeps_items_in_place() {
+    let mut lane = SingleBeltLane::new(BeltType::Fast, Some(Coordinate::new(1, 0)));
+    lane.items[0] = Some((item(1), 250));
+    lane.items[1] = Some((item(2), 100));
+    lane.enabled = false;
+
+    assert!(lane.tick_and_get_transfers().is_empty());
+    assert_eq!(get_positions(&lane), vec![100, 250]);
+
+    lane.enabled = true;
+    assert_eq!(
+        lane.tick_and_get_transfers(),
+        vec![(item(1), 10, quality::Quality::Normal, 1)]
+    );
+}
+
+#[test]
+fn test_stacks_move_and_transfer_as_one() {
+    let mut lane = SingleBeltLane::new(BeltType::Regular, Some(Coordinate::new(1, 0)));
+    assert!(lane.accept_stack(item(1), quality::Quality::Normal, 4, 200));
+    // Heights above four, or of nothing at all, are turned away
+    assert!(!lane.accept_stack(item(2), quality::Quality::Normal, 9, 0));
+    assert!(!lane.accept_stack(item(2), quality::Quality::Normal, 0, 0));
+    assert!(lane.accept_stack(item(2), quality::Quality::Normal, 4, 0));
+    assert_eq!(lane.item_count(), 8);
+    assert_eq!(lane.accepted, 8);
+    assert_eq!(lane.positions(), vec![0, 200]);
+    // Two stacks take two of the four places of a compressed lane
+    assert!((lane.compression() - 0.5).abs() < f64::EPSILON);
+
+    for _ in 0..6 {
+        assert!(lane.tick_and_get_transfers().is_empty());
+    }
+    assert_eq!(
+        lane.tick_and_get_transfers(),
+        vec![(item(1), 0, quality::Quality::Normal, 4)]
+    );
+    assert_eq!(lane.item_count(), 4);
+}
+
+#[test]
+fn test_take_from_stack() {
+    let mut lane = SingleBeltLane::new(BeltType::Regular, None);
+    assert!(lane.accept_stack(item(1), quality::Quality::Normal, 3, 100));
+    assert_eq!(lane.take_from_stack(0, 2), 2);
+    assert_eq!(lane.stack_height(0), 1);
+    assert_eq!(lane.take_from_stack(0, 2), 1);
+    assert_eq!(lane.items[0], None);
+    assert_eq!(lane.stack_height(0), 0);
+}
+
+#[test]
+fn test_stacked_belt_carries_whole_stacks_to_the_next_belt() {
+    let mut world = World::new();
+    let first = Coordinate::new(0, 0);
+    let second = Coordinate::new(1, 0);
+    let mut belt = SingleBelt::new(first, BeltType::Turbo, Some(second), Some(second));
+    assert!(
+        belt.left_lane
+            .accept_stack(item(1), quality::Quality::Normal, 4, 250)
+    );
+    world.add_belt(belt);
+    world.add_belt(SingleBelt::new(second, BeltType::Turbo, None, None));
+
+    world.tick();
+    assert_eq!(world.belt_item_count(first), 0);
+    assert_eq!(world.belt_item_count(second), 4);
+}
+
+#[test]
+fn test_stacking_multiplies_belt_throughput() {
+    assert!((BeltType::Turbo.item_throughput_per_second_one_lane(1) - 30.0).abs() < f32::EPSILON);
+    assert!(
+        (BeltType::Turbo.item_throughput_per_second_one_lane(MAX_STACK_HEIGHT) - 120.0).abs()
+            < f32::EPSILON
+    );
+}
+
+#[test]
+fn test_modded_belt_speeds() {
+    let hyper = BeltType::Modded {
+        positions_per_tick: 45,
+    };
+    assert_eq!(hyper.positions_per_tick(), 45);
+    assert!((hyper.tiles_traveled_per_second() - 10.546_875).abs() < f32::EPSILON);
+    assert!((hyper.item_throughput_per_second_one_lane(1) - 42.1875).abs() < f32::EPSILON);
+    assert!((BeltType::Regular.tiles_traveled_per_second() - 1.875).abs() < f32::EPSILON);
+    assert!((BeltType::Express.item_throughput_per_second_one_lane(1) - 22.5).abs() < f32::EPSILON);
+
+    let mut lane = SingleBeltLane::new(hyper, None);
+    lane.items[0] = Some((item(1), 10));
+    lane.tick_and_get_transfers();
+    assert_eq!(get_positions(&lane), vec![55]);
+}
+
+#[test]
+fn test_belt_faster_than_item_spacing_keeps_items_apart() {
+    let warp = BeltType::Modded {
+        positions_per_tick: 100,
+    };
+    let mut lane = SingleBeltLane::new(warp, None);
+    lane.items[0] = Some((item(1), 128));
+    lane.items[1] = Some((item(2), 64));
+    lane.items[2] = Some((item(3), 0));
+
+    lane.tick_and_get_transfers();
+    assert_eq!(get_positions(&lane), vec![100, 164, 228]);
+    // The front item stops at the end, the others close up behind it
+    lane.tick_and_get_transfers();
+    assert_eq!(get_positions(&lane), vec![127, 191, 255]);
+}
+
+#[test]
+fn test_belt_faster_than_item_spacing_transfers_several_items_a_tick() {
+    let warp = BeltType::Modded {
+        positions_per_tick: 130,
+    };
+    let mut world = World::new();
+    let first = Coordinate::new(0, 0);
+    let second = Coordinate::new(1, 0);
+    let mut belt = SingleBelt::new(first, warp, Some(second), Some(second));
+    belt.left_lane.items[0] = Some((item(1), 200));
+    belt.left_lane.items[1] = Some((item(2), 136));
+    world.add_belt(belt);
+    world.add_belt(SingleBelt::new(second, warp, None, None));
+
+    world.tick();
+    assert_eq!(world.belt_item_count(first), 0);
+    assert_eq!(get_positions(&world.belts[&second].left_lane), vec![10, 74]);
+}
+
+#[test]
+fn test_fast_belt_carries_items_over_short_lanes() {
+    let warp = BeltType::Modded {
+        positions_per_tick: 200,
+    };
+    let mut world = World::new();
+    let first = Coordinate::new(0, 0);
+    let short = Coordinate::new(1, 0);
+    let last = Coordinate::new(2, 0);
+    let mut belt = SingleBelt::new(first, warp, Some(short), Some(short));
+    belt.left_lane.items[0] = Some((item(1), 250));
+    world.add_belt(belt);
+    world
+        .add_belt(SingleBelt::new(short, warp, Some(last), Some(last)).with_lane_lengths(100, 100));
+    world.add_belt(SingleBelt::new(last, warp, None, None));
+
+    // 194 positions into the short lane is 94 into the one after it
+    world.tick();
+    assert_eq!(world.belt_item_count(short), 0);
+    assert_eq!(get_positions(&world.belts[&last].left_lane), vec![94]);
+
+    // Without room further on, the item stops at the end of the short lane
+    let mut belt = SingleBelt::new(first, warp, Some(short), Some(short));
+    belt.left_lane.items[0] = Some((item(2), 250));
+    world.add_belt(belt);
+    let full = world.belts.get_mut(&last).expect("Belt not found");
+    for (slot, position) in [0, 64, 128, 192].into_iter().enumerate() {
+        full.left_lane.items[slot + 1] = Some((item(3), position));
+        full.right_lane.items[slot + 1] = Some((item(3), position));
+    }
+    full.right_lane.items[0] = Some((item(3), 255));
+    full.set_enabled(false);
+    world.tick();
+    assert_eq!(get_positions(&world.belts[&short].left_lane), vec![99]);
+}
+
+#[test]
+fn test_fast_belt_carries_items_over_several_belts() {
+    let light = BeltType::Modded {
+        positions_per_tick: 600,
+    };
+    let mut world = World::new();
+    for x in 0..4 {
+        let next = (x < 3).then(|| Coordinate::new(x + 1, 0));
+        world.add_belt(SingleBelt::new(Coordinate::new(x, 0), light, next, next));
+    }
+    world
+        .get_lane_mut(Coordinate::new(0, 0), true)
+        .expect("Belt not found")
+        .items[0] = Some((item(1), 250));
+
+    // 850 positions is three belts and 82 positions further
+    world.tick();
+    assert_eq!(
+        get_positions(&world.belts[&Coordinate::new(3, 0)].left_lane),
+        vec![82]
+    );
+}
+
+#[test]
+fn test_fast_belt_goes_round_a_loop_at_most_once_a_tick() {
+    let light = BeltType::Modded {
+        positions_per_tick: u32::MAX,
+    };
+    let corners = [(0, 0), (1, 0), (1, 1), (0, 1)];
+    let mut world = World::new();
+    for (i, (x, y)) in corners.iter().enumerate() {
+        let (nx, ny) = corners[(i + 1) % corners.len()];
+        let next = Some(Coordinate::new(nx, ny));
+        world.add_belt(SingleBelt::new(Coordinate::new(*x, *y), light, next, next));
+    }
+    let start = Coordinate::new(0, 0);
+    world
+        .get_lane_mut(start, true)
+        .expect("Belt not found")
+        .items[0] = Some((item(1), 0));
+
+    // Once round, the item stops at the end of the belt it left
+    world.tick();
+    assert_eq!(get_positions(&world.belts[&start].left_lane), vec![255]);
+}
@@ -0,0 +1,308 @@
+//! Interactive terminal mode for stepping through a simulation.
+//!
+//! Commands are read a line at a time, so this works in any terminal without
+//! switching it to raw mode. While the simulation is running, input is read on a
+//! separate thread so `pause` takes effect between two ticks.
+
+use std::{
+    fmt::Write as _,
+    io::{BufRead, Write as _},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use super::{
+    BeltType, Coordinate, Direction, Item, World, ascii,
+    belt_tiers::BeltTiers,
+    events::{Event, EventFilter, SubscriberId},
+};
+
+/// Time between two ticks while running
+const RUN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Most recent events at the cursor shown below the inspector
+const MAX_SHOWN_EVENTS: usize = 8;
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+const HELP: &str = "\
+Commands:
+  n [count]                 step one or `count` ticks (an empty line steps once)
+  r | run, p | pause        run or pause the simulation
+  h/j/k/l                   move the cursor west/south/north/east
+  add <l|r> <item> <pos>    put an item on the left or right lane of the belt under the cursor
+  clear [l|r]               remove all items from one or both lanes
+  type <yellow|red|blue|green|modded tier>  change the tier of the belt under the cursor
+  ? | help                  show this help
+  q | quit                  exit";
+
+/// A parsed line of user input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Step(u32),
+    Run,
+    Pause,
+    Move(Direction),
+    Add {
+        is_left: bool,
+        item: Item,
+        position: u32,
+    },
+    Clear {
+        /// `None` clears both lanes
+        is_left: Option<bool>,
+    },
+    SetType(BeltType),
+    Help,
+    Quit,
+}
+
+fn parse_lane(word: &str) -> Result<bool, String> {
+    match word {
+        "l" | "left" => Ok(true),
+        "r" | "right" => Ok(false),
+        _ => Err(format!("Unknown lane `{word}`, expected `l` or `r`")),
+    }
+}
+
+/// A tier of the base game, or one of `tiers` added by mods
+fn parse_belt_type(word: &str, tiers: &BeltTiers) -> Result<BeltType, String> {
+    match word {
+        "yellow" | "regular" => Ok(BeltType::Regular),
+        "red" | "fast" => Ok(BeltType::Fast),
+        "blue" | "express" => Ok(BeltType::Express),
+        "green" | "turbo" => Ok(BeltType::Turbo),
+        _ => tiers
+            .get(word)
+            .ok_or_else(|| format!("Unknown belt type `{word}`")),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(word: Option<&str>, what: &str) -> Result<T, String> {
+    let word = word.ok_or_else(|| format!("Missing {what}"))?;
+    word.parse().map_err(|_| format!("Invalid {what} `{word}`"))
+}
+
+impl Command {
+    /// Parses one line of input, knowing the modded belt tiers of `tiers`. An empty
+    /// line steps a single tick.
+    pub fn parse(line: &str, tiers: &BeltTiers) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(Self::Step(1));
+        };
+        let command = match name {
+            "n" | "next" | "step" => match words.next() {
+                Some(count) => Self::Step(parse_number(Some(count), "tick count")?),
+                None => Self::Step(1),
+            },
+            "r" | "run" => Self::Run,
+            "p" | "pause" => Self::Pause,
+            "h" => Self::Move(Direction::West),
+            "j" => Self::Move(Direction::South),
+            "k" => Self::Move(Direction::North),
+            "l" => Self::Move(Direction::East),
+            "add" => {
+                let is_left = parse_lane(words.next().unwrap_or_default())?;
+                let item = parse_number(words.next(), "item")?;
+                let position = parse_number(words.next(), "position")?;
+                Self::Add {
+                    is_left,
+                    item,
+                    position,
+                }
+            }
+            "clear" => Self::Clear {
+                is_left: words.next().map(parse_lane).transpose()?,
+            },
+            "type" => Self::SetType(parse_belt_type(words.next().unwrap_or_default(), tiers)?),
+            "?" | "help" => Self::Help,
+            "q" | "quit" | "exit" => Self::Quit,
+            _ => return Err(format!("Unknown command `{name}`, type `help` for a list")),
+        };
+        if let Some(extra) = words.next() {
+            return Err(format!("Unexpected argument `{extra}`"));
+        }
+        Ok(command)
+    }
+}
+
+/// State of an interactive session
+pub struct App {
+    pub world: World,
+    pub cursor: Coordinate,
+    pub running: bool,
+    /// Feedback from the last command, shown below the grid
+    pub message: String,
+    subscriber: SubscriberId,
+    /// Events of the last step, filtered by the cursor when drawn
+    pub recent_events: Vec<Event>,
+}
+
+impl App {
+    pub fn new(mut world: World) -> Self {
+        let cursor = world.bounds().map_or(Coordinate::new(0, 0), |(min, _)| min);
+        let subscriber = world.events.subscribe(EventFilter::default());
+        Self {
+            world,
+            cursor,
+            running: false,
+            message: String::new(),
+            subscriber,
+            recent_events: Vec::new(),
+        }
+    }
+
+    pub fn step(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.world.tick();
+        }
+        self.recent_events = self.world.events.take(self.subscriber);
+    }
+
+    /// Applies a command. Returns `false` once the session should end.
+    pub fn handle(&mut self, command: Command) -> bool {
+        self.message.clear();
+        match command {
+            Command::Step(ticks) => {
+                self.running = false;
+                self.step(ticks);
+            }
+            Command::Run => self.running = true,
+            Command::Pause => self.running = false,
+            Command::Move(direction) => self.cursor = self.cursor.neighbor(direction),
+            Command::Add {
+                is_left,
+                item,
+                position,
+            } => match self.world.get_lane_mut(self.cursor, is_left) {
+                Some(lane) => {
+                    if !lane.accept_item(item, position) {
+                        self.message = "No room for the item on that lane".to_string();
+                    }
+                }
+                None => self.message = "No belt under the cursor".to_string(),
+            },
+            Command::Clear { is_left } => match self.world.belts.get_mut(&self.cursor) {
+                Some(belt) => {
+                    if is_left != Some(false) {
+                        belt.left_lane.clear();
+                    }
+                    if is_left != Some(true) {
+                        belt.right_lane.clear();
+                    }
+                }
+                None => self.message = "No belt under the cursor".to_string(),
+            },
+            Command::SetType(belt_type) => match self.world.belts.get_mut(&self.cursor) {
+                Some(belt) => {
+                    belt.left_lane.belt_type = belt_type;
+                    belt.right_lane.belt_type = belt_type;
+                }
+                None => self.message = "No belt under the cursor".to_string(),
+            },
+            Command::Help => HELP.clone_into(&mut self.message),
+            Command::Quit => return false,
+        }
+        true
+    }
+
+    /// Draws the whole screen: status line, grid, inspector and messages
+    pub fn draw(&self) -> String {
+        let mut out = String::new();
+        let state = if self.running { "running" } else { "paused" };
+        // Writing into a String cannot fail
+        let _ = writeln!(
+            out,
+            "Tick {} [{state}] cursor ({}, {})\n",
+            self.world.ticks, self.cursor.x, self.cursor.y
+        );
+        out.push_str(&ascii::render_world(
+            &self.world,
+            ascii::AsciiOptions {
+                color: true,
+                cursor: Some(self.cursor),
+            },
+        ));
+        out.push('\n');
+
+        match self.world.belts.get(&self.cursor) {
+            Some(belt) => {
+                let _ = writeln!(out, "{:?} belt", belt.left_lane.belt_type);
+                for is_left in [true, false] {
+                    if let Some(lane) = ascii::render_lane_at(&self.world, self.cursor, is_left) {
+                        out.push_str(&lane);
+                    }
+                }
+            }
+            None => out.push_str("No belt under the cursor\n"),
+        }
+
+        let events: Vec<&Event> = self
+            .recent_events
+            .iter()
+            .filter(|event| event.involves(self.cursor))
+            .collect();
+        if !events.is_empty() {
+            out.push_str("\nEvents at the cursor:\n");
+            for event in &events[events.len().saturating_sub(MAX_SHOWN_EVENTS)..] {
+                let _ = writeln!(out, "  {event}");
+            }
+        }
+
+        if !self.message.is_empty() {
+            let _ = writeln!(out, "\n{}", self.message);
+        }
+        out
+    }
+}
+
+/// Runs an interactive session on stdin and stdout until the user quits
+/// or the input is closed
+pub fn run(world: World) {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut app = App::new(world);
+    app.message = "Type `help` for a list of commands".to_string();
+    loop {
+        print!("{CLEAR_SCREEN}{}\n> ", app.draw());
+        let _ = std::io::stdout().flush();
+
+        let line = if app.running {
+            match receiver.recv_timeout(RUN_INTERVAL) {
+                Ok(line) => line,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    app.step(1);
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match receiver.recv() {
+                Ok(line) => line,
+                Err(_) => break,
+            }
+        };
+
+        match Command::parse(&line, &app.world.belt_tiers) {
+            Ok(command) => {
+                if !app.handle(command) {
+                    break;
+                }
+            }
+            Err(err) => app.message = err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,199 @@
+use super::*;
+use crate::{SingleBelt, quality::Quality};
+use std::num::NonZeroUsize;
+
+fn item(id: usize) -> Item {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+/// Parses `line` without any modded belt tiers
+fn parse(line: &str) -> Result<Command, String> {
+    Command::parse(line, &BeltTiers::default())
+}
+
+fn app_with_line() -> App {
+    let mut world = World::new();
+    let coord1 = Coordinate::new(0, 0);
+    let coord2 = Coordinate::new(1, 0);
+    world.add_belt(SingleBelt::new(coord2, BeltType::Regular, None, None));
+    world.add_belt(SingleBelt::new(
+        coord1,
+        BeltType::Regular,
+        Some(coord2),
+        Some(coord2),
+    ));
+    App::new(world)
+}
+
+fn positions(app: &App, coord: Coordinate, is_left: bool) -> Vec<u32> {
+    let belt = app.world.belts.get(&coord).expect("Belt not found");
+    let lane = if is_left {
+        &belt.left_lane
+    } else {
+        &belt.right_lane
+    };
+    lane.items.iter().flatten().map(|(_, pos)| *pos).collect()
+}
+
+#[test]
+fn test_parse_commands() {
+    assert_eq!(parse(""), Ok(Command::Step(1)));
+    assert_eq!(parse("n 10"), Ok(Command::Step(10)));
+    assert_eq!(parse("run"), Ok(Command::Run));
+    assert_eq!(parse("p"), Ok(Command::Pause));
+    assert_eq!(parse("k"), Ok(Command::Move(Direction::North)));
+    assert_eq!(
+        parse("add r 3 128"),
+        Ok(Command::Add {
+            is_left: false,
+            item: item(3),
+            position: 128
+        })
+    );
+    assert_eq!(
+        parse("clear l"),
+        Ok(Command::Clear {
+            is_left: Some(true)
+        })
+    );
+    assert_eq!(parse("clear"), Ok(Command::Clear { is_left: None }));
+    assert_eq!(parse("type blue"), Ok(Command::SetType(BeltType::Express)));
+    assert_eq!(parse("q"), Ok(Command::Quit));
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!(parse("jump").is_err());
+    assert!(parse("n many").is_err());
+    assert!(parse("add x 1 10").is_err());
+    // Item ids start at 1
+    assert!(parse("add l 0 10").is_err());
+    assert!(parse("add l 1").is_err());
+    assert!(parse("type purple").is_err());
+    assert!(parse("run now").is_err());
+}
+
+#[test]
+fn test_type_accepts_modded_tiers() {
+    let tiers = BeltTiers::parse("hyper-belt 45").expect("valid tiers");
+    assert_eq!(
+        Command::parse("type hyper-belt", &tiers),
+        Ok(Command::SetType(BeltType::Modded {
+            positions_per_tick: 45
+        }))
+    );
+    assert!(parse("type hyper-belt").is_err());
+}
+
+#[test]
+fn test_step_advances_world() {
+    let mut app = app_with_line();
+    let origin = Coordinate::new(0, 0);
+    assert!(app.handle(Command::Add {
+        is_left: true,
+        item: item(1),
+        position: 10
+    }));
+    assert!(app.handle(Command::Step(3)));
+    assert_eq!(app.world.ticks, 3);
+    assert_eq!(positions(&app, origin, true), vec![34]);
+}
+
+#[test]
+fn test_cursor_moves_and_edits_apply_under_it() {
+    let mut app = app_with_line();
+    assert_eq!(app.cursor, Coordinate::new(0, 0));
+    app.handle(Command::Move(Direction::East));
+    assert_eq!(app.cursor, Coordinate::new(1, 0));
+
+    app.handle(Command::SetType(BeltType::Turbo));
+    let belt = app.world.belts.get(&app.cursor).expect("Belt not found");
+    assert_eq!(belt.left_lane.belt_type, BeltType::Turbo);
+    assert_eq!(belt.right_lane.belt_type, BeltType::Turbo);
+
+    app.handle(Command::Add {
+        is_left: false,
+        item: item(2),
+        position: 100,
+    });
+    assert_eq!(positions(&app, app.cursor, false), vec![100]);
+    app.handle(Command::Clear {
+        is_left: Some(true),
+    });
+    assert_eq!(positions(&app, app.cursor, false), vec![100]);
+    app.handle(Command::Clear { is_left: None });
+    assert!(positions(&app, app.cursor, false).is_empty());
+}
+
+#[test]
+fn test_clear_resets_quality_and_stacks() {
+    let mut app = app_with_line();
+    let lane = app
+        .world
+        .get_lane_mut(app.cursor, true)
+        .expect("Belt not found");
+    assert!(lane.accept_stack(item(1), Quality::Epic, 3, 0));
+    app.handle(Command::Clear { is_left: None });
+
+    // An item put straight into the slot afterwards is a single normal one
+    let lane = app
+        .world
+        .get_lane_mut(app.cursor, true)
+        .expect("Belt not found");
+    lane.items[0] = Some((item(2), 10));
+    assert_eq!(lane.stack_height(0), 1);
+    assert_eq!(lane.quality(0), Quality::Normal);
+}
+
+#[test]
+fn test_edits_without_belt_report_message() {
+    let mut app = app_with_line();
+    app.handle(Command::Move(Direction::South));
+    app.handle(Command::SetType(BeltType::Fast));
+    assert_eq!(app.message, "No belt under the cursor");
+    assert!(app.draw().contains("No belt under the cursor"));
+}
+
+#[test]
+fn test_run_pause_and_quit() {
+    let mut app = app_with_line();
+    app.handle(Command::Run);
+    assert!(app.running);
+    assert!(app.draw().starts_with("Tick 0 [running] cursor (0, 0)"));
+    // Stepping manually pauses a running simulation
+    app.handle(Command::Step(1));
+    assert!(!app.running);
+    assert!(!app.handle(Command::Quit));
+}
+
+#[test]
+fn test_draw_inspects_both_lanes() {
+    let mut app = app_with_line();
+    app.handle(Command::Add {
+        is_left: true,
+        item: item(7),
+        position: 0,
+    });
+    let screen = app.draw();
+    assert!(screen.contains("Regular belt"));
+    assert!(screen.contains("(0, 0) left lane:\n7..."));
+    assert!(screen.contains("(0, 0) right lane:\n...."));
+}
+
+#[test]
+fn test_draw_shows_events_at_cursor() {
+    let mut app = app_with_line();
+    app.handle(Command::Add {
+        is_left: true,
+        item: item(4),
+        position: 250,
+    });
+    app.handle(Command::Step(1));
+    // The item left the belt under the cursor for the next one
+    let screen = app.draw();
+    assert!(screen.contains("Events at the cursor:"));
+    assert!(screen.contains("item 4 transferred from (0, 0) left lane to (1, 0) left lane at 2"));
+
+    app.handle(Command::Move(Direction::South));
+    assert!(!app.draw().contains("Events at the cursor:"));
+}
@@ -0,0 +1,314 @@
+//! Checks a simulated layout against the [`Plan`] it was designed from.
+//!
+//! The world first runs until it settles into a periodic state, found the same way as
+//! by [`World::run_until_periodic`], so belts and buffers can fill. Then it runs for a
+//! measurement window of whole cycles. During the window every machine counts its
+//! crafts and how often it waited for ingredients or for room for its products, and
+//! every watched belt counts the items of its item entering it and how often items
+//! on it stood still.
+//! Comparing the measured rates with the plan shows which part of a layout falls
+//! short and why.
+
+use std::collections::HashMap;
+
+use super::{
+    Coordinate, Item, TICKS_PER_SECOND, World,
+    crafting::MachineStatus,
+    ratio::{Material, Plan},
+    slots::Slots,
+};
+
+/// How long to run and which belts to watch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validation {
+    /// Most ticks to wait for the world to settle; measuring starts after them
+    /// even if it has not
+    pub max_warmup_ticks: u64,
+    /// Shortest measurement window, stretched to whole cycles once the world settled
+    pub window_ticks: u64,
+    /// Belts to measure and the item each of them is meant to carry, which decides
+    /// its planned rate
+    pub belts: Vec<(Coordinate, Item)>,
+}
+
+#[allow(dead_code)]
+impl Validation {
+    pub const fn new(max_warmup_ticks: u64, window_ticks: u64) -> Self {
+        Self {
+            max_warmup_ticks,
+            window_ticks,
+            belts: Vec::new(),
+        }
+    }
+
+    /// Watches the belt at `coordinate`, which should carry `item`. Belts watched for
+    /// the same item share its planned rate evenly. A belt carrying several items can
+    /// be watched once for each.
+    pub fn belt(mut self, coordinate: Coordinate, item: Item) -> Self {
+        self.belts.push((coordinate, item));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineReport {
+    pub coordinate: Coordinate,
+    pub recipe: String,
+    /// This machine's share of its recipe's planned crafts per second
+    pub planned: f64,
+    /// Crafts per second during the window
+    pub measured: f64,
+    /// Fraction of the window spent waiting for ingredients
+    pub starved: f64,
+    /// Fraction of the window spent waiting for room for products
+    pub blocked: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipeReport {
+    pub name: String,
+    /// Crafts per second
+    pub planned: f64,
+    pub measured: f64,
+    /// Machines crafting the recipe in the world
+    pub machines: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeltReport {
+    pub coordinate: Coordinate,
+    pub item: Item,
+    /// This belt's share of the planned items of `item` per second
+    pub planned: f64,
+    /// Items of `item` accepted onto the belt per second
+    pub measured: f64,
+    /// Fraction of the window during which items on the belt stood still
+    pub stalled: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    pub recipes: Vec<RecipeReport>,
+    /// Machines sorted by their coordinate
+    pub machines: Vec<MachineReport>,
+    pub belts: Vec<BeltReport>,
+    /// Tick count of the world when the cycle it settled into began, `None` if it did
+    /// not settle during the warm-up
+    pub settled: Option<u64>,
+    /// Length of the measurement window in ticks
+    pub window_ticks: u64,
+}
+
+#[allow(dead_code)]
+impl ValidationReport {
+    /// Whether every recipe and belt reaches its planned rate, allowing it to fall
+    /// short by `tolerance`, e.g. 0.01 for 1%
+    pub fn achieves_plan(&self, tolerance: f64) -> bool {
+        let reaches = |planned: f64, measured: f64| measured >= planned * (1.0 - tolerance);
+        self.recipes
+            .iter()
+            .all(|recipe| reaches(recipe.planned, recipe.measured))
+            && self
+                .belts
+                .iter()
+                .all(|belt| reaches(belt.planned, belt.measured))
+    }
+
+    /// Machines that spent more than `threshold` of the window waiting for ingredients
+    pub fn starved(&self, threshold: f64) -> impl Iterator<Item = &MachineReport> {
+        self.machines
+            .iter()
+            .filter(move |machine| machine.starved > threshold)
+    }
+
+    /// Machines that spent more than `threshold` of the window waiting for room
+    pub fn blocked(&self, threshold: f64) -> impl Iterator<Item = &MachineReport> {
+        self.machines
+            .iter()
+            .filter(move |machine| machine.blocked > threshold)
+    }
+}
+
+/// Share of the planned rate of `item` for each belt watched for it
+#[allow(clippy::cast_precision_loss)]
+fn planned_on_belt(plan: &Plan, validation: &Validation, item: Item) -> f64 {
+    let material = Material::Item(item);
+    let rate = plan
+        .outputs
+        .iter()
+        .chain(&plan.inputs)
+        .find(|flow| flow.material == material)
+        .map_or(0.0, |flow| flow.rate);
+    let sharing = validation
+        .belts
+        .iter()
+        .filter(|(_, other)| *other == item)
+        .count();
+    rate / sharing.max(1) as f64
+}
+
+/// Item positions of both lanes of a belt
+type BeltItems = [Slots<Option<(Item, u32)>>; 2];
+
+/// Whether an item on a lane kept its position over the tick
+fn stood_still(before: &BeltItems, after: &BeltItems) -> bool {
+    before.iter().zip(after).any(|(before_lane, after_lane)| {
+        before_lane
+            .iter()
+            .zip(after_lane)
+            .any(|(before, after)| before.is_some() && before == after)
+    })
+}
+
+/// Counts gathered over the measurement window
+#[derive(Debug, Default)]
+struct Counts {
+    starved: HashMap<Coordinate, u64>,
+    blocked: HashMap<Coordinate, u64>,
+    stalled: HashMap<Coordinate, u64>,
+}
+
+impl World {
+    fn belt_items(&self, coordinate: Coordinate) -> Option<BeltItems> {
+        self.belts
+            .get(&coordinate)
+            .map(|belt| [belt.left_lane.items.clone(), belt.right_lane.items.clone()])
+    }
+
+    /// Items of `item` accepted onto both lanes of the belt at `coordinate` so far
+    fn belt_accepted(&self, coordinate: Coordinate, item: Item) -> u64 {
+        self.belts.get(&coordinate).map_or(0, |belt| {
+            belt.left_lane.accepted_of(item) + belt.right_lane.accepted_of(item)
+        })
+    }
+
+    /// Ticks once while counting what `validation` measures
+    fn tick_measured(&mut self, validation: &Validation, counts: &mut Counts) {
+        let before: Vec<Option<BeltItems>> = validation
+            .belts
+            .iter()
+            .map(|(coordinate, _)| self.belt_items(*coordinate))
+            .collect();
+        self.tick();
+        for ((coordinate, _), before) in validation.belts.iter().zip(before) {
+            if let (Some(before), Some(after)) = (before, self.belt_items(*coordinate))
+                && stood_still(&before, &after)
+            {
+                *counts.stalled.entry(*coordinate).or_default() += 1;
+            }
+        }
+        for (coordinate, machine) in &self.machines {
+            let count = match machine.status() {
+                MachineStatus::MissingIngredients => &mut counts.starved,
+                MachineStatus::OutputFull => &mut counts.blocked,
+                MachineStatus::Working | MachineStatus::NoRecipe => continue,
+            };
+            *count.entry(*coordinate).or_default() += 1;
+        }
+    }
+
+    /// Runs the world through the warm-up and the measurement window of `validation`
+    /// and compares what it measured with `plan`
+    #[allow(dead_code, clippy::cast_precision_loss)]
+    pub fn validate(&mut self, plan: &Plan, validation: &Validation) -> ValidationReport {
+        let steady = self.run_until_periodic(validation.max_warmup_ticks);
+        let window_ticks = steady.as_ref().map_or(validation.window_ticks, |steady| {
+            validation.window_ticks.div_ceil(steady.period).max(1) * steady.period
+        });
+        let crafts_before: HashMap<Coordinate, u64> = self
+            .machines
+            .iter()
+            .map(|(coordinate, machine)| (*coordinate, machine.crafts))
+            .collect();
+        let accepted_before: Vec<u64> = validation
+            .belts
+            .iter()
+            .map(|(coordinate, item)| self.belt_accepted(*coordinate, *item))
+            .collect();
+        let mut counts = Counts::default();
+        for _ in 0..window_ticks {
+            self.tick_measured(validation, &mut counts);
+        }
+
+        let ticks = window_ticks.max(1) as f64;
+        let seconds = ticks / TICKS_PER_SECOND;
+        let recipe_of = |coordinate: &Coordinate| {
+            self.machines[coordinate]
+                .recipe
+                .as_ref()
+                .map(|recipe| recipe.name.clone())
+        };
+        let mut coordinates: Vec<Coordinate> = self.machines.keys().copied().collect();
+        coordinates.sort_by_key(|c| (c.y, c.x));
+
+        let recipes: Vec<RecipeReport> = plan
+            .recipes
+            .iter()
+            .map(|planned| {
+                let crafting: Vec<&Coordinate> = coordinates
+                    .iter()
+                    .filter(|c| recipe_of(c).as_ref() == Some(&planned.name))
+                    .collect();
+                let crafts: u64 = crafting
+                    .iter()
+                    .map(|c| self.machines[c].crafts - crafts_before.get(c).copied().unwrap_or(0))
+                    .sum();
+                RecipeReport {
+                    name: planned.name.clone(),
+                    planned: planned.crafts_per_second,
+                    measured: crafts as f64 / seconds,
+                    machines: crafting.len(),
+                }
+            })
+            .collect();
+
+        let machines = coordinates
+            .iter()
+            .filter_map(|coordinate| {
+                let name = recipe_of(coordinate)?;
+                let planned = recipes
+                    .iter()
+                    .find(|recipe| recipe.name == name)
+                    .map_or(0.0, |recipe| recipe.planned / recipe.machines as f64);
+                let crafts = self.machines[coordinate].crafts
+                    - crafts_before.get(coordinate).copied().unwrap_or(0);
+                let fraction = |count: &HashMap<Coordinate, u64>| {
+                    count.get(coordinate).copied().unwrap_or(0) as f64 / ticks
+                };
+                Some(MachineReport {
+                    coordinate: *coordinate,
+                    recipe: name,
+                    planned,
+                    measured: crafts as f64 / seconds,
+                    starved: fraction(&counts.starved),
+                    blocked: fraction(&counts.blocked),
+                })
+            })
+            .collect();
+
+        let belts = validation
+            .belts
+            .iter()
+            .zip(accepted_before)
+            .map(|((coordinate, item), accepted_before)| BeltReport {
+                coordinate: *coordinate,
+                item: *item,
+                planned: planned_on_belt(plan, validation, *item),
+                measured: (self.belt_accepted(*coordinate, *item) - accepted_before) as f64
+                    / seconds,
+                stalled: counts.stalled.get(coordinate).copied().unwrap_or(0) as f64 / ticks,
+            })
+            .collect();
+
+        ValidationReport {
+            recipes,
+            machines,
+            belts,
+            settled: steady.map(|steady| steady.start),
+            window_ticks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,151 @@
+use super::*;
+use crate::{
+    BeltType, Direction, SingleBelt,
+    crafting::{CraftingMachine, Recipe},
+    inserter::{Inserter, InserterKind},
+    power::{GeneratorKind, PoleKind},
+    ratio::{MachineSetup, RatioProblem},
+};
+use std::num::NonZeroUsize;
+
+fn id(id: usize) -> NonZeroUsize {
+    NonZeroUsize::new(id).expect("Failed to create NonZeroUsize")
+}
+
+/// Makes one ore per second out of nothing
+fn ore() -> Recipe {
+    Recipe::new("ore", 1.0).item_product(id(1), 1)
+}
+
+fn gear() -> Recipe {
+    Recipe::new("gear", 1.0)
+        .item_ingredient(id(1), 2)
+        .item_product(id(2), 1)
+}
+
+fn plan(gears_per_second: f64) -> Plan {
+    RatioProblem::new()
+        .recipe(ore(), MachineSetup::new(1.0, 0.0))
+        .recipe(gear(), MachineSetup::new(1.0, 0.0))
+        .target(Material::Item(id(2)), gears_per_second)
+        .solve(BeltType::Regular)
+        .expect("solvable")
+}
+
+/// An ore machine at (0, 0) whose output goes over the belts at (2, 0) and (3, 0)
+/// into a gear machine at (5, 0). With `sink`, the gears are taken out into a machine
+/// at (7, 0) that uses them up.
+fn layout(sink: bool) -> World {
+    let mut world = World::new();
+    world.add_machine(
+        Coordinate::new(0, 0),
+        CraftingMachine::new(1.0).with_recipe(ore()),
+    );
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(2, 0),
+        BeltType::Regular,
+        Some(Coordinate::new(3, 0)),
+        Some(Coordinate::new(3, 0)),
+    ));
+    world.add_belt(SingleBelt::new(
+        Coordinate::new(3, 0),
+        BeltType::Regular,
+        None,
+        None,
+    ));
+    world.add_machine(
+        Coordinate::new(5, 0),
+        CraftingMachine::new(1.0).with_recipe(gear()),
+    );
+    let mut inserters = vec![1, 4];
+    if sink {
+        world.add_machine(
+            Coordinate::new(7, 0),
+            CraftingMachine::new(1.0)
+                .with_recipe(Recipe::new("sink", 0.1).item_ingredient(id(2), 1)),
+        );
+        inserters.push(6);
+    }
+    for x in inserters {
+        world.add_inserter(
+            Coordinate::new(x, 0),
+            Inserter::new(InserterKind::Fast, Direction::East),
+        );
+    }
+    world
+        .power
+        .add_pole(Coordinate::new(3, 1), PoleKind::Medium);
+    for x in 0..=6 {
+        world
+            .power
+            .generators
+            .insert(Coordinate::new(x, 3), GeneratorKind::SolarPanel);
+    }
+    world
+}
+
+fn validation() -> Validation {
+    Validation::new(1200, 1200).belt(Coordinate::new(3, 0), id(1))
+}
+
+#[test]
+fn test_layout_achieves_its_plan() {
+    let plan = plan(0.5);
+    let report = layout(true).validate(&plan, &validation());
+
+    assert!(report.achieves_plan(0.1), "{report:?}");
+    assert_eq!(report.recipes.len(), 2);
+    assert!((report.recipes[1].measured - 0.5).abs() <= 0.05);
+    let belt = &report.belts[0];
+    assert!((belt.planned - 1.0).abs() < 1e-9);
+    assert!((belt.measured - 1.0).abs() <= 0.05);
+    assert_eq!(report.blocked(0.1).count(), 0);
+}
+
+#[test]
+fn test_too_few_producers_starve_the_consumer() {
+    // Two gears per second need two ore machines, but the layout has one
+    let plan = plan(1.0);
+    let report = layout(true).validate(&plan, &validation());
+
+    assert!(!report.achieves_plan(0.1));
+    let starved: Vec<Coordinate> = report.starved(0.3).map(|m| m.coordinate).collect();
+    // The sink is outside of the plan and waits on the gear machine
+    assert_eq!(starved, vec![Coordinate::new(5, 0), Coordinate::new(7, 0)]);
+    let gears = &report.machines[1];
+    assert!((gears.planned - 1.0).abs() < 1e-9);
+    assert!(gears.measured < 0.6);
+}
+
+#[test]
+fn test_missing_output_blocks_the_line() {
+    // Long enough for the gear machine and the belt in front of it to fill up
+    let validation = Validation::new(3600, 1200).belt(Coordinate::new(3, 0), id(1));
+    let report = layout(false).validate(&plan(0.5), &validation);
+
+    assert!(!report.achieves_plan(0.1), "{report:?}");
+    let blocked: Vec<Coordinate> = report.blocked(0.9).map(|m| m.coordinate).collect();
+    assert_eq!(blocked, vec![Coordinate::new(5, 0)]);
+    assert!(report.belts[0].stalled > 0.9);
+    assert!(report.belts[0].measured < 0.1);
+}
+
+#[test]
+fn test_belt_counts_only_its_item() {
+    let validation = validation().belt(Coordinate::new(3, 0), id(2));
+    let report = layout(true).validate(&plan(0.5), &validation);
+
+    // The gears are planned, but none of them pass over the ore belt
+    let gears = &report.belts[1];
+    assert!((gears.planned - 0.5).abs() < 1e-9);
+    assert!(gears.measured.abs() < 1e-9);
+    assert!((report.belts[0].measured - 1.0).abs() <= 0.05);
+}
+
+#[test]
+fn test_window_covers_whole_cycles() {
+    let report = layout(true).validate(&plan(0.5), &validation());
+
+    assert!(report.settled.is_some(), "{report:?}");
+    assert!(report.window_ticks >= 1200);
+}